@@ -0,0 +1,385 @@
+use aegis_client::{Client, FileUpload};
+use aegis_config::ConfigLoader;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::env;
+use std::fs;
+use std::io::{BufRead, BufReader};
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::Duration;
+
+const USAGE: &str = "usage: aegis-clipboard-collector [--config <file>] [--set <key=value>]... [--backend auto|x11|wayland] [--poll-ms N] [--store none|text|all] [--store-max-bytes N]";
+
+const COLLECTOR: &str = "clipboard";
+
+/// X11 targets that describe the selection rather than its content.
+const META_TARGETS: &[&str] = &["TARGETS", "TIMESTAMP", "MULTIPLE", "SAVE_TARGETS", "DELETE"];
+
+/// Preferred types for hashing, most specific first; otherwise the first
+/// advertised type is used.
+const PREFERRED_TYPES: &[&str] = &[
+    "text/plain;charset=utf-8",
+    "UTF8_STRING",
+    "text/plain",
+    "STRING",
+    "TEXT",
+    "image/png",
+];
+
+#[derive(Clone, Copy, PartialEq)]
+enum Backend {
+    X11,
+    Wayland,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum StorePolicy {
+    None,
+    Text,
+    All,
+}
+
+struct Snapshot {
+    mime_types: Vec<String>,
+    content_type: String,
+    content: Vec<u8>,
+    sha256: String,
+}
+
+struct Collector {
+    backend: Backend,
+    client: Client,
+    store: StorePolicy,
+    store_max_bytes: u64,
+    temp_dir: PathBuf,
+    stored: u64,
+    last: Option<(Vec<String>, String)>,
+}
+
+fn main() {
+    if let Err(err) = run() {
+        eprintln!("FAIL: {err}");
+        std::process::exit(1);
+    }
+}
+
+fn run() -> Result<(), String> {
+    let (loader, args) = ConfigLoader::from_args(env::args().skip(1))?;
+    let config = loader.load()?.config;
+
+    let mut backend = None;
+    let mut poll = Duration::from_millis(1000);
+    let mut store = StorePolicy::None;
+    let mut store_max_bytes = 1024 * 1024;
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--backend" => {
+                backend = match args.next().ok_or(USAGE)?.as_str() {
+                    "auto" => None,
+                    "x11" => Some(Backend::X11),
+                    "wayland" => Some(Backend::Wayland),
+                    other => return Err(format!("unsupported backend: {other}")),
+                }
+            }
+            "--poll-ms" => {
+                let value = args.next().ok_or(USAGE)?;
+                let millis = value
+                    .parse::<u64>()
+                    .ok()
+                    .filter(|millis| *millis > 0)
+                    .ok_or_else(|| format!("invalid poll interval: {value}"))?;
+                poll = Duration::from_millis(millis);
+            }
+            "--store" => {
+                store = match args.next().ok_or(USAGE)?.as_str() {
+                    "none" => StorePolicy::None,
+                    "text" => StorePolicy::Text,
+                    "all" => StorePolicy::All,
+                    other => return Err(format!("unsupported store policy: {other}")),
+                }
+            }
+            "--store-max-bytes" => {
+                let value = args.next().ok_or(USAGE)?;
+                store_max_bytes = value
+                    .parse::<u64>()
+                    .map_err(|_| format!("invalid size: {value}"))?;
+            }
+            _ => return Err(USAGE.to_string()),
+        }
+    }
+    let backend = match backend {
+        Some(backend) => backend,
+        None => detect_backend()?,
+    };
+
+    let temp_dir = config.temp_dir();
+    fs::create_dir_all(&temp_dir).map_err(|err| format!("create {}: {err}", temp_dir.display()))?;
+    let client = Client::from_config(&config, COLLECTOR);
+    client.spawn_heartbeat(COLLECTOR, config.server.heartbeat_interval_ms);
+    let mut collector = Collector {
+        backend,
+        client,
+        store,
+        store_max_bytes,
+        temp_dir,
+        stored: 0,
+        last: None,
+    };
+    match backend {
+        Backend::X11 => collector.run_x11(poll),
+        Backend::Wayland => collector.run_wayland(),
+    }
+}
+
+fn detect_backend() -> Result<Backend, String> {
+    if env::var_os("WAYLAND_DISPLAY").is_some() {
+        return Ok(Backend::Wayland);
+    }
+    if env::var_os("DISPLAY").is_some() {
+        return Ok(Backend::X11);
+    }
+    Err("no display found (DISPLAY/WAYLAND_DISPLAY unset)".to_string())
+}
+
+impl Collector {
+    /// X11 has no change notification without XFixes, so the selection is
+    /// polled. Owners that support the `TIMESTAMP` target let us skip
+    /// reading the content when ownership has not changed.
+    fn run_x11(&mut self, poll: Duration) -> Result<(), String> {
+        eprintln!("Watching the X11 clipboard every {}ms", poll.as_millis());
+        let mut last_stamp: Option<Vec<u8>> = None;
+        loop {
+            let stamp = read_x11("TIMESTAMP").filter(|stamp| !stamp.is_empty());
+            if stamp.is_none() || stamp != last_stamp {
+                last_stamp = stamp;
+                self.check();
+            }
+            thread::sleep(poll);
+        }
+    }
+
+    /// `wl-paste --watch` runs its command on every selection change; the
+    /// command's output is just a wake-up line for us.
+    fn run_wayland(&mut self) -> Result<(), String> {
+        let mut child = Command::new("wl-paste")
+            .args(["--watch", "echo", "changed"])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .spawn()
+            .map_err(|err| format!("start wl-paste: {err}"))?;
+        let stdout = child.stdout.take().ok_or("wl-paste stdout unavailable")?;
+        eprintln!("Watching the Wayland clipboard via wl-paste --watch");
+
+        for line in BufReader::new(stdout).lines() {
+            if line.is_err() {
+                break;
+            }
+            self.check();
+        }
+        let _ = child.kill();
+        let _ = child.wait();
+        Err("wl-paste exited (the compositor may not support wlr-data-control)".to_string())
+    }
+
+    fn check(&mut self) {
+        let Some(snapshot) = self.snapshot() else {
+            return;
+        };
+        let key = (snapshot.mime_types.clone(), snapshot.sha256.clone());
+        if self.last.as_ref() == Some(&key) {
+            return;
+        }
+        self.last = Some(key);
+
+        let mut payload = json!({
+            "mime_types": snapshot.mime_types,
+            "content_type": snapshot.content_type,
+            "size": snapshot.content.len(),
+            "sha256": snapshot.sha256,
+        });
+        if let Some(app) = focused_app(self.backend) {
+            payload["source_app"] = json!(app);
+        }
+        if self.should_store(&snapshot) {
+            match self.store_content(&snapshot) {
+                Ok(rel_path) => payload["rel_path"] = json!(rel_path),
+                Err(err) => eprintln!("store clipboard content: {err}"),
+            }
+        }
+        if let Err(err) = self.client.send_event("clipboard_changed", payload) {
+            eprintln!("send clipboard_changed: {err}");
+        }
+    }
+
+    fn snapshot(&self) -> Option<Snapshot> {
+        let mime_types = match self.backend {
+            Backend::X11 => read_x11("TARGETS").map(|raw| {
+                String::from_utf8_lossy(&raw)
+                    .lines()
+                    .map(str::trim)
+                    .filter(|target| !target.is_empty() && !META_TARGETS.contains(target))
+                    .map(str::to_string)
+                    .collect::<Vec<_>>()
+            })?,
+            Backend::Wayland => {
+                let output = Command::new("wl-paste")
+                    .arg("--list-types")
+                    .stderr(Stdio::null())
+                    .output()
+                    .ok()?;
+                String::from_utf8_lossy(&output.stdout)
+                    .lines()
+                    .map(str::trim)
+                    .filter(|mime| !mime.is_empty())
+                    .map(str::to_string)
+                    .collect()
+            }
+        };
+        let content_type = PREFERRED_TYPES
+            .iter()
+            .find(|preferred| mime_types.iter().any(|mime| mime == *preferred))
+            .map(|preferred| preferred.to_string())
+            .or_else(|| mime_types.first().cloned())?;
+        let content = match self.backend {
+            Backend::X11 => read_x11(&content_type)?,
+            Backend::Wayland => Command::new("wl-paste")
+                .args(["--no-newline", "--type", &content_type])
+                .stderr(Stdio::null())
+                .output()
+                .ok()
+                .filter(|output| output.status.success())?
+                .stdout,
+        };
+        let sha256 = Sha256::digest(&content)
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect();
+        Some(Snapshot {
+            mime_types,
+            content_type,
+            content,
+            sha256,
+        })
+    }
+
+    fn should_store(&self, snapshot: &Snapshot) -> bool {
+        if snapshot.content.len() as u64 > self.store_max_bytes {
+            return false;
+        }
+        match self.store {
+            StorePolicy::None => false,
+            StorePolicy::Text => is_text(&snapshot.content_type),
+            StorePolicy::All => true,
+        }
+    }
+
+    /// Copies the content into `files/clipboard/` through the core server.
+    fn store_content(&mut self, snapshot: &Snapshot) -> Result<String, String> {
+        self.stored += 1;
+        let name = format!("{:06}.{}", self.stored, extension_for(&snapshot.content_type));
+        let rel_path = format!("files/clipboard/{name}");
+        let temp_path = self
+            .temp_dir
+            .join(format!("aegis-clipboard-{}-{name}", std::process::id()));
+        fs::write(&temp_path, &snapshot.content)
+            .map_err(|err| format!("write {}: {err}", temp_path.display()))?;
+        let result = self.client.upload_file(&FileUpload {
+            source_path: temp_path.clone(),
+            rel_path: rel_path.clone(),
+            kind: "clipboard_content".to_string(),
+        });
+        let _ = fs::remove_file(&temp_path);
+        result.map(|_| rel_path)
+    }
+}
+
+fn read_x11(target: &str) -> Option<Vec<u8>> {
+    let output = Command::new("xclip")
+        .args(["-selection", "clipboard", "-o", "-t", target])
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    output.status.success().then_some(output.stdout)
+}
+
+fn is_text(content_type: &str) -> bool {
+    content_type.starts_with("text/") || matches!(content_type, "UTF8_STRING" | "STRING" | "TEXT")
+}
+
+fn extension_for(content_type: &str) -> &'static str {
+    match content_type {
+        "image/png" => "png",
+        "image/jpeg" => "jpg",
+        "text/html" => "html",
+        other if is_text(other) => "txt",
+        _ => "bin",
+    }
+}
+
+/// The app focused when the change was seen. Neither X11 nor Wayland
+/// exposes which client wrote the selection to a tool-only collector, and
+/// copying happens in the focused window in practice.
+fn focused_app(backend: Backend) -> Option<String> {
+    if env::var_os("SWAYSOCK").is_some() {
+        let output = Command::new("swaymsg")
+            .args(["-r", "-t", "get_tree"])
+            .stderr(Stdio::null())
+            .output()
+            .ok()?;
+        let tree: Value = serde_json::from_slice(&output.stdout).ok()?;
+        return find_focused(&tree);
+    }
+    if backend != Backend::X11 {
+        return None;
+    }
+
+    let output = Command::new("xprop")
+        .args(["-root", "_NET_ACTIVE_WINDOW"])
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    let line = String::from_utf8_lossy(&output.stdout);
+    let window = line.rsplit('#').next()?.trim().split(',').next()?.trim().to_string();
+    if !window.starts_with("0x") || window == "0x0" {
+        return None;
+    }
+    let output = Command::new("xprop")
+        .args(["-id", &window, "WM_CLASS"])
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    let line = String::from_utf8_lossy(&output.stdout);
+    let (_, classes) = line.split_once(" = ")?;
+    // WM_CLASS is "instance", "Class"; the class names the application
+    let classes: Vec<&str> = classes
+        .split(',')
+        .map(|class| class.trim().trim_matches('"'))
+        .collect();
+    classes
+        .get(1)
+        .or(classes.first())
+        .filter(|class| !class.is_empty())
+        .map(|class| class.to_string())
+}
+
+fn find_focused(node: &Value) -> Option<String> {
+    if node.get("focused").and_then(Value::as_bool) == Some(true) {
+        return node
+            .get("app_id")
+            .and_then(Value::as_str)
+            .or_else(|| {
+                node.get("window_properties")
+                    .and_then(|props| props.get("class"))
+                    .and_then(Value::as_str)
+            })
+            .map(str::to_string);
+    }
+    ["nodes", "floating_nodes"]
+        .iter()
+        .filter_map(|key| node.get(*key).and_then(Value::as_array))
+        .flatten()
+        .find_map(find_focused)
+}
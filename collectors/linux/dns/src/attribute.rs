@@ -0,0 +1,115 @@
+use std::fs;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+/// The process that owns the socket a query came from.
+pub struct Owner {
+    pub pid: u32,
+    pub exe: Option<String>,
+    pub app_id: String,
+}
+
+/// Finds the owner of the local UDP socket `client` via `/proc/net/udp*`
+/// and the socket links under `/proc/<pid>/fd`. Only works for clients in
+/// the same network namespace, and for other users' processes only when
+/// running as root.
+pub fn find_owner(client: SocketAddr) -> Option<Owner> {
+    let inode = find_socket_inode(client)?;
+    let pid = find_pid_for_inode(inode)?;
+    let exe = fs::read_link(format!("/proc/{pid}/exe"))
+        .ok()
+        .map(|path| path.to_string_lossy().to_string());
+    let app_id = exe
+        .as_ref()
+        .and_then(|exe| exe.rsplit('/').next().map(str::to_string))
+        .filter(|name| !name.is_empty())
+        .or_else(|| {
+            fs::read_to_string(format!("/proc/{pid}/comm"))
+                .ok()
+                .map(|comm| comm.trim().to_string())
+        })?;
+    Some(Owner { pid, exe, app_id })
+}
+
+fn find_socket_inode(client: SocketAddr) -> Option<u64> {
+    let tables: &[&str] = match client {
+        SocketAddr::V4(_) => &["/proc/net/udp", "/proc/net/udp6"],
+        SocketAddr::V6(_) => &["/proc/net/udp6"],
+    };
+    for table in tables {
+        let Ok(content) = fs::read_to_string(table) else {
+            continue;
+        };
+        for line in content.lines().skip(1) {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let (Some(local), Some(inode)) = (fields.get(1), fields.get(9)) else {
+                continue;
+            };
+            let Some((ip, port)) = parse_proc_addr(local) else {
+                continue;
+            };
+            if port != client.port() || !ip_matches(ip, client.ip()) {
+                continue;
+            }
+            if let Ok(inode) = inode.parse::<u64>() {
+                if inode != 0 {
+                    return Some(inode);
+                }
+            }
+        }
+    }
+    None
+}
+
+/// Unspecified binds and v4-mapped v6 sockets also match.
+fn ip_matches(bound: IpAddr, client: IpAddr) -> bool {
+    if bound.is_unspecified() || bound == client {
+        return true;
+    }
+    match (bound, client) {
+        (IpAddr::V6(bound), IpAddr::V4(client)) => bound.to_ipv4_mapped() == Some(client),
+        _ => false,
+    }
+}
+
+/// Addresses are the raw in-kernel words printed as hex, so each 32-bit
+/// word is in host byte order; the port is plain hex.
+fn parse_proc_addr(value: &str) -> Option<(IpAddr, u16)> {
+    let (addr, port) = value.split_once(':')?;
+    let port = u16::from_str_radix(port, 16).ok()?;
+    let mut bytes = Vec::with_capacity(16);
+    for chunk in addr.as_bytes().chunks(8) {
+        let word = u32::from_str_radix(std::str::from_utf8(chunk).ok()?, 16).ok()?;
+        bytes.extend_from_slice(&word.to_ne_bytes());
+    }
+    let ip = match bytes.len() {
+        4 => IpAddr::V4(Ipv4Addr::new(bytes[0], bytes[1], bytes[2], bytes[3])),
+        16 => {
+            let mut octets = [0u8; 16];
+            octets.copy_from_slice(&bytes);
+            IpAddr::V6(Ipv6Addr::from(octets))
+        }
+        _ => return None,
+    };
+    Some((ip, port))
+}
+
+fn find_pid_for_inode(inode: u64) -> Option<u32> {
+    let target = format!("socket:[{inode}]");
+    for entry in fs::read_dir("/proc").ok()?.flatten() {
+        let Some(pid) = entry.file_name().to_str().and_then(|name| name.parse::<u32>().ok())
+        else {
+            continue;
+        };
+        let Ok(fds) = fs::read_dir(format!("/proc/{pid}/fd")) else {
+            continue;
+        };
+        for fd in fds.flatten() {
+            if let Ok(link) = fs::read_link(fd.path()) {
+                if link.as_os_str() == target.as_str() {
+                    return Some(pid);
+                }
+            }
+        }
+    }
+    None
+}
@@ -0,0 +1,76 @@
+/// The question of a DNS query; only the first one is used, as resolvers
+/// in practice send exactly one.
+pub struct Question {
+    pub domain: String,
+    pub qtype: u16,
+}
+
+const HEADER_LEN: usize = 12;
+
+/// Parses a query packet. Responses, malformed packets and packets
+/// without a question return `None`.
+pub fn parse_query(packet: &[u8]) -> Option<Question> {
+    if packet.len() < HEADER_LEN {
+        return None;
+    }
+    let flags = u16::from_be_bytes([packet[2], packet[3]]);
+    let is_response = flags & 0x8000 != 0;
+    let qdcount = u16::from_be_bytes([packet[4], packet[5]]);
+    if is_response || qdcount == 0 {
+        return None;
+    }
+
+    let mut labels = Vec::new();
+    let mut offset = HEADER_LEN;
+    loop {
+        let len = *packet.get(offset)? as usize;
+        offset += 1;
+        if len == 0 {
+            break;
+        }
+        // Compression pointers are not valid in the first question
+        if len & 0xc0 != 0 {
+            return None;
+        }
+        let label = packet.get(offset..offset + len)?;
+        labels.push(String::from_utf8_lossy(label).to_ascii_lowercase());
+        offset += len;
+    }
+    let qtype = u16::from_be_bytes([*packet.get(offset)?, *packet.get(offset + 1)?]);
+
+    let domain = if labels.is_empty() {
+        ".".to_string()
+    } else {
+        labels.join(".")
+    };
+    Some(Question { domain, qtype })
+}
+
+pub fn qtype_name(qtype: u16) -> String {
+    match qtype {
+        1 => "A".to_string(),
+        2 => "NS".to_string(),
+        5 => "CNAME".to_string(),
+        6 => "SOA".to_string(),
+        12 => "PTR".to_string(),
+        15 => "MX".to_string(),
+        16 => "TXT".to_string(),
+        28 => "AAAA".to_string(),
+        33 => "SRV".to_string(),
+        64 => "SVCB".to_string(),
+        65 => "HTTPS".to_string(),
+        other => format!("TYPE{other}"),
+    }
+}
+
+/// A SERVFAIL answer to `query`, sent when the upstream cannot be reached
+/// so clients fail fast instead of timing out.
+pub fn servfail(query: &[u8]) -> Vec<u8> {
+    let mut reply = query.to_vec();
+    if reply.len() >= 4 {
+        // QR=1, keep opcode and RD, RA=1, RCODE=2
+        reply[2] = 0x80 | (query[2] & 0x79);
+        reply[3] = 0x80 | 0x02;
+    }
+    reply
+}
@@ -0,0 +1,314 @@
+mod attribute;
+mod dns;
+
+use aegis_client::Client;
+use aegis_config::ConfigLoader;
+use attribute::Owner;
+use serde_json::json;
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::net::{IpAddr, SocketAddr, UdpSocket};
+use std::sync::mpsc::{self, Receiver, Sender, TrySendError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const USAGE: &str = "usage: aegis-dns-collector [--config <file>] [--set <key=value>]... [--listen <ip:port>] [--upstream <ip[:port]>] [--window-ms N] [--timeout-ms N] [--workers N] [--no-attribute]";
+
+const COLLECTOR: &str = "dns";
+
+struct Observation {
+    domain: String,
+    qtype: u16,
+    owner: Option<Owner>,
+}
+
+/// A received query waiting for a worker.
+struct Query {
+    packet: Vec<u8>,
+    client: SocketAddr,
+}
+
+struct Options {
+    listen: SocketAddr,
+    upstream: Option<SocketAddr>,
+    window: Duration,
+    timeout: Duration,
+    workers: usize,
+    attribute: bool,
+}
+
+fn main() {
+    if let Err(err) = run() {
+        eprintln!("FAIL: {err}");
+        std::process::exit(1);
+    }
+}
+
+fn run() -> Result<(), String> {
+    let (loader, args) = ConfigLoader::from_args(env::args().skip(1))?;
+    let config = loader.load()?.config;
+    let options = parse_options(args)?;
+
+    let upstream = match options.upstream {
+        Some(upstream) => upstream,
+        None => system_nameserver()?,
+    };
+    if upstream == options.listen
+        || (upstream.port() == options.listen.port()
+            && options.listen.ip().is_unspecified()
+            && upstream.ip().is_loopback())
+    {
+        return Err(format!(
+            "upstream {upstream} is this stub itself; pass --upstream"
+        ));
+    }
+
+    let socket = UdpSocket::bind(options.listen)
+        .map_err(|err| format!("bind {}: {err}", options.listen))?;
+    eprintln!(
+        "DNS stub listening on {} (upstream {upstream})",
+        options.listen
+    );
+
+    let (tx, rx) = mpsc::channel::<Observation>();
+    // A fixed pool of workers relays queries; each one is busy for up to
+    // the upstream timeout, so at most `workers` queries wait in line and
+    // the rest are answered SERVFAIL right away instead of piling up.
+    let (query_tx, query_rx) = mpsc::sync_channel::<Query>(options.workers);
+    let query_rx = Arc::new(Mutex::new(query_rx));
+    for _ in 0..options.workers {
+        let reply_socket = socket
+            .try_clone()
+            .map_err(|err| format!("clone socket: {err}"))?;
+        let query_rx = Arc::clone(&query_rx);
+        let tx = tx.clone();
+        let timeout = options.timeout;
+        let attribute = options.attribute;
+        thread::spawn(move || {
+            relay_queries(&reply_socket, &query_rx, upstream, timeout, attribute, &tx)
+        });
+    }
+    drop(tx);
+
+    thread::spawn(move || {
+        let mut buf = [0u8; 4096];
+        let mut overloaded = false;
+        loop {
+            let (len, client) = match socket.recv_from(&mut buf) {
+                Ok(received) => received,
+                Err(err) => {
+                    eprintln!("recv: {err}");
+                    continue;
+                }
+            };
+            let query = Query {
+                packet: buf[..len].to_vec(),
+                client,
+            };
+            match query_tx.try_send(query) {
+                Ok(()) => overloaded = false,
+                Err(TrySendError::Full(query)) => {
+                    if !overloaded {
+                        eprintln!("all workers busy; answering SERVFAIL");
+                        overloaded = true;
+                    }
+                    // Only answer queries, so a stray response is not echoed
+                    if dns::parse_query(&query.packet).is_some() {
+                        let _ = socket.send_to(&dns::servfail(&query.packet), query.client);
+                    }
+                }
+                Err(TrySendError::Disconnected(_)) => break,
+            }
+        }
+    });
+
+    let client = Client::from_config(&config, COLLECTOR);
+    client.spawn_heartbeat(COLLECTOR, config.server.heartbeat_interval_ms);
+
+    // Each domain is reported once per app and window; repeats (TTL
+    // refreshes, A + AAAA pairs, retries) are dropped.
+    let mut last_reported: HashMap<(String, Option<String>), Instant> = HashMap::new();
+    let mut last_prune = Instant::now();
+    for observation in rx {
+        let app_id = observation.owner.as_ref().map(|owner| owner.app_id.clone());
+        let key = (observation.domain.clone(), app_id);
+        if let Some(reported) = last_reported.get(&key) {
+            if reported.elapsed() < options.window {
+                continue;
+            }
+        }
+
+        let mut payload = json!({
+            "domain": observation.domain,
+            "direction": "outbound",
+            "qtype": dns::qtype_name(observation.qtype),
+        });
+        if let Some(owner) = observation.owner.as_ref() {
+            payload["app_id"] = json!(owner.app_id);
+            payload["pid"] = json!(owner.pid);
+            if let Some(exe) = owner.exe.as_ref() {
+                payload["exe"] = json!(exe);
+            }
+        }
+        match client.send_event("net_domain", payload) {
+            Ok(_) => {
+                last_reported.insert(key, Instant::now());
+            }
+            Err(err) => eprintln!("send net_domain: {err}"),
+        }
+
+        if last_prune.elapsed() >= options.window {
+            let window = options.window;
+            last_reported.retain(|_, reported| reported.elapsed() < window);
+            last_prune = Instant::now();
+        }
+    }
+    Err("DNS listener stopped".to_string())
+}
+
+fn parse_options(args: Vec<String>) -> Result<Options, String> {
+    let mut options = Options {
+        listen: SocketAddr::from(([127, 0, 0, 1], 53)),
+        upstream: None,
+        window: Duration::from_secs(60),
+        timeout: Duration::from_secs(3),
+        workers: 16,
+        attribute: true,
+    };
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--listen" => {
+                let value = args.next().ok_or(USAGE)?;
+                options.listen = value
+                    .parse()
+                    .map_err(|_| format!("invalid listen address: {value}"))?;
+            }
+            "--upstream" => {
+                let value = args.next().ok_or(USAGE)?;
+                options.upstream = Some(
+                    parse_server(&value).ok_or_else(|| format!("invalid upstream: {value}"))?,
+                );
+            }
+            "--window-ms" => options.window = Duration::from_millis(parse_millis(args.next())?),
+            "--timeout-ms" => options.timeout = Duration::from_millis(parse_millis(args.next())?),
+            "--workers" => {
+                let value = args.next().ok_or(USAGE)?;
+                options.workers = value
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|workers| *workers > 0)
+                    .ok_or_else(|| format!("invalid worker count: {value}"))?;
+            }
+            "--no-attribute" => options.attribute = false,
+            _ => return Err(USAGE.to_string()),
+        }
+    }
+    Ok(options)
+}
+
+fn parse_millis(value: Option<String>) -> Result<u64, String> {
+    let value = value.ok_or(USAGE)?;
+    value
+        .parse::<u64>()
+        .ok()
+        .filter(|millis| *millis > 0)
+        .ok_or_else(|| format!("invalid duration: {value}"))
+}
+
+/// Accepts `ip`, `ip:port` and `[v6]:port`; the port defaults to 53.
+fn parse_server(value: &str) -> Option<SocketAddr> {
+    if let Ok(addr) = value.parse::<SocketAddr>() {
+        return Some(addr);
+    }
+    value
+        .parse::<IpAddr>()
+        .ok()
+        .map(|ip| SocketAddr::new(ip, 53))
+}
+
+/// First `nameserver` from /etc/resolv.conf.
+fn system_nameserver() -> Result<SocketAddr, String> {
+    let content = fs::read_to_string("/etc/resolv.conf")
+        .map_err(|err| format!("read /etc/resolv.conf: {err}; pass --upstream"))?;
+    content
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix("nameserver"))
+        .find_map(|server| parse_server(server.trim()))
+        .ok_or_else(|| "no nameserver in /etc/resolv.conf; pass --upstream".to_string())
+}
+
+/// Worker loop: relays queries until the receive thread goes away.
+fn relay_queries(
+    socket: &UdpSocket,
+    queries: &Mutex<Receiver<Query>>,
+    upstream: SocketAddr,
+    timeout: Duration,
+    attribute: bool,
+    tx: &Sender<Observation>,
+) {
+    loop {
+        // The lock is only held while waiting, not while relaying
+        let query = match queries.lock() {
+            Ok(queries) => queries.recv(),
+            Err(_) => return,
+        };
+        let Ok(query) = query else {
+            return;
+        };
+        forward_query(socket, &query.packet, query.client, upstream, timeout, attribute, tx);
+    }
+}
+
+/// Relays one query. The client's socket is looked up while the upstream
+/// answer is pending, since it is closed as soon as the answer arrives.
+fn forward_query(
+    socket: &UdpSocket,
+    query: &[u8],
+    client: SocketAddr,
+    upstream: SocketAddr,
+    timeout: Duration,
+    attribute: bool,
+    tx: &Sender<Observation>,
+) {
+    let bind_addr: SocketAddr = if upstream.is_ipv4() {
+        SocketAddr::from(([0, 0, 0, 0], 0))
+    } else {
+        SocketAddr::from(([0u16; 8], 0))
+    };
+    let upstream_socket = UdpSocket::bind(bind_addr)
+        .and_then(|upstream_socket| {
+            upstream_socket.connect(upstream)?;
+            upstream_socket.set_read_timeout(Some(timeout))?;
+            upstream_socket.send(query)?;
+            Ok(upstream_socket)
+        });
+
+    if let Some(question) = dns::parse_query(query) {
+        let owner = if attribute {
+            attribute::find_owner(client)
+        } else {
+            None
+        };
+        let _ = tx.send(Observation {
+            domain: question.domain,
+            qtype: question.qtype,
+            owner,
+        });
+    }
+
+    let mut buf = [0u8; 4096];
+    let reply = match upstream_socket.and_then(|upstream_socket| upstream_socket.recv(&mut buf)) {
+        Ok(len) => buf[..len].to_vec(),
+        Err(err) => {
+            eprintln!("upstream {upstream}: {err}");
+            dns::servfail(query)
+        }
+    };
+    if let Err(err) = socket.send_to(&reply, client) {
+        eprintln!("reply to {client}: {err}");
+    }
+}
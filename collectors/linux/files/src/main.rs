@@ -0,0 +1,403 @@
+mod watch;
+
+use aegis_client::{Client, FileUpload};
+use aegis_config::ConfigLoader;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+use watch::RawEvent;
+
+const USAGE: &str = "usage: aegis-file-collector [--config <file>] [--set <key=value>]... --watch <dir>... [--include <glob>]... [--exclude <glob>]... [--backend auto|inotify|fanotify] [--coalesce-ms N] [--max-rate N] [--hash] [--hash-max-bytes N] [--capture] [--capture-max-bytes N]";
+
+const COLLECTOR: &str = "file";
+
+#[derive(Clone, Copy, PartialEq)]
+enum Backend {
+    Auto,
+    Inotify,
+    Fanotify,
+}
+
+struct Options {
+    roots: Vec<PathBuf>,
+    includes: Vec<String>,
+    excludes: Vec<String>,
+    backend: Backend,
+    coalesce: Duration,
+    max_rate: u32,
+    hash: bool,
+    hash_max_bytes: u64,
+    capture: bool,
+    capture_max_bytes: u64,
+}
+
+fn main() {
+    if let Err(err) = run() {
+        eprintln!("FAIL: {err}");
+        std::process::exit(1);
+    }
+}
+
+fn run() -> Result<(), String> {
+    let (loader, args) = ConfigLoader::from_args(env::args().skip(1))?;
+    let config = loader.load()?.config;
+    let mut options = parse_options(args)?;
+
+    // Never report our own bundle writes back into the bundle
+    if let Ok(save_dir) = config.save_dir().canonicalize() {
+        options
+            .excludes
+            .push(format!("{}/**", save_dir.to_string_lossy()));
+    }
+
+    let (tx, rx) = mpsc::channel();
+    let fanotify = match options.backend {
+        Backend::Inotify => None,
+        Backend::Auto => watch::init_fanotify(&options.roots),
+        Backend::Fanotify => Some(
+            watch::init_fanotify(&options.roots)
+                .ok_or("fanotify unavailable (requires CAP_SYS_ADMIN)")?,
+        ),
+    };
+    eprintln!(
+        "Watching {} director{} with {}",
+        options.roots.len(),
+        if options.roots.len() == 1 { "y" } else { "ies" },
+        if fanotify.is_some() { "inotify + fanotify" } else { "inotify" }
+    );
+
+    if let Some(fd) = fanotify {
+        let roots = options.roots.clone();
+        let tx = tx.clone();
+        thread::spawn(move || {
+            if let Err(err) = watch::watch_fanotify(fd, roots, tx) {
+                eprintln!("fanotify: {err}");
+            }
+        });
+    }
+    let roots = options.roots.clone();
+    let skip_modified = fanotify.is_some();
+    thread::spawn(move || {
+        if let Err(err) = watch::watch_inotify(roots, skip_modified, tx) {
+            eprintln!("inotify: {err}");
+        }
+    });
+
+    let client = Client::from_config(&config, COLLECTOR);
+    client.spawn_heartbeat(COLLECTOR, config.server.heartbeat_interval_ms);
+    let mut limiter = RateLimiter::new(options.coalesce, options.max_rate);
+    let mut captured: u64 = 0;
+    loop {
+        match rx.recv_timeout(Duration::from_secs(1)) {
+            Ok(event) => {
+                if !is_selected(&event.path, &options.includes, &options.excludes) {
+                    continue;
+                }
+                if !limiter.allow(&event) {
+                    continue;
+                }
+                let (payload, size) = activity_payload(&event, &options);
+                if let Err(err) = client.send_event("file_activity", payload) {
+                    eprintln!("send file_activity: {err}");
+                    continue;
+                }
+                if options.capture && should_capture(&event, size, options.capture_max_bytes) {
+                    captured += 1;
+                    if let Err(err) = capture_file(&client, &event.path, captured) {
+                        eprintln!("capture {}: {err}", event.path.display());
+                    }
+                }
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => {
+                return Err("watchers stopped".to_string());
+            }
+        }
+
+        if let Some(payload) = limiter.take_suppressed() {
+            if let Err(err) = client.send_event("file_activity_suppressed", payload) {
+                eprintln!("send file_activity_suppressed: {err}");
+            }
+        }
+    }
+}
+
+fn parse_options(args: Vec<String>) -> Result<Options, String> {
+    let mut options = Options {
+        roots: Vec::new(),
+        includes: Vec::new(),
+        excludes: Vec::new(),
+        backend: Backend::Auto,
+        coalesce: Duration::from_millis(1000),
+        max_rate: 50,
+        hash: false,
+        hash_max_bytes: 16 * 1024 * 1024,
+        capture: false,
+        capture_max_bytes: 10 * 1024 * 1024,
+    };
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--watch" => {
+                let dir = PathBuf::from(args.next().ok_or(USAGE)?);
+                let dir = dir
+                    .canonicalize()
+                    .map_err(|err| format!("watch {}: {err}", dir.display()))?;
+                if !dir.is_dir() {
+                    return Err(format!("not a directory: {}", dir.display()));
+                }
+                options.roots.push(dir);
+            }
+            "--include" => options.includes.push(args.next().ok_or(USAGE)?),
+            "--exclude" => options.excludes.push(args.next().ok_or(USAGE)?),
+            "--backend" => {
+                options.backend = match args.next().ok_or(USAGE)?.as_str() {
+                    "auto" => Backend::Auto,
+                    "inotify" => Backend::Inotify,
+                    "fanotify" => Backend::Fanotify,
+                    other => return Err(format!("unsupported backend: {other}")),
+                }
+            }
+            "--coalesce-ms" => {
+                options.coalesce = Duration::from_millis(parse_number(args.next())?)
+            }
+            "--max-rate" => {
+                let rate = parse_number(args.next())?;
+                options.max_rate =
+                    u32::try_from(rate).map_err(|_| format!("--max-rate too large: {rate}"))?;
+            }
+            "--hash" => options.hash = true,
+            "--hash-max-bytes" => options.hash_max_bytes = parse_number(args.next())?,
+            "--capture" => options.capture = true,
+            "--capture-max-bytes" => options.capture_max_bytes = parse_number(args.next())?,
+            _ => return Err(USAGE.to_string()),
+        }
+    }
+
+    if options.roots.is_empty() {
+        return Err(USAGE.to_string());
+    }
+    Ok(options)
+}
+
+fn parse_number(value: Option<String>) -> Result<u64, String> {
+    let value = value.ok_or(USAGE)?;
+    value
+        .parse::<u64>()
+        .map_err(|_| format!("invalid number: {value}"))
+}
+
+/// Includes default to everything; excludes always win.
+fn is_selected(path: &Path, includes: &[String], excludes: &[String]) -> bool {
+    let path = path.to_string_lossy();
+    if excludes.iter().any(|pattern| pattern_matches(pattern, &path)) {
+        return false;
+    }
+    includes.is_empty() || includes.iter().any(|pattern| pattern_matches(pattern, &path))
+}
+
+/// Patterns without `/` match the file name, others the full path.
+fn pattern_matches(pattern: &str, path: &str) -> bool {
+    if pattern.contains('/') {
+        glob_match(pattern.as_bytes(), path.as_bytes())
+    } else {
+        let name = path.rsplit('/').next().unwrap_or(path);
+        glob_match(pattern.as_bytes(), name.as_bytes())
+    }
+}
+
+/// `*` and `?` stay within one path segment, `**` crosses segments.
+/// `**/` skips whole segments only, so `**/.git` does not match `repo.git`.
+fn glob_match(pattern: &[u8], text: &[u8]) -> bool {
+    match pattern.first() {
+        None => text.is_empty(),
+        Some(b'*') if pattern.get(1) == Some(&b'*') => {
+            let rest = &pattern[2..];
+            match rest.strip_prefix(b"/") {
+                // Trailing `**` matches whatever is left
+                None if rest.is_empty() => true,
+                None => (0..=text.len()).any(|skip| glob_match(rest, &text[skip..])),
+                Some(rest) => (0..=text.len())
+                    .filter(|skip| *skip == 0 || text[skip - 1] == b'/')
+                    .any(|skip| glob_match(rest, &text[skip..])),
+            }
+        }
+        Some(b'*') => {
+            let rest = &pattern[1..];
+            for skip in 0..=text.len() {
+                if glob_match(rest, &text[skip..]) {
+                    return true;
+                }
+                if text.get(skip) == Some(&b'/') {
+                    break;
+                }
+            }
+            false
+        }
+        Some(b'?') => match text.first() {
+            Some(byte) if *byte != b'/' => glob_match(&pattern[1..], &text[1..]),
+            _ => false,
+        },
+        Some(byte) => text.first() == Some(byte) && glob_match(&pattern[1..], &text[1..]),
+    }
+}
+
+/// Drops repeats of the same path and operation within `coalesce`, and
+/// caps the overall rate at `max_rate` events per second; what was
+/// dropped is reported as one `file_activity_suppressed` event.
+struct RateLimiter {
+    coalesce: Duration,
+    max_rate: u32,
+    recent: HashMap<(PathBuf, &'static str), Instant>,
+    window_start: Instant,
+    window_count: u32,
+    suppressed: u64,
+}
+
+impl RateLimiter {
+    fn new(coalesce: Duration, max_rate: u32) -> Self {
+        Self {
+            coalesce,
+            max_rate,
+            recent: HashMap::new(),
+            window_start: Instant::now(),
+            window_count: 0,
+            suppressed: 0,
+        }
+    }
+
+    fn allow(&mut self, event: &RawEvent) -> bool {
+        let now = Instant::now();
+        if now.duration_since(self.window_start) >= Duration::from_secs(1) {
+            self.window_start = now;
+            self.window_count = 0;
+            let coalesce = self.coalesce;
+            self.recent
+                .retain(|_, seen| now.duration_since(*seen) < coalesce);
+        }
+
+        let key = (event.path.clone(), event.op);
+        if let Some(seen) = self.recent.get(&key) {
+            if now.duration_since(*seen) < self.coalesce {
+                self.suppressed += 1;
+                return false;
+            }
+        }
+        if self.max_rate > 0 && self.window_count >= self.max_rate {
+            self.suppressed += 1;
+            return false;
+        }
+        self.recent.insert(key, now);
+        self.window_count += 1;
+        true
+    }
+
+    fn take_suppressed(&mut self) -> Option<Value> {
+        if self.suppressed == 0 || self.window_start.elapsed() < Duration::from_secs(1) {
+            return None;
+        }
+        let count = std::mem::take(&mut self.suppressed);
+        Some(json!({ "count": count }))
+    }
+}
+
+fn activity_payload(event: &RawEvent, options: &Options) -> (Value, Option<u64>) {
+    let metadata = if event.op == "deleted" || event.op == "moved_from" {
+        None
+    } else {
+        fs::metadata(&event.path).ok()
+    };
+    let size = metadata.as_ref().filter(|meta| meta.is_file()).map(|meta| meta.len());
+
+    let mut payload = json!({
+        "path": event.path.to_string_lossy(),
+        "op": event.op,
+    });
+    if let Some(meta) = metadata.as_ref() {
+        if meta.is_dir() {
+            payload["dir"] = json!(true);
+        }
+    }
+    if let Some(size) = size {
+        payload["size"] = json!(size);
+        if options.hash && size <= options.hash_max_bytes {
+            match hash_file(&event.path) {
+                Ok(hash) => payload["hash"] = json!(hash),
+                Err(err) => eprintln!("hash {}: {err}", event.path.display()),
+            }
+        }
+    }
+    if let Some(pid) = event.pid {
+        payload["pid"] = json!(pid);
+    }
+    (payload, size)
+}
+
+fn should_capture(event: &RawEvent, size: Option<u64>, max_bytes: u64) -> bool {
+    matches!(event.op, "created" | "modified" | "moved_to")
+        && size.map(|size| size <= max_bytes).unwrap_or(false)
+}
+
+/// Asks the core server to copy the file into `files/fs/`.
+fn capture_file(client: &Client, path: &Path, index: u64) -> Result<(), String> {
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().replace(['/', '\\'], "_"))
+        .unwrap_or_else(|| "file".to_string());
+    let rel_path = format!("files/fs/{index:06}_{name}");
+    client
+        .upload_file(&FileUpload {
+            source_path: path.to_path_buf(),
+            rel_path,
+            kind: "file_capture".to_string(),
+        })
+        .map(|_| ())
+}
+
+fn hash_file(path: &Path) -> io::Result<String> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buf = [0u8; 64 * 1024];
+    loop {
+        let read = file.read(&mut buf)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buf[..read]);
+    }
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn double_star_only_skips_whole_segments() {
+        let matches = |pattern: &str, path: &str| pattern_matches(pattern, path);
+        assert!(matches("**/node_modules/**", "/x/node_modules/y"));
+        assert!(matches("**/node_modules/**", "/node_modules/a/b"));
+        assert!(!matches("**/node_modules/**", "/x/my_node_modules/y"));
+        assert!(matches("**/.git", "/a/.git"));
+        assert!(!matches("**/.git", "/a/repo.git"));
+        assert!(matches("/a/**/b", "/a/b"));
+        assert!(matches("/a/**/b", "/a/x/y/b"));
+        assert!(!matches("/a/**/b", "/a/xb"));
+        assert!(matches("/tmp/**", "/tmp/a/b.txt"));
+        assert!(matches("**.log", "/var/log/app.log"));
+        assert!(matches("*.log", "/var/log/app.log"));
+        assert!(!matches("/var/*.log", "/var/log/app.log"));
+    }
+}
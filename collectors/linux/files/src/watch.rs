@@ -0,0 +1,279 @@
+use std::collections::HashMap;
+use std::ffi::{CString, OsStr};
+use std::fs;
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::Sender;
+
+/// A filesystem change before filtering and rate limiting.
+pub struct RawEvent {
+    pub path: PathBuf,
+    pub op: &'static str,
+    pub pid: Option<i32>,
+}
+
+const INOTIFY_MASK: u32 = libc::IN_CREATE
+    | libc::IN_CLOSE_WRITE
+    | libc::IN_DELETE
+    | libc::IN_MOVED_FROM
+    | libc::IN_MOVED_TO
+    | libc::IN_DELETE_SELF;
+
+/// Watches `roots` recursively with inotify. When `skip_modified` is set the
+/// fanotify watcher reports modifications instead, so only structural
+/// changes (create/delete/move) are forwarded here.
+pub fn watch_inotify(
+    roots: Vec<PathBuf>,
+    skip_modified: bool,
+    tx: Sender<RawEvent>,
+) -> Result<(), String> {
+    let fd = unsafe { libc::inotify_init1(libc::IN_CLOEXEC) };
+    if fd < 0 {
+        return Err(format!("inotify_init1: {}", io::Error::last_os_error()));
+    }
+
+    let mut watches: HashMap<i32, PathBuf> = HashMap::new();
+    for root in &roots {
+        add_watch_recursive(fd, root, &mut watches)?;
+    }
+
+    // A directory's IN_MOVED_FROM waiting for the IN_MOVED_TO with the same
+    // cookie, which the kernel queues right after it
+    let mut moved_dir: Option<(u32, PathBuf)> = None;
+    let mut buf = vec![0u8; 64 * 1024];
+    let header = std::mem::size_of::<libc::inotify_event>();
+    loop {
+        let len = unsafe { libc::read(fd, buf.as_mut_ptr().cast(), buf.len()) };
+        if len < 0 {
+            let err = io::Error::last_os_error();
+            if err.kind() == io::ErrorKind::Interrupted {
+                continue;
+            }
+            return Err(format!("read inotify: {err}"));
+        }
+
+        let mut offset = 0;
+        while offset + header <= len as usize {
+            let event: libc::inotify_event =
+                unsafe { std::ptr::read_unaligned(buf[offset..].as_ptr().cast()) };
+            let name_start = offset + header;
+            let name_end = name_start + event.len as usize;
+            offset = name_end;
+
+            if event.mask & libc::IN_Q_OVERFLOW != 0 {
+                eprintln!("inotify queue overflowed; some events were lost");
+                continue;
+            }
+            let moved_from = match moved_dir.take() {
+                Some((cookie, from))
+                    if event.mask & libc::IN_MOVED_TO != 0 && event.cookie == cookie =>
+                {
+                    Some(from)
+                }
+                // Unpaired: the directory left the watched trees
+                Some((_, from)) => {
+                    forget_tree(fd, &mut watches, &from);
+                    None
+                }
+                None => None,
+            };
+            if event.mask & (libc::IN_DELETE_SELF | libc::IN_IGNORED) != 0 {
+                watches.remove(&event.wd);
+                continue;
+            }
+            let Some(dir) = watches.get(&event.wd) else {
+                continue;
+            };
+            let name = &buf[name_start..name_end.min(buf.len())];
+            let name = name.split(|byte| *byte == 0).next().unwrap_or_default();
+            if name.is_empty() {
+                continue;
+            }
+            let path = dir.join(OsStr::from_bytes(name));
+
+            let is_dir = event.mask & libc::IN_ISDIR != 0;
+            if is_dir && event.mask & libc::IN_MOVED_FROM != 0 {
+                moved_dir = Some((event.cookie, path.clone()));
+            }
+            if is_dir && event.mask & libc::IN_MOVED_TO != 0 {
+                if let Some(from) = &moved_from {
+                    // Watches follow the inodes; only their paths change
+                    rename_tree(&mut watches, from, &path);
+                } else if let Err(err) = add_watch_recursive(fd, &path, &mut watches) {
+                    eprintln!("watch {}: {err}", path.display());
+                }
+            }
+            if is_dir && event.mask & libc::IN_CREATE != 0 {
+                if let Err(err) = add_watch_recursive(fd, &path, &mut watches) {
+                    eprintln!("watch {}: {err}", path.display());
+                }
+            }
+
+            let op = if event.mask & libc::IN_CREATE != 0 {
+                "created"
+            } else if event.mask & libc::IN_CLOSE_WRITE != 0 {
+                if skip_modified {
+                    continue;
+                }
+                "modified"
+            } else if event.mask & libc::IN_DELETE != 0 {
+                "deleted"
+            } else if event.mask & libc::IN_MOVED_FROM != 0 {
+                "moved_from"
+            } else if event.mask & libc::IN_MOVED_TO != 0 {
+                "moved_to"
+            } else {
+                continue;
+            };
+            if tx.send(RawEvent { path, op, pid: None }).is_err() {
+                return Ok(());
+            }
+        }
+    }
+}
+
+fn add_watch_recursive(
+    fd: i32,
+    dir: &Path,
+    watches: &mut HashMap<i32, PathBuf>,
+) -> Result<(), String> {
+    let c_path = CString::new(dir.as_os_str().as_bytes())
+        .map_err(|_| format!("invalid path: {}", dir.display()))?;
+    let wd = unsafe { libc::inotify_add_watch(fd, c_path.as_ptr(), INOTIFY_MASK) };
+    if wd < 0 {
+        return Err(format!(
+            "inotify_add_watch {}: {}",
+            dir.display(),
+            io::Error::last_os_error()
+        ));
+    }
+    watches.insert(wd, dir.to_path_buf());
+
+    let entries = fs::read_dir(dir).map_err(|err| format!("read {}: {err}", dir.display()))?;
+    for entry in entries.flatten() {
+        let is_dir = entry
+            .file_type()
+            .map(|file_type| file_type.is_dir())
+            .unwrap_or(false);
+        if is_dir {
+            // Subdirectories may vanish or be unreadable; keep the rest
+            if let Err(err) = add_watch_recursive(fd, &entry.path(), watches) {
+                eprintln!("{err}");
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Rewrites the watched paths under `from` after it was renamed to `to`.
+fn rename_tree(watches: &mut HashMap<i32, PathBuf>, from: &Path, to: &Path) {
+    for dir in watches.values_mut() {
+        if let Ok(rest) = dir.strip_prefix(from) {
+            *dir = if rest.as_os_str().is_empty() {
+                to.to_path_buf()
+            } else {
+                to.join(rest)
+            };
+        }
+    }
+}
+
+/// Stops watching a directory that moved out of the watched trees, along
+/// with everything below it.
+fn forget_tree(fd: i32, watches: &mut HashMap<i32, PathBuf>, dir: &Path) {
+    watches.retain(|wd, path| {
+        if !path.starts_with(dir) {
+            return true;
+        }
+        unsafe { libc::inotify_rm_watch(fd, *wd) };
+        false
+    });
+}
+
+/// Opens a fanotify group for close-after-write on the mounts holding
+/// `roots`. Requires CAP_SYS_ADMIN; returns `None` when unavailable.
+pub fn init_fanotify(roots: &[PathBuf]) -> Option<i32> {
+    let fd = unsafe {
+        libc::fanotify_init(
+            libc::FAN_CLASS_NOTIF | libc::FAN_CLOEXEC,
+            (libc::O_RDONLY | libc::O_LARGEFILE) as u32,
+        )
+    };
+    if fd < 0 {
+        return None;
+    }
+    for root in roots {
+        let Ok(c_path) = CString::new(root.as_os_str().as_bytes()) else {
+            unsafe { libc::close(fd) };
+            return None;
+        };
+        let result = unsafe {
+            libc::fanotify_mark(
+                fd,
+                libc::FAN_MARK_ADD | libc::FAN_MARK_MOUNT,
+                libc::FAN_CLOSE_WRITE,
+                libc::AT_FDCWD,
+                c_path.as_ptr(),
+            )
+        };
+        if result < 0 {
+            eprintln!(
+                "fanotify_mark {}: {}",
+                root.display(),
+                io::Error::last_os_error()
+            );
+            unsafe { libc::close(fd) };
+            return None;
+        }
+    }
+    Some(fd)
+}
+
+/// Reports modifications with the writing process attached. Marks cover
+/// whole mounts, so events outside `roots` are dropped here.
+pub fn watch_fanotify(fd: i32, roots: Vec<PathBuf>, tx: Sender<RawEvent>) -> Result<(), String> {
+    let own_pid = std::process::id() as i32;
+    let mut buf = vec![0u8; 64 * 1024];
+    let header = std::mem::size_of::<libc::fanotify_event_metadata>();
+    loop {
+        let len = unsafe { libc::read(fd, buf.as_mut_ptr().cast(), buf.len()) };
+        if len < 0 {
+            let err = io::Error::last_os_error();
+            if err.kind() == io::ErrorKind::Interrupted {
+                continue;
+            }
+            return Err(format!("read fanotify: {err}"));
+        }
+
+        let mut offset = 0;
+        while offset + header <= len as usize {
+            let event: libc::fanotify_event_metadata =
+                unsafe { std::ptr::read_unaligned(buf[offset..].as_ptr().cast()) };
+            if event.event_len == 0 {
+                break;
+            }
+            offset += event.event_len as usize;
+            if event.fd < 0 {
+                continue;
+            }
+
+            let path = fs::read_link(format!("/proc/self/fd/{}", event.fd));
+            unsafe { libc::close(event.fd) };
+            let Ok(path) = path else {
+                continue;
+            };
+            if event.pid == own_pid || !roots.iter().any(|root| path.starts_with(root)) {
+                continue;
+            }
+            let raw = RawEvent {
+                path,
+                op: "modified",
+                pid: Some(event.pid),
+            };
+            if tx.send(raw).is_err() {
+                return Ok(());
+            }
+        }
+    }
+}
@@ -0,0 +1,319 @@
+use aegis_client::{Client, FocusChange};
+use aegis_config::ConfigLoader;
+use serde_json::Value;
+use std::env;
+use std::fs;
+use std::io::{BufRead, BufReader};
+use std::process::{Command, Stdio};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const USAGE: &str = "usage: aegis-focus-collector [--config <file>] [--set <key=value>]... [--backend auto|x11|sway] [--debounce-ms N]";
+
+const COLLECTOR: &str = "focus";
+
+#[derive(Clone, PartialEq, Debug)]
+struct FocusInfo {
+    app_id: String,
+    app_name: String,
+    window_title: Option<String>,
+    pid: Option<u32>,
+    exe: Option<String>,
+}
+
+#[derive(Clone, Copy)]
+enum Backend {
+    X11,
+    Sway,
+}
+
+fn main() {
+    if let Err(err) = run() {
+        eprintln!("FAIL: {err}");
+        std::process::exit(1);
+    }
+}
+
+fn run() -> Result<(), String> {
+    let (loader, args) = ConfigLoader::from_args(env::args().skip(1))?;
+    let config = loader.load()?.config;
+
+    let mut backend = None;
+    let mut debounce = Duration::from_millis(300);
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--backend" => {
+                backend = match args.next().ok_or(USAGE)?.as_str() {
+                    "auto" => None,
+                    "x11" => Some(Backend::X11),
+                    "sway" => Some(Backend::Sway),
+                    other => return Err(format!("unsupported backend: {other}")),
+                }
+            }
+            "--debounce-ms" => {
+                let value = args.next().ok_or(USAGE)?;
+                let millis = value
+                    .parse::<u64>()
+                    .map_err(|_| format!("invalid debounce: {value}"))?;
+                debounce = Duration::from_millis(millis);
+            }
+            _ => return Err(USAGE.to_string()),
+        }
+    }
+    let backend = match backend {
+        Some(backend) => backend,
+        None => detect_backend()?,
+    };
+
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let result = match backend {
+            Backend::X11 => watch_x11(tx),
+            Backend::Sway => watch_sway(tx),
+        };
+        if let Err(err) = result {
+            eprintln!("focus backend: {err}");
+        }
+    });
+
+    let client = Client::from_config(&config, COLLECTOR);
+    client.spawn_heartbeat(COLLECTOR, config.server.heartbeat_interval_ms);
+
+    // Only report a window once focus has rested on it for `debounce`, so
+    // alt-tab sweeps do not flood the bundle.
+    let mut pending: Option<(FocusInfo, Instant)> = None;
+    let mut last_sent: Option<FocusInfo> = None;
+    loop {
+        let timeout = pending
+            .as_ref()
+            .map(|(_, since)| debounce.saturating_sub(since.elapsed()))
+            .unwrap_or(Duration::from_secs(3600));
+        match rx.recv_timeout(timeout) {
+            Ok(info) => pending = Some((info, Instant::now())),
+            Err(RecvTimeoutError::Timeout) => {
+                let Some((info, _)) = pending.take() else {
+                    continue;
+                };
+                if last_sent.as_ref() == Some(&info) {
+                    continue;
+                }
+                match client.send_focus(&focus_change(&info)) {
+                    Ok(_) => last_sent = Some(info),
+                    Err(err) => eprintln!("send focus event: {err}"),
+                }
+            }
+            Err(RecvTimeoutError::Disconnected) => {
+                return Err("focus backend stopped".to_string());
+            }
+        }
+    }
+}
+
+fn detect_backend() -> Result<Backend, String> {
+    if env::var_os("SWAYSOCK").is_some() {
+        return Ok(Backend::Sway);
+    }
+    if env::var_os("DISPLAY").is_some() {
+        return Ok(Backend::X11);
+    }
+    if env::var_os("WAYLAND_DISPLAY").is_some() {
+        return Err("this Wayland compositor does not expose the focused window; run under sway or X11".to_string());
+    }
+    Err("no display found (DISPLAY/SWAYSOCK unset)".to_string())
+}
+
+fn focus_change(info: &FocusInfo) -> FocusChange {
+    FocusChange {
+        app_id: info.app_id.clone(),
+        app_name: info.app_name.clone(),
+        window_title: info.window_title.clone(),
+        pid: info.pid,
+        exe: info.exe.clone(),
+    }
+}
+
+/// Follows `_NET_ACTIVE_WINDOW` on the root window via `xprop -spy`.
+fn watch_x11(tx: Sender<FocusInfo>) -> Result<(), String> {
+    let mut child = Command::new("xprop")
+        .args(["-root", "-spy", "_NET_ACTIVE_WINDOW"])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::inherit())
+        .spawn()
+        .map_err(|err| format!("start xprop: {err}"))?;
+    let stdout = child.stdout.take().ok_or("xprop stdout unavailable")?;
+
+    for line in BufReader::new(stdout).lines() {
+        let line = line.map_err(|err| format!("read xprop: {err}"))?;
+        let Some(window) = parse_active_window(&line) else {
+            continue;
+        };
+        match resolve_x11_window(&window) {
+            Ok(info) => {
+                if tx.send(info).is_err() {
+                    break;
+                }
+            }
+            Err(err) => eprintln!("resolve window {window}: {err}"),
+        }
+    }
+
+    let _ = child.kill();
+    let _ = child.wait();
+    Err("xprop exited".to_string())
+}
+
+/// Extracts the window id from `_NET_ACTIVE_WINDOW(WINDOW): window id # 0x1a00007`;
+/// `0x0` means nothing is focused.
+fn parse_active_window(line: &str) -> Option<String> {
+    let (_, id) = line.rsplit_once('#')?;
+    let id = id.trim().split(',').next()?.trim();
+    if !id.starts_with("0x") || id == "0x0" {
+        return None;
+    }
+    Some(id.to_string())
+}
+
+fn resolve_x11_window(window: &str) -> Result<FocusInfo, String> {
+    let output = Command::new("xprop")
+        .args(["-id", window, "_NET_WM_PID", "WM_CLASS", "_NET_WM_NAME", "WM_NAME"])
+        .stderr(Stdio::null())
+        .output()
+        .map_err(|err| format!("run xprop: {err}"))?;
+    if !output.status.success() {
+        return Err("window vanished".to_string());
+    }
+
+    let mut pid = None;
+    let mut class = Vec::new();
+    let mut net_wm_name = None;
+    let mut wm_name = None;
+    for line in String::from_utf8_lossy(&output.stdout).lines() {
+        let Some((name, value)) = line.split_once(" = ") else {
+            continue;
+        };
+        let name = name.split('(').next().unwrap_or_default();
+        match name {
+            "_NET_WM_PID" => pid = value.trim().parse::<u32>().ok(),
+            "WM_CLASS" => class = parse_quoted_list(value),
+            "_NET_WM_NAME" => net_wm_name = parse_quoted_list(value).into_iter().next(),
+            "WM_NAME" => wm_name = parse_quoted_list(value).into_iter().next(),
+            _ => {}
+        }
+    }
+
+    let exe = pid.and_then(|pid| {
+        fs::read_link(format!("/proc/{pid}/exe"))
+            .ok()
+            .map(|path| path.to_string_lossy().to_string())
+    });
+    Ok(build_focus_info(
+        class.get(1).or(class.first()).cloned(),
+        net_wm_name.or(wm_name),
+        pid,
+        exe,
+    ))
+}
+
+fn build_focus_info(
+    app_id: Option<String>,
+    window_title: Option<String>,
+    pid: Option<u32>,
+    exe: Option<String>,
+) -> FocusInfo {
+    let exe_name = exe.as_ref().and_then(|exe| {
+        exe.rsplit('/')
+            .next()
+            .filter(|name| !name.is_empty())
+            .map(str::to_string)
+    });
+    let app_id = app_id
+        .filter(|id| !id.is_empty())
+        .or_else(|| exe_name.clone())
+        .unwrap_or_else(|| "unknown".to_string());
+    let app_name = exe_name.unwrap_or_else(|| app_id.clone());
+    FocusInfo {
+        app_id,
+        app_name,
+        window_title: window_title.filter(|title| !title.is_empty()),
+        pid,
+        exe,
+    }
+}
+
+/// Parses xprop's `"a", "b"` string lists, honouring backslash escapes.
+fn parse_quoted_list(value: &str) -> Vec<String> {
+    let mut items = Vec::new();
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '"' {
+            continue;
+        }
+        let mut item = String::new();
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => {
+                    if let Some(escaped) = chars.next() {
+                        item.push(escaped);
+                    }
+                }
+                '"' => break,
+                other => item.push(other),
+            }
+        }
+        items.push(item);
+    }
+    items
+}
+
+/// Follows sway's window events; XWayland windows report their X11 class.
+fn watch_sway(tx: Sender<FocusInfo>) -> Result<(), String> {
+    let mut child = Command::new("swaymsg")
+        .args(["-r", "-m", "-t", "subscribe", "[\"window\"]"])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::inherit())
+        .spawn()
+        .map_err(|err| format!("start swaymsg: {err}"))?;
+    let stdout = child.stdout.take().ok_or("swaymsg stdout unavailable")?;
+
+    for line in BufReader::new(stdout).lines() {
+        let line = line.map_err(|err| format!("read swaymsg: {err}"))?;
+        let Ok(event) = serde_json::from_str::<Value>(&line) else {
+            continue;
+        };
+        if event.get("change").and_then(Value::as_str) != Some("focus") {
+            continue;
+        }
+        let Some(container) = event.get("container") else {
+            continue;
+        };
+        let pid = container
+            .get("pid")
+            .and_then(Value::as_u64)
+            .and_then(|pid| u32::try_from(pid).ok());
+        let app_id = container
+            .get("app_id")
+            .and_then(Value::as_str)
+            .or_else(|| {
+                container
+                    .get("window_properties")
+                    .and_then(|props| props.get("class"))
+                    .and_then(Value::as_str)
+            })
+            .map(str::to_string);
+        let title = container.get("name").and_then(Value::as_str).map(str::to_string);
+        let exe = pid.and_then(|pid| {
+            fs::read_link(format!("/proc/{pid}/exe"))
+                .ok()
+                .map(|path| path.to_string_lossy().to_string())
+        });
+        if tx.send(build_focus_info(app_id, title, pid, exe)).is_err() {
+            break;
+        }
+    }
+
+    let _ = child.kill();
+    let _ = child.wait();
+    Err("swaymsg exited".to_string())
+}
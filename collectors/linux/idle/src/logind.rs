@@ -0,0 +1,155 @@
+use std::io::{BufRead, BufReader};
+use std::process::{Command, Stdio};
+
+const SESSION_PREFIX: &str = "/org/freedesktop/login1/session";
+
+/// A logind signal relevant to presence.
+#[derive(Clone, Copy)]
+pub enum LogindSignal {
+    /// `Session.Lock`: logind asks the screen locker to lock.
+    Lock,
+    /// `Session.Unlock`
+    Unlock,
+    /// `IdleHint` changed on the session
+    IdleHint(bool),
+    /// `LockedHint` changed on the session (set by the locker itself)
+    LockedHint(bool),
+    /// `Manager.PrepareForSleep`: true before suspend, false after resume
+    PrepareForSleep(bool),
+}
+
+/// Object path of a logind session, escaped the way systemd escapes bus
+/// labels: every byte outside `[A-Za-z0-9]`, and a leading digit, becomes
+/// `_xx`. Session "2" lives at `.../session/_32`.
+pub fn session_path(session_id: &str) -> String {
+    let mut path = format!("{SESSION_PREFIX}/");
+    if session_id.is_empty() {
+        path.push('_');
+        return path;
+    }
+    for (index, byte) in session_id.bytes().enumerate() {
+        if byte.is_ascii_alphanumeric() && !(index == 0 && byte.is_ascii_digit()) {
+            path.push(byte as char);
+        } else {
+            path.push_str(&format!("_{byte:02x}"));
+        }
+    }
+    path
+}
+
+/// Follows logind signals through `dbus-monitor` until it exits or
+/// `on_signal` returns false. `address` selects a bus other than the system
+/// bus; `session` limits session signals to one session object path.
+pub fn watch(
+    address: Option<&str>,
+    session: Option<&str>,
+    mut on_signal: impl FnMut(LogindSignal) -> bool,
+) -> Result<(), String> {
+    let session_match = match session {
+        Some(path) => format!("path='{path}'"),
+        None => format!("path_namespace='{SESSION_PREFIX}'"),
+    };
+    let rules = [
+        "type='signal',sender='org.freedesktop.login1',interface='org.freedesktop.login1.Manager',member='PrepareForSleep'".to_string(),
+        format!("type='signal',sender='org.freedesktop.login1',interface='org.freedesktop.login1.Session',{session_match}"),
+        format!("type='signal',sender='org.freedesktop.login1',interface='org.freedesktop.DBus.Properties',member='PropertiesChanged',{session_match}"),
+    ];
+
+    let mut command = Command::new("dbus-monitor");
+    match address {
+        Some(address) => command.args(["--address", address]),
+        None => command.arg("--system"),
+    };
+    let mut child = command
+        .args(&rules)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::inherit())
+        .spawn()
+        .map_err(|err| format!("start dbus-monitor: {err}"))?;
+    let stdout = child.stdout.take().ok_or("dbus-monitor stdout unavailable")?;
+
+    let mut parser = MonitorParser::default();
+    for line in BufReader::new(stdout).lines() {
+        let line = line.map_err(|err| format!("read dbus-monitor: {err}"))?;
+        if let Some(signal) = parser.feed(&line) {
+            if !on_signal(signal) {
+                break;
+            }
+        }
+    }
+
+    let _ = child.kill();
+    let _ = child.wait();
+    Err("dbus-monitor exited".to_string())
+}
+
+/// Incremental parser for the default `dbus-monitor` text format: an
+/// unindented header line per message, followed by indented body lines.
+#[derive(Default)]
+struct MonitorParser {
+    interface: String,
+    member: String,
+    /// Last string seen in a `PropertiesChanged` body, i.e. the property
+    /// name a following `variant` line belongs to
+    property: Option<String>,
+}
+
+impl MonitorParser {
+    fn feed(&mut self, line: &str) -> Option<LogindSignal> {
+        if !line.starts_with(char::is_whitespace) {
+            self.interface.clear();
+            self.member.clear();
+            self.property = None;
+            if !line.starts_with("signal ") {
+                return None;
+            }
+            // signal time=.. sender=:1.5 -> destination=.. serial=8 path=..; interface=..; member=..
+            for field in line.split_whitespace() {
+                let field = field.trim_end_matches(';');
+                if let Some(interface) = field.strip_prefix("interface=") {
+                    self.interface = interface.to_string();
+                } else if let Some(member) = field.strip_prefix("member=") {
+                    self.member = member.to_string();
+                }
+            }
+            return match (self.interface.as_str(), self.member.as_str()) {
+                ("org.freedesktop.login1.Session", "Lock") => Some(LogindSignal::Lock),
+                ("org.freedesktop.login1.Session", "Unlock") => Some(LogindSignal::Unlock),
+                _ => None,
+            };
+        }
+
+        let body = line.trim();
+        match self.member.as_str() {
+            "PrepareForSleep" => {
+                let sleeping = parse_boolean(body)?;
+                // Only the first argument matters
+                self.member.clear();
+                Some(LogindSignal::PrepareForSleep(sleeping))
+            }
+            "PropertiesChanged" => {
+                if let Some(name) = body.strip_prefix("string ") {
+                    self.property = Some(name.trim_matches('"').to_string());
+                    return None;
+                }
+                let value = body.strip_prefix("variant")?.trim();
+                let property = self.property.take()?;
+                let value = parse_boolean(value)?;
+                match property.as_str() {
+                    "IdleHint" => Some(LogindSignal::IdleHint(value)),
+                    "LockedHint" => Some(LogindSignal::LockedHint(value)),
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+}
+
+fn parse_boolean(body: &str) -> Option<bool> {
+    match body.strip_prefix("boolean ")?.trim() {
+        "true" => Some(true),
+        "false" => Some(false),
+        _ => None,
+    }
+}
@@ -0,0 +1,239 @@
+mod logind;
+
+use aegis_client::Client;
+use aegis_config::ConfigLoader;
+use logind::LogindSignal;
+use serde_json::{json, Value};
+use std::env;
+use std::process::{Command, Stdio};
+use std::sync::mpsc::{self, Sender};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+const USAGE: &str = "usage: aegis-idle-collector [--config <file>] [--set <key=value>]... [--idle-secs N] [--poll-ms N] [--session-id ID] [--dbus-address ADDR] [--no-x11] [--no-logind]";
+
+const COLLECTOR: &str = "idle";
+
+enum Observation {
+    /// X11 input idle time crossed the threshold in either direction
+    X11Idle { idle: bool, idle_ms: u64 },
+    Logind(LogindSignal),
+}
+
+/// Presence as last reported; `None` until a source has said anything, so
+/// the first observation is always reported even if it is "unlocked".
+#[derive(Default)]
+struct Presence {
+    idle: Option<bool>,
+    idle_since: Option<SystemTime>,
+    locked: Option<bool>,
+    suspended: Option<bool>,
+    suspended_at: Option<SystemTime>,
+}
+
+fn main() {
+    if let Err(err) = run() {
+        eprintln!("FAIL: {err}");
+        std::process::exit(1);
+    }
+}
+
+fn run() -> Result<(), String> {
+    let (loader, args) = ConfigLoader::from_args(env::args().skip(1))?;
+    let config = loader.load()?.config;
+
+    let mut idle_after = Duration::from_secs(300);
+    let mut poll = Duration::from_millis(1000);
+    let mut session_id = env::var("XDG_SESSION_ID").ok().filter(|id| !id.is_empty());
+    let mut dbus_address = None;
+    let mut use_x11 = env::var_os("DISPLAY").is_some();
+    let mut use_logind = true;
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--idle-secs" => {
+                let value = args.next().ok_or(USAGE)?;
+                let secs = value
+                    .parse::<u64>()
+                    .ok()
+                    .filter(|secs| *secs > 0)
+                    .ok_or_else(|| format!("invalid idle threshold: {value}"))?;
+                idle_after = Duration::from_secs(secs);
+            }
+            "--poll-ms" => {
+                let value = args.next().ok_or(USAGE)?;
+                let millis = value
+                    .parse::<u64>()
+                    .ok()
+                    .filter(|millis| *millis > 0)
+                    .ok_or_else(|| format!("invalid poll interval: {value}"))?;
+                poll = Duration::from_millis(millis);
+            }
+            "--session-id" => session_id = Some(args.next().ok_or(USAGE)?),
+            "--dbus-address" => dbus_address = Some(args.next().ok_or(USAGE)?),
+            "--no-x11" => use_x11 = false,
+            "--no-logind" => use_logind = false,
+            _ => return Err(USAGE.to_string()),
+        }
+    }
+    if !use_x11 && !use_logind {
+        return Err("no idle source enabled (DISPLAY unset and --no-logind given)".to_string());
+    }
+
+    let (tx, rx) = mpsc::channel();
+    if use_x11 {
+        let tx = tx.clone();
+        thread::spawn(move || {
+            if let Err(err) = watch_x11(idle_after, poll, tx) {
+                eprintln!("x11 idle source: {err}");
+            }
+        });
+    }
+    if use_logind {
+        let session = session_id.as_deref().map(logind::session_path);
+        if session.is_none() {
+            eprintln!("XDG_SESSION_ID unset; following lock/idle signals of every session");
+        }
+        let tx = tx.clone();
+        thread::spawn(move || {
+            let result = logind::watch(dbus_address.as_deref(), session.as_deref(), |signal| {
+                tx.send(Observation::Logind(signal)).is_ok()
+            });
+            if let Err(err) = result {
+                eprintln!("logind source: {err}");
+            }
+        });
+    }
+    drop(tx);
+
+    let client = Client::from_config(&config, COLLECTOR);
+    client.spawn_heartbeat(COLLECTOR, config.server.heartbeat_interval_ms);
+    let mut presence = Presence::default();
+    for observation in rx {
+        for (event_type, payload) in presence.apply(observation) {
+            if let Err(err) = client.send_event(event_type, payload) {
+                eprintln!("send {event_type}: {err}");
+            }
+        }
+    }
+    Err("all idle sources stopped".to_string())
+}
+
+impl Presence {
+    /// Turns an observation into the events it implies; repeated states
+    /// (logind's `Lock` followed by `LockedHint`, say) report nothing.
+    fn apply(&mut self, observation: Observation) -> Vec<(&'static str, Value)> {
+        let now = SystemTime::now();
+        let mut events = Vec::new();
+        match observation {
+            Observation::X11Idle { idle, idle_ms } => {
+                let since = now - Duration::from_millis(idle_ms);
+                self.set_idle(idle, "x11", Some(idle_ms), since, &mut events);
+            }
+            Observation::Logind(LogindSignal::IdleHint(idle)) => {
+                self.set_idle(idle, "logind", None, now, &mut events);
+            }
+            Observation::Logind(LogindSignal::Lock) => self.set_locked(true, "Lock", &mut events),
+            Observation::Logind(LogindSignal::Unlock) => self.set_locked(false, "Unlock", &mut events),
+            Observation::Logind(LogindSignal::LockedHint(locked)) => {
+                self.set_locked(locked, "LockedHint", &mut events);
+            }
+            Observation::Logind(LogindSignal::PrepareForSleep(sleeping)) => {
+                if self.suspended != Some(sleeping) {
+                    self.suspended = Some(sleeping);
+                    if sleeping {
+                        self.suspended_at = Some(now);
+                        events.push(("system_suspend", json!({ "source": "logind" })));
+                    } else {
+                        let mut payload = json!({ "source": "logind" });
+                        if let Some(at) = self.suspended_at.take() {
+                            let elapsed = now.duration_since(at).unwrap_or_default();
+                            payload["suspended_ms"] = json!(elapsed.as_millis() as u64);
+                        }
+                        events.push(("system_resume", payload));
+                    }
+                }
+            }
+        }
+        events
+    }
+
+    fn set_locked(&mut self, locked: bool, via: &str, events: &mut Vec<(&'static str, Value)>) {
+        if self.locked == Some(locked) {
+            return;
+        }
+        self.locked = Some(locked);
+        let event_type = if locked { "screen_locked" } else { "screen_unlocked" };
+        events.push((event_type, json!({ "source": "logind", "via": via })));
+    }
+
+    fn set_idle(
+        &mut self,
+        idle: bool,
+        source: &str,
+        idle_ms: Option<u64>,
+        since: SystemTime,
+        events: &mut Vec<(&'static str, Value)>,
+    ) {
+        if self.idle == Some(idle) {
+            return;
+        }
+        self.idle = Some(idle);
+        if idle {
+            self.idle_since = Some(since);
+            let mut payload = json!({ "source": source });
+            if let Some(idle_ms) = idle_ms {
+                payload["idle_ms"] = json!(idle_ms);
+            }
+            events.push(("user_idle", payload));
+        } else {
+            let mut payload = json!({ "source": source });
+            if let Some(since) = self.idle_since.take() {
+                let elapsed = SystemTime::now().duration_since(since).unwrap_or_default();
+                payload["idle_duration_ms"] = json!(elapsed.as_millis() as u64);
+            }
+            events.push(("user_active", payload));
+        }
+    }
+}
+
+/// Polls the MIT-SCREEN-SAVER idle counter through `xprintidle` and reports
+/// threshold crossings. The counter resets on any keyboard or pointer input.
+fn watch_x11(idle_after: Duration, poll: Duration, tx: Sender<Observation>) -> Result<(), String> {
+    let threshold = idle_after.as_millis() as u64;
+    // Fail fast when the tool or the extension is unavailable
+    let mut idle_ms = x11_idle_ms()?;
+    let mut idle = false;
+    eprintln!(
+        "Watching X11 idle time every {}ms (threshold {}s)",
+        poll.as_millis(),
+        idle_after.as_secs()
+    );
+    loop {
+        if (idle_ms >= threshold) != idle {
+            idle = !idle;
+            if tx.send(Observation::X11Idle { idle, idle_ms }).is_err() {
+                return Ok(());
+            }
+        }
+        thread::sleep(poll);
+        // A transient failure (X server restarting) keeps the last state
+        if let Ok(ms) = x11_idle_ms() {
+            idle_ms = ms;
+        }
+    }
+}
+
+fn x11_idle_ms() -> Result<u64, String> {
+    let output = Command::new("xprintidle")
+        .stderr(Stdio::null())
+        .output()
+        .map_err(|err| format!("run xprintidle: {err}"))?;
+    if !output.status.success() {
+        return Err("xprintidle failed (is the MIT-SCREEN-SAVER extension available?)".to_string());
+    }
+    let text = String::from_utf8_lossy(&output.stdout);
+    text.trim()
+        .parse::<u64>()
+        .map_err(|_| format!("unexpected xprintidle output: {}", text.trim()))
+}
@@ -0,0 +1,397 @@
+use aegis_client::{Client, InputStats};
+use aegis_config::ConfigLoader;
+use std::env;
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, Read};
+use std::mem::size_of;
+use std::path::PathBuf;
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const USAGE: &str = "usage: aegis-input-collector [--config <file>] [--set <key=value>]... [--interval-ms N] [--bin-ms N] [--device /dev/input/eventN]... [--replay <fixture>] [--print]";
+
+const COLLECTOR: &str = "input";
+
+// Linux input event codes (linux/input-event-codes.h)
+const EV_KEY: u16 = 0x01;
+const EV_REL: u16 = 0x02;
+const EV_ABS: u16 = 0x03;
+const BTN_MISC: u16 = 0x100;
+const KEY_BACKSPACE: u16 = 14;
+const KEY_LEFTCTRL: u16 = 29;
+const KEY_RIGHTCTRL: u16 = 97;
+const KEY_LEFTSHIFT: u16 = 42;
+const KEY_RIGHTSHIFT: u16 = 54;
+const KEY_V: u16 = 47;
+const KEY_INSERT: u16 = 110;
+
+/// `struct input_event`: a `struct timeval` followed by type, code, value.
+const EVENT_SIZE: usize = 2 * size_of::<usize>() + 8;
+
+/// What an input event means for the statistics. Key identities are
+/// reduced to this before leaving the device reader and are never stored.
+#[derive(Clone, Copy)]
+enum Activity {
+    Key,
+    Backspace,
+    Paste,
+    Pointer,
+}
+
+#[derive(Default)]
+struct Modifiers {
+    ctrl: u8,
+    shift: u8,
+}
+
+fn main() {
+    if let Err(err) = run() {
+        eprintln!("FAIL: {err}");
+        std::process::exit(1);
+    }
+}
+
+fn run() -> Result<(), String> {
+    let (loader, args) = ConfigLoader::from_args(env::args().skip(1))?;
+    let config = loader.load()?.config;
+
+    let mut interval_ms: u64 = 10_000;
+    let mut bin_ms: u64 = 1_000;
+    let mut devices = Vec::new();
+    let mut replay = None;
+    let mut print = false;
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--interval-ms" => interval_ms = parse_millis(args.next())?,
+            "--bin-ms" => bin_ms = parse_millis(args.next())?,
+            "--device" => devices.push(PathBuf::from(args.next().ok_or(USAGE)?)),
+            "--replay" => replay = Some(PathBuf::from(args.next().ok_or(USAGE)?)),
+            "--print" => print = true,
+            _ => return Err(USAGE.to_string()),
+        }
+    }
+    if bin_ms > interval_ms {
+        return Err("--bin-ms must not exceed --interval-ms".to_string());
+    }
+
+    let mut sink = if print {
+        Sink::Print
+    } else {
+        let client = Client::from_config(&config, COLLECTOR);
+        if replay.is_none() {
+            client.spawn_heartbeat(COLLECTOR, config.server.heartbeat_interval_ms);
+        }
+        Sink::Core(client)
+    };
+
+    if let Some(fixture) = replay {
+        return replay_fixture(&fixture, interval_ms, bin_ms, &mut sink);
+    }
+
+    if devices.is_empty() {
+        devices = detect_devices()?;
+    }
+    let (tx, rx) = mpsc::channel();
+    let started = Instant::now();
+    for device in devices {
+        let file = File::open(&device).map_err(|err| {
+            format!(
+                "open {}: {err} (reading input devices needs root or membership in the input group)",
+                device.display()
+            )
+        })?;
+        let tx = tx.clone();
+        thread::spawn(move || {
+            if let Err(err) = read_device(file, started, tx) {
+                eprintln!("{}: {err}", device.display());
+            }
+        });
+    }
+    drop(tx);
+
+    let mut stats = IntervalStats::new(interval_ms, bin_ms, 0);
+    loop {
+        let now_ms = started.elapsed().as_millis() as u64;
+        let timeout = Duration::from_millis(stats.end_ms().saturating_sub(now_ms));
+        match rx.recv_timeout(timeout) {
+            Ok((at_ms, activity)) => {
+                for payload in stats.advance(at_ms) {
+                    sink.send(payload);
+                }
+                stats.record(at_ms, activity);
+            }
+            Err(RecvTimeoutError::Timeout) => {
+                for payload in stats.advance(started.elapsed().as_millis() as u64) {
+                    sink.send(payload);
+                }
+            }
+            Err(RecvTimeoutError::Disconnected) => {
+                return Err("all input devices closed".to_string());
+            }
+        }
+    }
+}
+
+fn parse_millis(value: Option<String>) -> Result<u64, String> {
+    let value = value.ok_or(USAGE)?;
+    value
+        .parse::<u64>()
+        .ok()
+        .filter(|millis| *millis > 0)
+        .ok_or_else(|| format!("invalid milliseconds: {value}"))
+}
+
+/// Keyboards and pointers listed in `/proc/bus/input/devices`.
+fn detect_devices() -> Result<Vec<PathBuf>, String> {
+    let content = fs::read_to_string("/proc/bus/input/devices")
+        .map_err(|err| format!("read /proc/bus/input/devices: {err}"))?;
+    let mut devices = Vec::new();
+    for line in content.lines() {
+        let Some(handlers) = line.strip_prefix("H: Handlers=") else {
+            continue;
+        };
+        let handlers: Vec<&str> = handlers.split_whitespace().collect();
+        let is_input = handlers
+            .iter()
+            .any(|handler| *handler == "kbd" || handler.starts_with("mouse"));
+        if !is_input {
+            continue;
+        }
+        if let Some(event) = handlers.iter().find(|handler| handler.starts_with("event")) {
+            devices.push(PathBuf::from("/dev/input").join(event));
+        }
+    }
+    if devices.is_empty() {
+        return Err("no keyboard or pointer devices found".to_string());
+    }
+    Ok(devices)
+}
+
+fn read_device(
+    mut file: File,
+    started: Instant,
+    tx: Sender<(u64, Activity)>,
+) -> Result<(), String> {
+    let mut modifiers = Modifiers::default();
+    let mut buf = [0u8; EVENT_SIZE];
+    loop {
+        file.read_exact(&mut buf)
+            .map_err(|err| format!("read event: {err}"))?;
+        let offset = EVENT_SIZE - 8;
+        let event_type = u16::from_ne_bytes([buf[offset], buf[offset + 1]]);
+        let code = u16::from_ne_bytes([buf[offset + 2], buf[offset + 3]]);
+        let value = i32::from_ne_bytes([
+            buf[offset + 4],
+            buf[offset + 5],
+            buf[offset + 6],
+            buf[offset + 7],
+        ]);
+        if let Some(activity) = classify(event_type, code, value, &mut modifiers) {
+            let at_ms = started.elapsed().as_millis() as u64;
+            if tx.send((at_ms, activity)).is_err() {
+                return Ok(());
+            }
+        }
+    }
+}
+
+fn classify(event_type: u16, code: u16, value: i32, modifiers: &mut Modifiers) -> Option<Activity> {
+    match event_type {
+        EV_KEY => {
+            let counter = match code {
+                KEY_LEFTCTRL | KEY_RIGHTCTRL => Some(&mut modifiers.ctrl),
+                KEY_LEFTSHIFT | KEY_RIGHTSHIFT => Some(&mut modifiers.shift),
+                _ => None,
+            };
+            if let Some(counter) = counter {
+                match value {
+                    1 => *counter = counter.saturating_add(1),
+                    0 => *counter = counter.saturating_sub(1),
+                    _ => {}
+                }
+                // Modifiers only change the key they are held with, so
+                // Ctrl+V is one paste rather than two keystrokes and a paste
+                return None;
+            }
+            // Only fresh presses count; releases and auto-repeat do not
+            if value != 1 {
+                return None;
+            }
+            if code >= BTN_MISC {
+                return Some(Activity::Pointer);
+            }
+            if (code == KEY_V && modifiers.ctrl > 0) || (code == KEY_INSERT && modifiers.shift > 0)
+            {
+                return Some(Activity::Paste);
+            }
+            if code == KEY_BACKSPACE {
+                return Some(Activity::Backspace);
+            }
+            Some(Activity::Key)
+        }
+        EV_REL | EV_ABS => Some(Activity::Pointer),
+        _ => None,
+    }
+}
+
+/// Counters for one interval. `idle_bins` splits the interval into
+/// `bin_ms` slices and marks each slice 1 when no input was seen in it.
+struct IntervalStats {
+    interval_ms: u64,
+    bin_ms: u64,
+    start_ms: u64,
+    key_count: u64,
+    backspace_count: u64,
+    paste_count: u64,
+    active_bins: Vec<bool>,
+}
+
+impl IntervalStats {
+    fn new(interval_ms: u64, bin_ms: u64, start_ms: u64) -> Self {
+        let bins = interval_ms.div_ceil(bin_ms) as usize;
+        Self {
+            interval_ms,
+            bin_ms,
+            start_ms,
+            key_count: 0,
+            backspace_count: 0,
+            paste_count: 0,
+            active_bins: vec![false; bins],
+        }
+    }
+
+    fn end_ms(&self) -> u64 {
+        self.start_ms + self.interval_ms
+    }
+
+    fn record(&mut self, at_ms: u64, activity: Activity) {
+        let bin = (at_ms.saturating_sub(self.start_ms) / self.bin_ms) as usize;
+        if let Some(active) = self.active_bins.get_mut(bin) {
+            *active = true;
+        }
+        match activity {
+            Activity::Key => self.key_count += 1,
+            // Backspace and paste are keystrokes too
+            Activity::Backspace => {
+                self.key_count += 1;
+                self.backspace_count += 1;
+            }
+            Activity::Paste => {
+                self.key_count += 1;
+                self.paste_count += 1;
+            }
+            Activity::Pointer => {}
+        }
+    }
+
+    /// Closes every interval that ended at or before `now_ms`.
+    fn advance(&mut self, now_ms: u64) -> Vec<InputStats> {
+        let mut payloads = Vec::new();
+        while now_ms >= self.end_ms() {
+            payloads.push(self.payload(self.interval_ms));
+            *self = Self::new(self.interval_ms, self.bin_ms, self.end_ms());
+        }
+        payloads
+    }
+
+    /// Closes the current interval early, e.g. at the end of a replay.
+    fn finish(&mut self, now_ms: u64) -> Option<InputStats> {
+        let elapsed = now_ms.saturating_sub(self.start_ms).min(self.interval_ms);
+        if elapsed == 0 {
+            return None;
+        }
+        self.active_bins.truncate(elapsed.div_ceil(self.bin_ms) as usize);
+        Some(self.payload(elapsed))
+    }
+
+    fn payload(&self, interval_ms: u64) -> InputStats {
+        let idle_bins: Vec<u8> = self
+            .active_bins
+            .iter()
+            .map(|active| if *active { 0 } else { 1 })
+            .collect();
+        InputStats {
+            interval_ms,
+            key_count: self.key_count,
+            backspace_count: self.backspace_count,
+            paste_count: self.paste_count,
+            idle_bin_ms: Some(self.bin_ms),
+            idle_bins: Some(idle_bins),
+        }
+    }
+}
+
+/// Replays a text fixture of `<time_ms> <type> <code> <value>` lines
+/// (`#` starts a comment) through the same classification and
+/// aggregation as live devices.
+fn replay_fixture(
+    path: &PathBuf,
+    interval_ms: u64,
+    bin_ms: u64,
+    sink: &mut Sink,
+) -> Result<(), String> {
+    let file = File::open(path).map_err(|err| format!("open {}: {err}", path.display()))?;
+    let mut modifiers = Modifiers::default();
+    let mut stats = IntervalStats::new(interval_ms, bin_ms, 0);
+    let mut last_ms = 0;
+
+    for (index, line) in BufReader::new(file).lines().enumerate() {
+        let line = line.map_err(|err| format!("read fixture: {err}"))?;
+        let line = line.split('#').next().unwrap_or_default().trim();
+        if line.is_empty() {
+            continue;
+        }
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let parsed = match fields.as_slice() {
+            [at, event_type, code, value] => at
+                .parse::<u64>()
+                .ok()
+                .zip(event_type.parse::<u16>().ok())
+                .zip(code.parse::<u16>().ok())
+                .zip(value.parse::<i32>().ok()),
+            _ => None,
+        };
+        let Some((((at_ms, event_type), code), value)) = parsed else {
+            return Err(format!("invalid fixture line {}", index + 1));
+        };
+        if at_ms < last_ms {
+            return Err(format!("fixture line {} goes back in time", index + 1));
+        }
+        last_ms = at_ms;
+
+        for payload in stats.advance(at_ms) {
+            sink.send(payload);
+        }
+        if let Some(activity) = classify(event_type, code, value, &mut modifiers) {
+            stats.record(at_ms, activity);
+        }
+    }
+
+    if let Some(payload) = stats.finish(last_ms + 1) {
+        sink.send(payload);
+    }
+    Ok(())
+}
+
+enum Sink {
+    Print,
+    Core(Client),
+}
+
+impl Sink {
+    fn send(&mut self, stats: InputStats) {
+        match self {
+            Sink::Print => match serde_json::to_value(&stats) {
+                Ok(payload) => println!("{payload}"),
+                Err(err) => eprintln!("encode input_stats: {err}"),
+            },
+            Sink::Core(client) => {
+                if let Err(err) = client.send_input_stats(&stats) {
+                    eprintln!("send input_stats: {err}");
+                }
+            }
+        }
+    }
+}
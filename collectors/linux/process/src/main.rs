@@ -0,0 +1,343 @@
+mod netlink;
+mod procfs;
+mod redact;
+
+use aegis_client::Client;
+use aegis_config::ConfigLoader;
+use netlink::{KernelEvent, ProcConnector};
+use procfs::ProcessInfo;
+use redact::{ArgvMode, Redactor};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::env;
+use std::fs::File;
+use std::io::{self, Read};
+use std::os::unix::fs::MetadataExt;
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+const USAGE: &str = "usage: aegis-process-collector [--config <file>] [--set <key=value>]... [--backend auto|netlink|proc] [--poll-ms N] [--argv full|redacted|none] [--redact <keyword>]... [--no-hash] [--include-existing]";
+
+const COLLECTOR: &str = "process";
+
+#[derive(Clone, Copy, PartialEq)]
+enum Backend {
+    Auto,
+    Netlink,
+    Proc,
+}
+
+struct Tracked {
+    start_ticks: Option<u64>,
+    exe: Option<String>,
+    /// `None` for processes that were already running at startup.
+    seen_start: Option<Instant>,
+    reported: bool,
+}
+
+struct Collector {
+    client: Client,
+    redactor: Redactor,
+    hash: bool,
+    hash_cache: HashMap<(u64, u64, i64, u64), String>,
+    processes: HashMap<i32, Tracked>,
+    own_pid: i32,
+}
+
+fn main() {
+    if let Err(err) = run() {
+        eprintln!("FAIL: {err}");
+        std::process::exit(1);
+    }
+}
+
+fn run() -> Result<(), String> {
+    let (loader, args) = ConfigLoader::from_args(env::args().skip(1))?;
+    let config = loader.load()?.config;
+
+    let mut backend = Backend::Auto;
+    let mut poll = Duration::from_millis(500);
+    let mut argv_mode = ArgvMode::Redacted;
+    let mut keywords = Vec::new();
+    let mut hash = true;
+    let mut include_existing = false;
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--backend" => {
+                backend = match args.next().ok_or(USAGE)?.as_str() {
+                    "auto" => Backend::Auto,
+                    "netlink" => Backend::Netlink,
+                    "proc" => Backend::Proc,
+                    other => return Err(format!("unsupported backend: {other}")),
+                }
+            }
+            "--poll-ms" => {
+                let value = args.next().ok_or(USAGE)?;
+                let millis = value
+                    .parse::<u64>()
+                    .ok()
+                    .filter(|millis| *millis > 0)
+                    .ok_or_else(|| format!("invalid poll interval: {value}"))?;
+                poll = Duration::from_millis(millis);
+            }
+            "--argv" => {
+                argv_mode = match args.next().ok_or(USAGE)?.as_str() {
+                    "full" => ArgvMode::Full,
+                    "redacted" => ArgvMode::Redacted,
+                    "none" => ArgvMode::None,
+                    other => return Err(format!("unsupported argv mode: {other}")),
+                }
+            }
+            "--redact" => keywords.push(args.next().ok_or(USAGE)?),
+            "--no-hash" => hash = false,
+            "--include-existing" => include_existing = true,
+            _ => return Err(USAGE.to_string()),
+        }
+    }
+
+    let connector = match backend {
+        Backend::Proc => None,
+        Backend::Netlink => Some(ProcConnector::open()?),
+        Backend::Auto => match ProcConnector::open() {
+            Ok(connector) => Some(connector),
+            Err(err) => {
+                eprintln!("{err}; falling back to /proc polling");
+                None
+            }
+        },
+    };
+
+    let client = Client::from_config(&config, COLLECTOR);
+    client.spawn_heartbeat(COLLECTOR, config.server.heartbeat_interval_ms);
+    let mut collector = Collector {
+        client,
+        redactor: Redactor::new(argv_mode, keywords),
+        hash,
+        hash_cache: HashMap::new(),
+        processes: HashMap::new(),
+        own_pid: std::process::id() as i32,
+    };
+    // Subscribe before the snapshot so nothing starting in between is missed
+    collector.snapshot(include_existing);
+
+    match connector {
+        Some(connector) => {
+            eprintln!("Watching processes via netlink proc connector");
+            collector.run_netlink(connector)
+        }
+        None => {
+            eprintln!("Watching processes by polling /proc every {}ms", poll.as_millis());
+            collector.run_polling(poll)
+        }
+    }
+}
+
+impl Collector {
+    fn snapshot(&mut self, report: bool) {
+        for pid in procfs::list_pids() {
+            let Some(info) = ProcessInfo::read(pid) else {
+                continue;
+            };
+            if info.is_kernel_thread() || pid == self.own_pid {
+                continue;
+            }
+            if report {
+                self.report_started(&info, true);
+            }
+            self.processes.insert(
+                pid,
+                Tracked {
+                    start_ticks: info.start_ticks,
+                    exe: info.exe,
+                    seen_start: None,
+                    reported: report,
+                },
+            );
+        }
+    }
+
+    fn run_netlink(&mut self, mut connector: ProcConnector) -> Result<(), String> {
+        // Reading /proc and talking to the server must not hold up the
+        // socket, or the kernel drops events.
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || loop {
+            match connector.next_events() {
+                Ok(events) => {
+                    if events.into_iter().any(|event| tx.send(event).is_err()) {
+                        return;
+                    }
+                }
+                Err(err) => {
+                    eprintln!("{err}");
+                    return;
+                }
+            }
+        });
+
+        for event in rx {
+            match event {
+                KernelEvent::Exec { pid } => self.on_exec(pid),
+                KernelEvent::Exit { pid, status } => self.on_exit(pid, Some(status)),
+            }
+        }
+        Err("proc connector stopped".to_string())
+    }
+
+    fn run_polling(&mut self, poll: Duration) -> Result<(), String> {
+        loop {
+            thread::sleep(poll);
+            let mut alive = Vec::new();
+            for pid in procfs::list_pids() {
+                if pid == self.own_pid {
+                    continue;
+                }
+                let start_ticks = procfs::read_start_ticks(pid);
+                match self.processes.get(&pid) {
+                    Some(tracked) if tracked.start_ticks == start_ticks => {}
+                    Some(_) => {
+                        // pid reused between two polls
+                        self.on_exit(pid, None);
+                        self.on_exec(pid);
+                    }
+                    None => self.on_exec(pid),
+                }
+                alive.push(pid);
+            }
+            alive.sort_unstable();
+            let gone: Vec<i32> = self
+                .processes
+                .keys()
+                .filter(|pid| alive.binary_search(pid).is_err())
+                .copied()
+                .collect();
+            for pid in gone {
+                self.on_exit(pid, None);
+            }
+        }
+    }
+
+    fn on_exec(&mut self, pid: i32) {
+        if pid == self.own_pid {
+            return;
+        }
+        let info = ProcessInfo::read(pid);
+        if info.as_ref().is_some_and(ProcessInfo::is_kernel_thread) {
+            return;
+        }
+        let tracked = match info {
+            Some(info) => {
+                self.report_started(&info, false);
+                Tracked {
+                    start_ticks: info.start_ticks,
+                    exe: info.exe,
+                    seen_start: Some(Instant::now()),
+                    reported: true,
+                }
+            }
+            // Exited before /proc could be read
+            None => {
+                self.send("process_started", json!({ "pid": pid }));
+                Tracked {
+                    start_ticks: None,
+                    exe: None,
+                    seen_start: Some(Instant::now()),
+                    reported: true,
+                }
+            }
+        };
+        self.processes.insert(pid, tracked);
+    }
+
+    /// Only processes whose start was reported get an exit event, so every
+    /// `process_exited` in the bundle has a matching `process_started`
+    /// (forked children that never exec are not reported either way).
+    fn on_exit(&mut self, pid: i32, status: Option<u32>) {
+        let Some(tracked) = self.processes.remove(&pid) else {
+            return;
+        };
+        if !tracked.reported {
+            return;
+        }
+        let mut payload = json!({ "pid": pid });
+        if let Some(exe) = tracked.exe {
+            payload["exe"] = json!(exe);
+        }
+        if let Some(status) = status {
+            let signal = status & 0x7f;
+            if signal == 0 {
+                payload["exit_code"] = json!((status >> 8) & 0xff);
+            } else {
+                payload["signal"] = json!(signal);
+            }
+        }
+        if let Some(since) = tracked.seen_start {
+            payload["duration_ms"] = json!(since.elapsed().as_millis() as u64);
+        }
+        self.send("process_exited", payload);
+    }
+
+    fn report_started(&mut self, info: &ProcessInfo, existing: bool) {
+        let mut payload = json!({ "pid": info.pid });
+        if let Some(ppid) = info.ppid {
+            payload["ppid"] = json!(ppid);
+        }
+        if let Some(uid) = info.uid {
+            payload["uid"] = json!(uid);
+        }
+        if let Some(exe) = info.exe.as_ref() {
+            payload["exe"] = json!(exe);
+            if self.hash {
+                if let Some(hash) = self.exe_hash(info.pid) {
+                    payload["exe_hash"] = json!(hash);
+                }
+            }
+        }
+        if let Some(argv) = self.redactor.apply(&info.argv) {
+            payload["argv"] = json!(argv);
+        }
+        if existing {
+            payload["existing"] = json!(true);
+        }
+        self.send("process_started", payload);
+    }
+
+    /// Hashes through `/proc/<pid>/exe` so replaced or deleted binaries
+    /// still hash to what is actually running; cached per file version.
+    fn exe_hash(&mut self, pid: i32) -> Option<String> {
+        let mut file = File::open(format!("/proc/{pid}/exe")).ok()?;
+        let meta = file.metadata().ok()?;
+        let key = (meta.dev(), meta.ino(), meta.mtime(), meta.size());
+        if let Some(hash) = self.hash_cache.get(&key) {
+            return Some(hash.clone());
+        }
+        let hash = hash_reader(&mut file).ok()?;
+        self.hash_cache.insert(key, hash.clone());
+        Some(hash)
+    }
+
+    fn send(&mut self, message_type: &str, payload: Value) {
+        if let Err(err) = self.client.send_event(message_type, payload) {
+            eprintln!("send {message_type}: {err}");
+        }
+    }
+}
+
+fn hash_reader(reader: &mut impl Read) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    let mut buf = [0u8; 64 * 1024];
+    loop {
+        let read = reader.read(&mut buf)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buf[..read]);
+    }
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect())
+}
@@ -0,0 +1,159 @@
+use std::io;
+use std::mem;
+
+// linux/connector.h and linux/cn_proc.h
+const CN_IDX_PROC: u32 = 1;
+const CN_VAL_PROC: u32 = 1;
+const PROC_CN_MCAST_LISTEN: u32 = 1;
+const PROC_EVENT_EXEC: u32 = 0x0000_0002;
+const PROC_EVENT_EXIT: u32 = 0x8000_0000;
+
+const NLMSG_HEADER_LEN: usize = 16;
+const CN_MSG_LEN: usize = 20;
+// what, cpu, timestamp_ns
+const PROC_EVENT_HEADER_LEN: usize = 16;
+
+/// A process event from the kernel, already narrowed to whole processes
+/// (thread execs and exits are dropped).
+pub enum KernelEvent {
+    Exec { pid: i32 },
+    /// `status` is the wait(2) status of the thread group leader.
+    Exit { pid: i32, status: u32 },
+}
+
+/// Subscription to the proc connector. Requires CAP_NET_ADMIN.
+pub struct ProcConnector {
+    fd: i32,
+    buf: Vec<u8>,
+}
+
+impl ProcConnector {
+    pub fn open() -> Result<Self, String> {
+        let fd = unsafe {
+            libc::socket(
+                libc::AF_NETLINK,
+                libc::SOCK_DGRAM | libc::SOCK_CLOEXEC,
+                libc::NETLINK_CONNECTOR,
+            )
+        };
+        if fd < 0 {
+            return Err(format!("netlink socket: {}", io::Error::last_os_error()));
+        }
+        let connector = Self {
+            fd,
+            buf: vec![0u8; 64 * 1024],
+        };
+
+        let mut addr: libc::sockaddr_nl = unsafe { mem::zeroed() };
+        addr.nl_family = libc::AF_NETLINK as libc::sa_family_t;
+        addr.nl_pid = std::process::id();
+        addr.nl_groups = CN_IDX_PROC;
+        let result = unsafe {
+            libc::bind(
+                fd,
+                (&addr as *const libc::sockaddr_nl).cast(),
+                mem::size_of::<libc::sockaddr_nl>() as libc::socklen_t,
+            )
+        };
+        if result < 0 {
+            return Err(format!("netlink bind: {}", io::Error::last_os_error()));
+        }
+
+        let mut message = Vec::with_capacity(NLMSG_HEADER_LEN + CN_MSG_LEN + 4);
+        let total = (NLMSG_HEADER_LEN + CN_MSG_LEN + 4) as u32;
+        message.extend_from_slice(&total.to_ne_bytes());
+        message.extend_from_slice(&(libc::NLMSG_DONE as u16).to_ne_bytes());
+        message.extend_from_slice(&0u16.to_ne_bytes());
+        message.extend_from_slice(&0u32.to_ne_bytes());
+        message.extend_from_slice(&std::process::id().to_ne_bytes());
+        message.extend_from_slice(&CN_IDX_PROC.to_ne_bytes());
+        message.extend_from_slice(&CN_VAL_PROC.to_ne_bytes());
+        message.extend_from_slice(&0u32.to_ne_bytes());
+        message.extend_from_slice(&0u32.to_ne_bytes());
+        message.extend_from_slice(&4u16.to_ne_bytes());
+        message.extend_from_slice(&0u16.to_ne_bytes());
+        message.extend_from_slice(&PROC_CN_MCAST_LISTEN.to_ne_bytes());
+        let sent = unsafe { libc::send(fd, message.as_ptr().cast(), message.len(), 0) };
+        if sent < 0 {
+            return Err(format!("netlink subscribe: {}", io::Error::last_os_error()));
+        }
+        Ok(connector)
+    }
+
+    /// Blocks until the next datagram and returns the events it carries.
+    pub fn next_events(&mut self) -> Result<Vec<KernelEvent>, String> {
+        let len = loop {
+            let len = unsafe { libc::recv(self.fd, self.buf.as_mut_ptr().cast(), self.buf.len(), 0) };
+            if len >= 0 {
+                break len as usize;
+            }
+            let err = io::Error::last_os_error();
+            match err.raw_os_error() {
+                Some(libc::EINTR) => continue,
+                // The socket buffer overflowed; events were lost but the
+                // subscription is still valid.
+                Some(libc::ENOBUFS) => {
+                    eprintln!("proc connector overflowed; some events were lost");
+                    continue;
+                }
+                _ => return Err(format!("netlink recv: {err}")),
+            }
+        };
+
+        let mut events = Vec::new();
+        let mut offset = 0;
+        while offset + NLMSG_HEADER_LEN <= len {
+            let msg_len = read_u32(&self.buf, offset) as usize;
+            if msg_len < NLMSG_HEADER_LEN || offset + msg_len > len {
+                break;
+            }
+            let body = &self.buf[offset + NLMSG_HEADER_LEN..offset + msg_len];
+            if let Some(event) = parse_proc_event(body) {
+                events.push(event);
+            }
+            // NLMSG_ALIGN
+            offset += (msg_len + 3) & !3;
+        }
+        Ok(events)
+    }
+}
+
+impl Drop for ProcConnector {
+    fn drop(&mut self) {
+        unsafe { libc::close(self.fd) };
+    }
+}
+
+fn parse_proc_event(body: &[u8]) -> Option<KernelEvent> {
+    if body.len() < CN_MSG_LEN + PROC_EVENT_HEADER_LEN {
+        return None;
+    }
+    if read_u32(body, 0) != CN_IDX_PROC || read_u32(body, 4) != CN_VAL_PROC {
+        return None;
+    }
+    let event = &body[CN_MSG_LEN..];
+    let what = read_u32(event, 0);
+    let data = &event[PROC_EVENT_HEADER_LEN..];
+    match what {
+        PROC_EVENT_EXEC if data.len() >= 8 => {
+            let pid = read_u32(data, 0) as i32;
+            let tgid = read_u32(data, 4) as i32;
+            (pid == tgid).then_some(KernelEvent::Exec { pid })
+        }
+        PROC_EVENT_EXIT if data.len() >= 12 => {
+            let pid = read_u32(data, 0) as i32;
+            let tgid = read_u32(data, 4) as i32;
+            (pid == tgid).then_some(KernelEvent::Exit {
+                pid,
+                status: read_u32(data, 8),
+            })
+        }
+        _ => None,
+    }
+}
+
+fn read_u32(buf: &[u8], offset: usize) -> u32 {
+    let mut bytes = [0u8; 4];
+    bytes.copy_from_slice(&buf[offset..offset + 4]);
+    u32::from_ne_bytes(bytes)
+}
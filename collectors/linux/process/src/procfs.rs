@@ -0,0 +1,90 @@
+use std::fs;
+
+/// What `/proc/<pid>` tells us about a process at one moment.
+pub struct ProcessInfo {
+    pub pid: i32,
+    pub ppid: Option<i32>,
+    pub uid: Option<u32>,
+    pub exe: Option<String>,
+    pub argv: Vec<String>,
+    /// Start time in clock ticks since boot; with the pid it identifies a
+    /// process even after the pid is reused.
+    pub start_ticks: Option<u64>,
+}
+
+impl ProcessInfo {
+    pub fn read(pid: i32) -> Option<Self> {
+        let stat = fs::read_to_string(format!("/proc/{pid}/stat")).ok()?;
+        let (ppid, start_ticks) = parse_stat(&stat);
+        let uid = fs::read_to_string(format!("/proc/{pid}/status"))
+            .ok()
+            .and_then(|status| parse_uid(&status));
+        let exe = fs::read_link(format!("/proc/{pid}/exe"))
+            .ok()
+            .map(|path| path.to_string_lossy().to_string());
+        let argv = fs::read(format!("/proc/{pid}/cmdline"))
+            .map(|raw| parse_cmdline(&raw))
+            .unwrap_or_default();
+        Some(Self {
+            pid,
+            ppid,
+            uid,
+            exe,
+            argv,
+            start_ticks,
+        })
+    }
+
+    /// Kernel threads have no executable and are children of kthreadd.
+    pub fn is_kernel_thread(&self) -> bool {
+        self.pid == 2 || (self.ppid == Some(2) && self.exe.is_none())
+    }
+}
+
+/// Lists every pid currently in `/proc`.
+pub fn list_pids() -> Vec<i32> {
+    let Ok(entries) = fs::read_dir("/proc") else {
+        return Vec::new();
+    };
+    entries
+        .flatten()
+        .filter_map(|entry| entry.file_name().to_str()?.parse::<i32>().ok())
+        .collect()
+}
+
+/// Start time only, for cheap identity checks while polling.
+pub fn read_start_ticks(pid: i32) -> Option<u64> {
+    let stat = fs::read_to_string(format!("/proc/{pid}/stat")).ok()?;
+    parse_stat(&stat).1
+}
+
+/// Fields after the parenthesised command name, which may itself contain
+/// spaces or parentheses: state is field 3, ppid 4, starttime 22.
+fn parse_stat(stat: &str) -> (Option<i32>, Option<u64>) {
+    let Some((_, rest)) = stat.rsplit_once(')') else {
+        return (None, None);
+    };
+    let fields: Vec<&str> = rest.split_whitespace().collect();
+    let ppid = fields.get(1).and_then(|value| value.parse().ok());
+    let start_ticks = fields.get(19).and_then(|value| value.parse().ok());
+    (ppid, start_ticks)
+}
+
+/// Real uid from the `Uid:` line.
+fn parse_uid(status: &str) -> Option<u32> {
+    status
+        .lines()
+        .find_map(|line| line.strip_prefix("Uid:"))
+        .and_then(|ids| ids.split_whitespace().next())
+        .and_then(|uid| uid.parse().ok())
+}
+
+fn parse_cmdline(raw: &[u8]) -> Vec<String> {
+    let raw = raw.strip_suffix(&[0]).unwrap_or(raw);
+    if raw.is_empty() {
+        return Vec::new();
+    }
+    raw.split(|byte| *byte == 0)
+        .map(|arg| String::from_utf8_lossy(arg).to_string())
+        .collect()
+}
@@ -0,0 +1,342 @@
+use std::env;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+const USAGE: &str = "usage: aegis-linux-recorder <output_path> <seconds> [--codec hevc|h264] [--width N] [--height N] [--fps N] [--bitrate N] [--source auto|x11|pipewire] [--display :N]";
+
+static SIGNALLED: AtomicBool = AtomicBool::new(false);
+
+#[derive(Clone, Copy, PartialEq)]
+enum Source {
+    X11,
+    PipeWire,
+}
+
+struct Settings {
+    output_path: PathBuf,
+    seconds: u64,
+    codec: String,
+    width: u32,
+    height: u32,
+    fps: u32,
+    bitrate: u64,
+    source: Source,
+    display: String,
+}
+
+fn main() {
+    if let Err(err) = run() {
+        eprintln!("FAIL: {err}");
+        std::process::exit(1);
+    }
+}
+
+fn run() -> Result<(), String> {
+    let settings = parse_args(env::args().skip(1))?;
+    if let Some(parent) = settings.output_path.parent() {
+        if !parent.as_os_str().is_empty() {
+            fs::create_dir_all(parent).map_err(|err| format!("create dirs: {err}"))?;
+        }
+    }
+
+    if settings.source == Source::PipeWire {
+        check_pipewiregrab()?;
+    }
+
+    install_signal_handlers();
+    let stop_requested = watch_stdin();
+
+    let mut child = spawn_ffmpeg(&settings)?;
+    loop {
+        if let Some(status) = child.try_wait().map_err(|err| format!("wait ffmpeg: {err}"))? {
+            if !status.success() {
+                return Err(format!("ffmpeg exited with {status}"));
+            }
+            return Ok(());
+        }
+        if stop_requested.load(Ordering::SeqCst) || SIGNALLED.load(Ordering::SeqCst) {
+            return stop_ffmpeg(&mut child);
+        }
+        thread::sleep(Duration::from_millis(100));
+    }
+}
+
+fn parse_args(args: impl Iterator<Item = String>) -> Result<Settings, String> {
+    let mut args = args;
+    let output_path = args.next().ok_or(USAGE)?;
+    let seconds = args
+        .next()
+        .ok_or(USAGE)?
+        .parse::<u64>()
+        .ok()
+        .filter(|value| *value > 0)
+        .ok_or("invalid duration")?;
+
+    let mut settings = Settings {
+        output_path: PathBuf::from(output_path),
+        seconds,
+        codec: "hevc".to_string(),
+        width: 1280,
+        height: 720,
+        fps: 30,
+        bitrate: 2_000_000,
+        source: default_source(),
+        display: env::var("DISPLAY").unwrap_or_else(|_| ":0".to_string()),
+    };
+
+    while let Some(flag) = args.next() {
+        let value = args.next().ok_or_else(|| format!("{flag} requires a value"))?;
+        match flag.as_str() {
+            "--codec" => settings.codec = value,
+            "--width" => settings.width = parse_positive(&flag, &value)?,
+            "--height" => settings.height = parse_positive(&flag, &value)?,
+            "--fps" => settings.fps = parse_positive(&flag, &value)?,
+            "--bitrate" => settings.bitrate = parse_positive(&flag, &value)?,
+            "--source" => {
+                settings.source = match value.as_str() {
+                    "auto" => default_source(),
+                    "x11" => Source::X11,
+                    "pipewire" => Source::PipeWire,
+                    _ => return Err(format!("unsupported source: {value}")),
+                }
+            }
+            "--display" => settings.display = value,
+            _ => return Err(format!("unknown option: {flag}\n{USAGE}")),
+        }
+    }
+
+    if encoder_for(&settings.codec).is_none() {
+        return Err(format!("unsupported codec: {}", settings.codec));
+    }
+    Ok(settings)
+}
+
+fn parse_positive<T: std::str::FromStr + Default + PartialOrd>(
+    flag: &str,
+    value: &str,
+) -> Result<T, String> {
+    value
+        .parse::<T>()
+        .ok()
+        .filter(|parsed| *parsed > T::default())
+        .ok_or_else(|| format!("invalid value for {flag}: {value}"))
+}
+
+fn default_source() -> Source {
+    if env::var_os("WAYLAND_DISPLAY").is_some() {
+        Source::PipeWire
+    } else {
+        Source::X11
+    }
+}
+
+fn encoder_for(codec: &str) -> Option<&'static str> {
+    match codec {
+        "hevc" => Some("libx265"),
+        "h264" => Some("libx264"),
+        _ => None,
+    }
+}
+
+fn ffmpeg_args(settings: &Settings) -> Vec<String> {
+    let mut args: Vec<String> = vec!["-hide_banner".into(), "-loglevel".into(), "error".into()];
+
+    match settings.source {
+        Source::X11 => {
+            args.extend([
+                "-f".into(),
+                "x11grab".into(),
+                "-framerate".into(),
+                settings.fps.to_string(),
+                "-draw_mouse".into(),
+                "1".into(),
+                "-i".into(),
+                settings.display.clone(),
+            ]);
+        }
+        Source::PipeWire => {
+            // The pipewiregrab source negotiates the stream through the
+            // xdg-desktop-portal ScreenCast interface.
+            args.extend([
+                "-f".into(),
+                "lavfi".into(),
+                "-i".into(),
+                format!("pipewiregrab=framerate={}", settings.fps),
+            ]);
+        }
+    }
+
+    // The configured resolution is an upper bound; smaller screens are kept
+    // as they are, larger ones are scaled down preserving aspect ratio.
+    let scale = format!(
+        "scale=w='min({w},iw)':h='min({h},ih)':force_original_aspect_ratio=decrease:force_divisible_by=2",
+        w = settings.width,
+        h = settings.height,
+    );
+    let encoder = encoder_for(&settings.codec).unwrap_or("libx265");
+    args.extend([
+        "-t".into(),
+        settings.seconds.to_string(),
+        "-vf".into(),
+        scale,
+        "-r".into(),
+        settings.fps.to_string(),
+        "-c:v".into(),
+        encoder.into(),
+        "-preset".into(),
+        "veryfast".into(),
+        "-b:v".into(),
+        settings.bitrate.to_string(),
+        "-maxrate".into(),
+        settings.bitrate.to_string(),
+        "-bufsize".into(),
+        (settings.bitrate * 2).to_string(),
+        "-g".into(),
+        (settings.fps * 2).to_string(),
+        "-pix_fmt".into(),
+        "yuv420p".into(),
+    ]);
+    if settings.codec == "hevc" {
+        args.extend(["-tag:v".into(), "hvc1".into()]);
+    }
+    args.extend([
+        "-an".into(),
+        "-y".into(),
+        settings.output_path.to_string_lossy().to_string(),
+    ]);
+    args
+}
+
+fn ffmpeg_program() -> String {
+    env::var("AEGIS_FFMPEG").unwrap_or_else(|_| "ffmpeg".to_string())
+}
+
+/// pipewiregrab is not in released ffmpeg builds, so fail before the first
+/// segment rather than with ffmpeg's "No such filter".
+fn check_pipewiregrab() -> Result<(), String> {
+    let program = ffmpeg_program();
+    let output = Command::new(&program)
+        .args(["-hide_banner", "-filters"])
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .map_err(|err| format!("start {program}: {err}"))?;
+    if has_filter(&String::from_utf8_lossy(&output.stdout), "pipewiregrab") {
+        return Ok(());
+    }
+    Err(format!(
+        "{program} has no pipewiregrab filter, which --source pipewire needs; \
+         use an ffmpeg built with it (AEGIS_FFMPEG) or record XWayland with --source x11"
+    ))
+}
+
+/// Looks `name` up in `ffmpeg -filters` output, where each filter line is
+/// `<flags> <name> <pads> <description>`.
+fn has_filter(listing: &str, name: &str) -> bool {
+    listing
+        .lines()
+        .any(|line| line.split_whitespace().nth(1) == Some(name))
+}
+
+fn spawn_ffmpeg(settings: &Settings) -> Result<Child, String> {
+    let program = ffmpeg_program();
+    Command::new(&program)
+        .args(ffmpeg_args(settings))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::inherit())
+        .spawn()
+        .map_err(|err| format!("start {program}: {err}"))
+}
+
+/// Asks ffmpeg to finish the file (`q` on its stdin), forcing it after 5s.
+fn stop_ffmpeg(child: &mut Child) -> Result<(), String> {
+    if let Some(mut stdin) = child.stdin.take() {
+        let _ = stdin.write_all(b"q");
+    }
+
+    let wait_start = Instant::now();
+    while wait_start.elapsed() < Duration::from_secs(5) {
+        if child
+            .try_wait()
+            .map_err(|err| format!("wait ffmpeg: {err}"))?
+            .is_some()
+        {
+            return Ok(());
+        }
+        thread::sleep(Duration::from_millis(100));
+    }
+
+    let _ = child.kill();
+    let _ = child.wait();
+    Err("ffmpeg did not stop within 5s".to_string())
+}
+
+/// A newline or EOF on stdin ends the segment early, like the macOS recorder.
+fn watch_stdin() -> Arc<AtomicBool> {
+    let stop_requested = Arc::new(AtomicBool::new(false));
+    let flag = Arc::clone(&stop_requested);
+    thread::spawn(move || {
+        let mut stdin = io::stdin();
+        let mut byte = [0u8; 1];
+        loop {
+            match stdin.read(&mut byte) {
+                Ok(0) | Err(_) => break,
+                Ok(_) if byte[0] == b'\n' => break,
+                Ok(_) => {}
+            }
+        }
+        flag.store(true, Ordering::SeqCst);
+    });
+    stop_requested
+}
+
+extern "C" fn handle_signal(_signal: libc::c_int) {
+    SIGNALLED.store(true, Ordering::SeqCst);
+}
+
+fn install_signal_handlers() {
+    let handler = handle_signal as extern "C" fn(libc::c_int) as libc::sighandler_t;
+    unsafe {
+        libc::signal(libc::SIGINT, handler);
+        libc::signal(libc::SIGTERM, handler);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings(args: &[&str]) -> Settings {
+        parse_args(args.iter().map(|arg| arg.to_string())).unwrap()
+    }
+
+    #[test]
+    fn pipewire_source_reads_pipewiregrab_through_lavfi() {
+        let settings = settings(&["out.mp4", "5", "--source", "pipewire", "--fps", "15"]);
+        let args = ffmpeg_args(&settings);
+        let input = args.iter().position(|arg| arg == "-i").unwrap();
+        assert_eq!(args[input - 2..input], ["-f", "lavfi"]);
+        assert_eq!(args[input + 1], "pipewiregrab=framerate=15");
+        assert!(!args.iter().any(|arg| arg == "x11grab"));
+        assert_eq!(args.last().unwrap(), "out.mp4");
+        assert_eq!(args[args.len() - 3..args.len() - 1], ["-an", "-y"]);
+    }
+
+    #[test]
+    fn has_filter_matches_the_name_column_only() {
+        let listing = "Filters:\n  T.. = Timeline support\n \
+                       ... pipewiregrab      |->V       Capture screen through PipeWire.\n \
+                       ... nullsrc           |->V       Null video source.\n";
+        assert!(has_filter(listing, "pipewiregrab"));
+        assert!(has_filter(listing, "nullsrc"));
+        assert!(!has_filter(listing, "x11grab"));
+        assert!(!has_filter(listing, "Capture"));
+    }
+}
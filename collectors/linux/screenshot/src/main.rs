@@ -0,0 +1,339 @@
+use aegis_client::{Client, ShotUpload};
+use aegis_config::ConfigLoader;
+use std::env;
+use std::fs;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const USAGE: &str = "usage: aegis-screenshot-collector [--config <file>] [--set <key=value>]... [--interval-secs N] [--on-focus] [--focus-delay-ms N] [--min-gap-ms N] [--format jpeg|webp] [--quality 1-100] [--source auto|x11|wayland] [--display :N]";
+
+const COLLECTOR: &str = "screenshot";
+
+#[derive(Clone, Copy, PartialEq)]
+enum Source {
+    X11,
+    Wayland,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Format {
+    Jpeg,
+    Webp,
+}
+
+impl Format {
+    fn extension(self) -> &'static str {
+        match self {
+            Format::Jpeg => "jpg",
+            Format::Webp => "webp",
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Format::Jpeg => "jpeg",
+            Format::Webp => "webp",
+        }
+    }
+
+    /// ffmpeg encoder options for a 1-100 quality; mjpeg's `-q:v` runs
+    /// from 2 (best) to 31 (worst).
+    fn encoder_args(self, quality: u32) -> Vec<String> {
+        match self {
+            Format::Jpeg => {
+                let q = 31 - (quality - 1) * 29 / 99;
+                vec![
+                    "-c:v".into(),
+                    "mjpeg".into(),
+                    "-q:v".into(),
+                    q.to_string(),
+                    "-pix_fmt".into(),
+                    "yuvj420p".into(),
+                ]
+            }
+            Format::Webp => vec![
+                "-c:v".into(),
+                "libwebp".into(),
+                "-quality".into(),
+                quality.to_string(),
+            ],
+        }
+    }
+}
+
+struct Options {
+    interval: Option<Duration>,
+    on_focus: bool,
+    focus_delay: Duration,
+    min_gap: Duration,
+    format: Format,
+    quality: u32,
+    source: Source,
+    display: String,
+}
+
+fn main() {
+    if let Err(err) = run() {
+        eprintln!("FAIL: {err}");
+        std::process::exit(1);
+    }
+}
+
+fn run() -> Result<(), String> {
+    let (loader, args) = ConfigLoader::from_args(env::args().skip(1))?;
+    let config = loader.load()?.config;
+    let options = parse_options(args)?;
+    if options.interval.is_none() && !options.on_focus {
+        return Err("nothing to do: set --interval-secs or --on-focus".to_string());
+    }
+
+    let temp_dir = config.temp_dir();
+    fs::create_dir_all(&temp_dir).map_err(|err| format!("create {}: {err}", temp_dir.display()))?;
+
+    let (tx, rx) = mpsc::channel();
+    if options.on_focus {
+        let source = options.source;
+        thread::spawn(move || {
+            if let Err(err) = watch_focus(source, tx) {
+                eprintln!("focus watcher: {err}");
+            }
+        });
+    }
+
+    let client = Client::from_config(&config, COLLECTOR);
+    client.spawn_heartbeat(COLLECTOR, config.server.heartbeat_interval_ms);
+    let mut next_interval = options.interval.map(|interval| Instant::now() + interval);
+    let mut pending_focus: Option<Instant> = None;
+    let mut last_shot: Option<Instant> = None;
+    let mut focus_alive = options.on_focus;
+    loop {
+        let deadline = [next_interval, pending_focus].into_iter().flatten().min();
+        let timeout = deadline
+            .map(|deadline| deadline.saturating_duration_since(Instant::now()))
+            .unwrap_or(Duration::from_secs(3600));
+        match rx.recv_timeout(timeout) {
+            // A new focus change restarts the delay so the shot shows the
+            // window that focus finally settled on.
+            Ok(()) => pending_focus = Some(Instant::now() + options.focus_delay),
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => {
+                if focus_alive {
+                    focus_alive = false;
+                    if next_interval.is_none() {
+                        return Err("focus watcher stopped".to_string());
+                    }
+                }
+                if next_interval.is_some() {
+                    thread::sleep(timeout);
+                }
+            }
+        }
+
+        let now = Instant::now();
+        if let Some(due) = pending_focus {
+            if now >= due {
+                let gap_ok = last_shot.is_none_or(|last| now.duration_since(last) >= options.min_gap);
+                if gap_ok {
+                    pending_focus = None;
+                    take_shot(&client, &options, &temp_dir, "focus");
+                    last_shot = Some(Instant::now());
+                } else if let Some(last) = last_shot {
+                    pending_focus = Some(last + options.min_gap);
+                }
+            }
+        }
+        if let (Some(due), Some(interval)) = (next_interval, options.interval) {
+            if Instant::now() >= due {
+                take_shot(&client, &options, &temp_dir, "interval");
+                last_shot = Some(Instant::now());
+                // Skip missed ticks instead of bursting after a stall
+                next_interval = Some((due + interval).max(Instant::now()));
+            }
+        }
+    }
+}
+
+fn parse_options(args: Vec<String>) -> Result<Options, String> {
+    let mut options = Options {
+        interval: Some(Duration::from_secs(60)),
+        on_focus: false,
+        focus_delay: Duration::from_millis(500),
+        min_gap: Duration::from_secs(2),
+        format: Format::Jpeg,
+        quality: 75,
+        source: default_source(),
+        display: env::var("DISPLAY").unwrap_or_else(|_| ":0".to_string()),
+    };
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--interval-secs" => {
+                let secs = parse_number(args.next())?;
+                options.interval = (secs > 0).then(|| Duration::from_secs(secs));
+            }
+            "--on-focus" => options.on_focus = true,
+            "--focus-delay-ms" => {
+                options.focus_delay = Duration::from_millis(parse_number(args.next())?)
+            }
+            "--min-gap-ms" => options.min_gap = Duration::from_millis(parse_number(args.next())?),
+            "--format" => {
+                options.format = match args.next().ok_or(USAGE)?.as_str() {
+                    "jpeg" | "jpg" => Format::Jpeg,
+                    "webp" => Format::Webp,
+                    other => return Err(format!("unsupported format: {other}")),
+                }
+            }
+            "--quality" => {
+                let quality = parse_number(args.next())?;
+                if !(1..=100).contains(&quality) {
+                    return Err(format!("quality must be 1-100, got {quality}"));
+                }
+                options.quality = quality as u32;
+            }
+            "--source" => {
+                options.source = match args.next().ok_or(USAGE)?.as_str() {
+                    "auto" => default_source(),
+                    "x11" => Source::X11,
+                    "wayland" => Source::Wayland,
+                    other => return Err(format!("unsupported source: {other}")),
+                }
+            }
+            "--display" => options.display = args.next().ok_or(USAGE)?,
+            _ => return Err(USAGE.to_string()),
+        }
+    }
+    Ok(options)
+}
+
+fn parse_number(value: Option<String>) -> Result<u64, String> {
+    let value = value.ok_or(USAGE)?;
+    value
+        .parse::<u64>()
+        .map_err(|_| format!("invalid number: {value}"))
+}
+
+fn default_source() -> Source {
+    if env::var_os("WAYLAND_DISPLAY").is_some() {
+        Source::Wayland
+    } else {
+        Source::X11
+    }
+}
+
+/// Captures one shot and hands it to the core server, which numbers it.
+/// Retries resend the same message, so a lost reply never yields a
+/// second copy under another number.
+fn take_shot(client: &Client, options: &Options, temp_dir: &Path, trigger: &str) {
+    let temp_path = temp_dir.join(format!(
+        "aegis-shot-{}.{}",
+        std::process::id(),
+        options.format.extension()
+    ));
+    if let Err(err) = capture(options, &temp_path) {
+        eprintln!("capture: {err}");
+        return;
+    }
+
+    let result = client.upload_shot(&ShotUpload {
+        source_path: temp_path.clone(),
+        rel_path: None,
+        trigger: Some(trigger.to_string()),
+        format: Some(options.format.name().to_string()),
+        quality: Some(options.quality),
+    });
+    if let Err(err) = result {
+        eprintln!("send shot_saved: {err}");
+    }
+    let _ = fs::remove_file(&temp_path);
+}
+
+fn capture(options: &Options, output: &Path) -> Result<(), String> {
+    let ffmpeg = env::var("AEGIS_FFMPEG").unwrap_or_else(|_| "ffmpeg".to_string());
+    let mut args: Vec<String> = vec!["-hide_banner".into(), "-loglevel".into(), "error".into()];
+
+    // grim (wlroots screencopy) writes a PNG that ffmpeg re-encodes
+    let grim_png = output.with_extension("png");
+    match options.source {
+        Source::X11 => args.extend([
+            "-f".into(),
+            "x11grab".into(),
+            "-i".into(),
+            options.display.clone(),
+        ]),
+        Source::Wayland => {
+            let status = Command::new("grim")
+                .arg(&grim_png)
+                .stderr(Stdio::inherit())
+                .status()
+                .map_err(|err| format!("start grim: {err}"))?;
+            if !status.success() {
+                return Err(format!("grim exited with {status}"));
+            }
+            args.extend(["-i".into(), grim_png.to_string_lossy().to_string()]);
+        }
+    }
+    args.extend(["-frames:v".into(), "1".into()]);
+    args.extend(options.format.encoder_args(options.quality));
+    args.extend(["-y".into(), output.to_string_lossy().to_string()]);
+
+    let status = Command::new(&ffmpeg)
+        .args(&args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::inherit())
+        .status()
+        .map_err(|err| format!("start {ffmpeg}: {err}"));
+    if options.source == Source::Wayland {
+        let _ = fs::remove_file(&grim_png);
+    }
+    let status = status?;
+    if !status.success() {
+        return Err(format!("{ffmpeg} exited with {status}"));
+    }
+    Ok(())
+}
+
+/// Signals every change of the focused window.
+fn watch_focus(source: Source, tx: Sender<()>) -> Result<(), String> {
+    let mut command = if env::var_os("SWAYSOCK").is_some() {
+        let mut command = Command::new("swaymsg");
+        command.args(["-m", "-t", "subscribe", "[\"window\"]"]);
+        command
+    } else if source == Source::X11 {
+        let mut command = Command::new("xprop");
+        command.args(["-root", "-spy", "_NET_ACTIVE_WINDOW"]);
+        command
+    } else {
+        return Err("focus changes need X11 or sway".to_string());
+    };
+    let mut child = command
+        .stdout(Stdio::piped())
+        .stderr(Stdio::inherit())
+        .spawn()
+        .map_err(|err| format!("start {:?}: {err}", command.get_program()))?;
+    let stdout = child.stdout.take().ok_or("focus watcher stdout unavailable")?;
+
+    let mut last_line = String::new();
+    for line in BufReader::new(stdout).lines() {
+        let Ok(line) = line else {
+            break;
+        };
+        let is_focus = if line.starts_with('{') {
+            line.contains("\"change\": \"focus\"") || line.contains("\"change\":\"focus\"")
+        } else {
+            line != last_line
+        };
+        last_line = line;
+        if is_focus && tx.send(()).is_err() {
+            break;
+        }
+    }
+    let _ = child.kill();
+    let _ = child.wait();
+    Err("focus watcher exited".to_string())
+}
@@ -0,0 +1,171 @@
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+/// The bytes appended to the cast file since the previous chunk.
+pub struct Chunk {
+    pub index: u64,
+    pub offset: u64,
+    pub length: u64,
+    pub sha256: String,
+    pub events: u64,
+}
+
+/// Writes an asciicast v2 file and tracks it in hashable chunks.
+pub struct CastWriter {
+    file: File,
+    path: PathBuf,
+    start: Instant,
+    /// Trailing bytes of an incomplete UTF-8 sequence, per event code.
+    pending_output: Vec<u8>,
+    pending_input: Vec<u8>,
+    written: u64,
+    file_hasher: Sha256,
+    chunk_index: u64,
+    chunk_offset: u64,
+    chunk_hasher: Sha256,
+    chunk_events: u64,
+}
+
+impl CastWriter {
+    pub fn create(path: &Path, header: Value) -> Result<Self, String> {
+        let file = File::create(path).map_err(|err| format!("create {}: {err}", path.display()))?;
+        let mut writer = Self {
+            file,
+            path: path.to_path_buf(),
+            start: Instant::now(),
+            pending_output: Vec::new(),
+            pending_input: Vec::new(),
+            written: 0,
+            file_hasher: Sha256::new(),
+            chunk_index: 0,
+            chunk_offset: 0,
+            chunk_hasher: Sha256::new(),
+            chunk_events: 0,
+        };
+        let line = serde_json::to_string(&header).map_err(|err| err.to_string())?;
+        writer.write_line(&line)?;
+        Ok(writer)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn output(&mut self, data: &[u8]) -> Result<(), String> {
+        let text = take_utf8(&mut self.pending_output, data);
+        self.event("o", text)
+    }
+
+    pub fn input(&mut self, data: &[u8]) -> Result<(), String> {
+        let text = take_utf8(&mut self.pending_input, data);
+        self.event("i", text)
+    }
+
+    pub fn resize(&mut self, cols: u16, rows: u16) -> Result<(), String> {
+        self.event("r", format!("{cols}x{rows}"))
+    }
+
+    fn event(&mut self, code: &str, data: String) -> Result<(), String> {
+        if data.is_empty() {
+            return Ok(());
+        }
+        // Microsecond resolution keeps the file stable across formatters
+        let elapsed = (self.start.elapsed().as_micros() as f64) / 1_000_000.0;
+        let line = serde_json::to_string(&json!([elapsed, code, data]))
+            .map_err(|err| err.to_string())?;
+        self.write_line(&line)?;
+        self.chunk_events += 1;
+        Ok(())
+    }
+
+    fn write_line(&mut self, line: &str) -> Result<(), String> {
+        let mut bytes = line.as_bytes().to_vec();
+        bytes.push(b'\n');
+        self.file
+            .write_all(&bytes)
+            .map_err(|err| format!("write {}: {err}", self.path.display()))?;
+        self.file_hasher.update(&bytes);
+        self.chunk_hasher.update(&bytes);
+        self.written += bytes.len() as u64;
+        Ok(())
+    }
+
+    /// Closes the current chunk, or returns `None` if nothing was written
+    /// since the last one. The file is synced first so the hashed bytes
+    /// are on disk before the event that vouches for them.
+    pub fn take_chunk(&mut self) -> Result<Option<Chunk>, String> {
+        let length = self.written - self.chunk_offset;
+        if length == 0 {
+            return Ok(None);
+        }
+        self.file
+            .sync_data()
+            .map_err(|err| format!("sync {}: {err}", self.path.display()))?;
+        let hasher = std::mem::replace(&mut self.chunk_hasher, Sha256::new());
+        let chunk = Chunk {
+            index: self.chunk_index,
+            offset: self.chunk_offset,
+            length,
+            sha256: to_hex(&hasher.finalize()),
+            events: std::mem::take(&mut self.chunk_events),
+        };
+        self.chunk_index += 1;
+        self.chunk_offset = self.written;
+        Ok(Some(chunk))
+    }
+
+    /// Flushes what is left of incomplete UTF-8 sequences, lossily.
+    pub fn finish(&mut self) -> Result<(), String> {
+        let output = std::mem::take(&mut self.pending_output);
+        self.event("o", String::from_utf8_lossy(&output).to_string())?;
+        let input = std::mem::take(&mut self.pending_input);
+        self.event("i", String::from_utf8_lossy(&input).to_string())
+    }
+
+    pub fn chunk_count(&self) -> u64 {
+        self.chunk_index
+    }
+
+    pub fn bytes(&self) -> u64 {
+        self.written
+    }
+
+    pub fn file_sha256(&self) -> String {
+        to_hex(&self.file_hasher.clone().finalize())
+    }
+}
+
+/// Decodes as much of `pending + data` as forms complete UTF-8, keeping an
+/// incomplete trailing sequence for the next read. Invalid bytes become
+/// U+FFFD, as asciicast players do.
+fn take_utf8(pending: &mut Vec<u8>, data: &[u8]) -> String {
+    pending.extend_from_slice(data);
+    let keep = incomplete_suffix_len(pending);
+    let complete: Vec<u8> = pending.drain(..pending.len() - keep).collect();
+    String::from_utf8_lossy(&complete).to_string()
+}
+
+fn incomplete_suffix_len(bytes: &[u8]) -> usize {
+    for back in 1..=3.min(bytes.len()) {
+        let byte = bytes[bytes.len() - back];
+        if byte & 0xc0 == 0x80 {
+            continue;
+        }
+        let needed = match byte {
+            b if b & 0xe0 == 0xc0 => 2,
+            b if b & 0xf0 == 0xe0 => 3,
+            b if b & 0xf8 == 0xf0 => 4,
+            _ => return 0,
+        };
+        return if needed > back { back } else { 0 };
+    }
+    0
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
@@ -0,0 +1,293 @@
+mod cast;
+mod pty;
+
+use aegis_client::{Client, FileUpload, Message};
+use aegis_config::ConfigLoader;
+use cast::{CastWriter, Chunk};
+use pty::{Pty, RawMode, WindowSize};
+use serde_json::json;
+use std::env;
+use std::fs;
+use std::io;
+use std::os::unix::process::ExitStatusExt;
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+const USAGE: &str = "usage: aegis-term [--config <file>] [--set <key=value>]... [--chunk-secs N] [--record-input] [--] [command [args]...]";
+
+static RESIZED: AtomicBool = AtomicBool::new(false);
+static TERMINATED: AtomicBool = AtomicBool::new(false);
+
+fn main() {
+    match run() {
+        Ok(code) => std::process::exit(code),
+        Err(err) => {
+            eprintln!("FAIL: {err}");
+            std::process::exit(1);
+        }
+    }
+}
+
+fn run() -> Result<i32, String> {
+    let (loader, args) = ConfigLoader::from_args(env::args().skip(1))?;
+    let config = loader.load()?.config;
+
+    let mut chunk_interval = Duration::from_secs(10);
+    let mut record_input = false;
+    let mut command_args = Vec::new();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--chunk-secs" => {
+                let value = args.next().ok_or(USAGE)?;
+                let secs = value
+                    .parse::<u64>()
+                    .ok()
+                    .filter(|secs| *secs > 0)
+                    .ok_or_else(|| format!("invalid chunk interval: {value}"))?;
+                chunk_interval = Duration::from_secs(secs);
+            }
+            "--record-input" => record_input = true,
+            "--" => {
+                command_args.extend(args.by_ref());
+            }
+            flag if flag.starts_with("--") => return Err(USAGE.to_string()),
+            _ => {
+                command_args.push(arg);
+                command_args.extend(args.by_ref());
+            }
+        }
+    }
+    if command_args.is_empty() {
+        command_args.push(env::var("SHELL").unwrap_or_else(|_| "/bin/sh".to_string()));
+    }
+
+    let started_at = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default();
+    let name = format!("term_{started_at}_{}.cast", std::process::id());
+    let rel_path = format!("files/term/{name}");
+    let temp_dir = config.temp_dir();
+    fs::create_dir_all(&temp_dir).map_err(|err| format!("create {}: {err}", temp_dir.display()))?;
+    let cast_path = temp_dir.join(&name);
+
+    let stdin_is_tty = unsafe { libc::isatty(0) } == 1;
+    let size = pty::window_size(0).unwrap_or(WindowSize { cols: 80, rows: 24 });
+    let mut header = json!({
+        "version": 2,
+        "width": size.cols,
+        "height": size.rows,
+        "timestamp": started_at,
+        "command": command_args.join(" "),
+    });
+    let term = env::var("TERM").unwrap_or_else(|_| "xterm-256color".to_string());
+    header["env"] = json!({ "TERM": term });
+    if let Ok(shell) = env::var("SHELL") {
+        header["env"]["SHELL"] = json!(shell);
+    }
+    // Refuse to start an unrecorded shell: the user asked for a
+    // recorded one, so this first event is never spooled
+    let client = Client::from_config(&config, "term");
+    client.send_now(&Message::new(
+        "terminal_session_started",
+        json!({
+            "rel_path": rel_path,
+            "command": command_args,
+            "cols": size.cols,
+            "rows": size.rows,
+            "input_recorded": record_input,
+        }),
+    ))?;
+
+    let mut cast = CastWriter::create(&cast_path, header)?;
+
+    let pty = Pty::open()?;
+    pty.set_size(size);
+    let mut command = Command::new(&command_args[0]);
+    command
+        .args(&command_args[1..])
+        .env("TERM", &term)
+        .env("AEGIS_TERM_SESSION", &rel_path);
+    let mut child = pty.spawn(&mut command)?;
+    // The command still holds the slave descriptors; without dropping it
+    // the master never sees EOF when the shell exits.
+    drop(command);
+    install_signal_handlers();
+
+    let session_start = Instant::now();
+    let mut send_errors = 0u64;
+    let mut last_error = String::new();
+    {
+        let _raw = if stdin_is_tty { RawMode::enable(0) } else { None };
+        let mut stdin_open = true;
+        let mut last_size = size;
+        let mut next_chunk = Instant::now() + chunk_interval;
+        let mut buf = [0u8; 8192];
+
+        loop {
+            if TERMINATED.load(Ordering::SeqCst) {
+                unsafe { libc::kill(child.id() as i32, libc::SIGHUP) };
+                TERMINATED.store(false, Ordering::SeqCst);
+            }
+            if RESIZED.swap(false, Ordering::SeqCst) {
+                if let Some(size) = pty::window_size(0) {
+                    if size != last_size {
+                        pty.set_size(size);
+                        cast.resize(size.cols, size.rows)?;
+                        last_size = size;
+                    }
+                }
+            }
+            if Instant::now() >= next_chunk {
+                if let Some(chunk) = cast.take_chunk()? {
+                    if let Err(err) = send_chunk(&client, &rel_path, &chunk) {
+                        send_errors += 1;
+                        last_error = err;
+                    }
+                }
+                next_chunk = Instant::now() + chunk_interval;
+            }
+
+            let mut fds = [
+                libc::pollfd {
+                    fd: pty.master,
+                    events: libc::POLLIN,
+                    revents: 0,
+                },
+                libc::pollfd {
+                    fd: if stdin_open { 0 } else { -1 },
+                    events: libc::POLLIN,
+                    revents: 0,
+                },
+            ];
+            let timeout = next_chunk
+                .saturating_duration_since(Instant::now())
+                .min(Duration::from_millis(500));
+            let ready = unsafe { libc::poll(fds.as_mut_ptr(), 2, timeout.as_millis() as i32) };
+            if ready < 0 {
+                let err = io::Error::last_os_error();
+                if err.kind() == io::ErrorKind::Interrupted {
+                    continue;
+                }
+                return Err(format!("poll: {err}"));
+            }
+            // Background jobs may keep the slave open after the shell exits
+            if ready == 0 && child.try_wait().ok().flatten().is_some() {
+                break;
+            }
+
+            if fds[0].revents & (libc::POLLIN | libc::POLLHUP | libc::POLLERR) != 0 {
+                let len = unsafe { libc::read(pty.master, buf.as_mut_ptr().cast(), buf.len()) };
+                // EIO once the last slave descriptor is closed
+                if len <= 0 {
+                    break;
+                }
+                let data = &buf[..len as usize];
+                let _ = pty::write_all(1, data);
+                cast.output(data)?;
+            }
+            if fds[1].revents & (libc::POLLIN | libc::POLLHUP | libc::POLLERR) != 0 {
+                let len = unsafe { libc::read(0, buf.as_mut_ptr().cast(), buf.len()) };
+                if len <= 0 {
+                    // Piped input ended: send EOF to the shell like Ctrl+D
+                    stdin_open = false;
+                    if !stdin_is_tty {
+                        let _ = pty::write_all(pty.master, &[4]);
+                    }
+                    continue;
+                }
+                let data = &buf[..len as usize];
+                pty::write_all(pty.master, data).map_err(|err| format!("write pty: {err}"))?;
+                if record_input {
+                    cast.input(data)?;
+                }
+            }
+        }
+    }
+
+    let status = child.wait().map_err(|err| format!("wait child: {err}"))?;
+    cast.finish()?;
+    if let Some(chunk) = cast.take_chunk()? {
+        if let Err(err) = send_chunk(&client, &rel_path, &chunk) {
+            send_errors += 1;
+            last_error = err;
+        }
+    }
+
+    let mut ended = json!({
+        "rel_path": rel_path,
+        "duration_ms": session_start.elapsed().as_millis() as u64,
+        "chunks": cast.chunk_count(),
+        "bytes": cast.bytes(),
+        "sha256": cast.file_sha256(),
+    });
+    match (status.code(), status.signal()) {
+        (Some(code), _) => ended["exit_code"] = json!(code),
+        (None, Some(signal)) => ended["signal"] = json!(signal),
+        _ => {}
+    }
+    let cast_file = cast.path().to_path_buf();
+    let finished = client
+        .upload_file(&FileUpload {
+            source_path: cast_file.clone(),
+            rel_path,
+            kind: "terminal_cast".to_string(),
+        })
+        .and_then(|_| client.send_event("terminal_session_ended", ended));
+
+    if send_errors > 0 {
+        eprintln!("aegis-term: {send_errors} chunk event(s) not delivered: {last_error}");
+    }
+    match finished {
+        Ok(_) => {
+            let _ = fs::remove_file(&cast_file);
+        }
+        Err(err) => {
+            eprintln!(
+                "aegis-term: session not finalized ({err}); cast kept at {}",
+                cast_file.display()
+            );
+        }
+    }
+
+    Ok(status
+        .code()
+        .unwrap_or_else(|| 128 + status.signal().unwrap_or_default()))
+}
+
+fn send_chunk(client: &Client, rel_path: &str, chunk: &Chunk) -> Result<(), String> {
+    client.send_event(
+        "terminal_chunk",
+        json!({
+            "rel_path": rel_path,
+            "index": chunk.index,
+            "offset": chunk.offset,
+            "length": chunk.length,
+            "sha256": chunk.sha256,
+            "events": chunk.events,
+        }),
+    )
+    .map(|_| ())
+}
+
+extern "C" fn handle_resize(_signal: libc::c_int) {
+    RESIZED.store(true, Ordering::SeqCst);
+}
+
+extern "C" fn handle_terminate(_signal: libc::c_int) {
+    TERMINATED.store(true, Ordering::SeqCst);
+}
+
+/// SIGTERM/SIGHUP are passed on to the shell as a hangup so the session
+/// still ends cleanly and the cast is finalized.
+fn install_signal_handlers() {
+    let resize = handle_resize as extern "C" fn(libc::c_int) as libc::sighandler_t;
+    let terminate = handle_terminate as extern "C" fn(libc::c_int) as libc::sighandler_t;
+    unsafe {
+        libc::signal(libc::SIGWINCH, resize);
+        libc::signal(libc::SIGTERM, terminate);
+        libc::signal(libc::SIGHUP, terminate);
+    }
+}
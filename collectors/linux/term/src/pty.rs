@@ -0,0 +1,139 @@
+use std::ffi::CStr;
+use std::fs::OpenOptions;
+use std::io;
+use std::os::unix::process::CommandExt;
+use std::process::{Child, Command, Stdio};
+
+/// Master side of a pseudo-terminal.
+pub struct Pty {
+    pub master: i32,
+    slave_path: String,
+}
+
+impl Pty {
+    pub fn open() -> Result<Self, String> {
+        let master = unsafe { libc::posix_openpt(libc::O_RDWR | libc::O_NOCTTY | libc::O_CLOEXEC) };
+        if master < 0 {
+            return Err(format!("posix_openpt: {}", io::Error::last_os_error()));
+        }
+        let mut pty = Self {
+            master,
+            slave_path: String::new(),
+        };
+        if unsafe { libc::grantpt(master) } != 0 || unsafe { libc::unlockpt(master) } != 0 {
+            return Err(format!("unlock pty: {}", io::Error::last_os_error()));
+        }
+        let mut name = [0 as libc::c_char; 128];
+        if unsafe { libc::ptsname_r(master, name.as_mut_ptr(), name.len()) } != 0 {
+            return Err(format!("ptsname: {}", io::Error::last_os_error()));
+        }
+        pty.slave_path = unsafe { CStr::from_ptr(name.as_ptr()) }
+            .to_string_lossy()
+            .to_string();
+        Ok(pty)
+    }
+
+    /// Runs `command` as the session leader with the slave as its
+    /// controlling terminal and stdio.
+    pub fn spawn(&self, command: &mut Command) -> Result<Child, String> {
+        let slave = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(&self.slave_path)
+            .map_err(|err| format!("open {}: {err}", self.slave_path))?;
+        let stdout = slave.try_clone().map_err(|err| err.to_string())?;
+        let stderr = slave.try_clone().map_err(|err| err.to_string())?;
+        command
+            .stdin(Stdio::from(slave))
+            .stdout(Stdio::from(stdout))
+            .stderr(Stdio::from(stderr));
+        unsafe {
+            command.pre_exec(|| {
+                if libc::setsid() < 0 || libc::ioctl(0, libc::TIOCSCTTY, 0) < 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                Ok(())
+            });
+        }
+        command
+            .spawn()
+            .map_err(|err| format!("start {:?}: {err}", command.get_program()))
+    }
+
+    pub fn set_size(&self, size: WindowSize) {
+        let winsize = libc::winsize {
+            ws_row: size.rows,
+            ws_col: size.cols,
+            ws_xpixel: 0,
+            ws_ypixel: 0,
+        };
+        unsafe { libc::ioctl(self.master, libc::TIOCSWINSZ, &winsize) };
+    }
+}
+
+impl Drop for Pty {
+    fn drop(&mut self) {
+        unsafe { libc::close(self.master) };
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub struct WindowSize {
+    pub cols: u16,
+    pub rows: u16,
+}
+
+/// Size of the terminal on `fd`, or `None` when it is not a terminal.
+pub fn window_size(fd: i32) -> Option<WindowSize> {
+    let mut winsize: libc::winsize = unsafe { std::mem::zeroed() };
+    if unsafe { libc::ioctl(fd, libc::TIOCGWINSZ, &mut winsize) } < 0 || winsize.ws_col == 0 {
+        return None;
+    }
+    Some(WindowSize {
+        cols: winsize.ws_col,
+        rows: winsize.ws_row,
+    })
+}
+
+/// Puts a terminal into raw mode and restores it when dropped, so keys
+/// like Ctrl+C reach the wrapped shell instead of this process.
+pub struct RawMode {
+    fd: i32,
+    saved: libc::termios,
+}
+
+impl RawMode {
+    pub fn enable(fd: i32) -> Option<Self> {
+        let mut saved: libc::termios = unsafe { std::mem::zeroed() };
+        if unsafe { libc::tcgetattr(fd, &mut saved) } != 0 {
+            return None;
+        }
+        let mut raw = saved;
+        unsafe {
+            libc::cfmakeraw(&mut raw);
+            libc::tcsetattr(fd, libc::TCSANOW, &raw);
+        }
+        Some(Self { fd, saved })
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        unsafe { libc::tcsetattr(self.fd, libc::TCSANOW, &self.saved) };
+    }
+}
+
+pub fn write_all(fd: i32, mut data: &[u8]) -> io::Result<()> {
+    while !data.is_empty() {
+        let written = unsafe { libc::write(fd, data.as_ptr().cast(), data.len()) };
+        if written < 0 {
+            let err = io::Error::last_os_error();
+            if err.kind() == io::ErrorKind::Interrupted {
+                continue;
+            }
+            return Err(err);
+        }
+        data = &data[written as usize..];
+    }
+    Ok(())
+}
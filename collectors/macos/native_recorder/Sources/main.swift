@@ -1,13 +1,29 @@
 import AVFoundation
 import Foundation
 
+struct VideoSettings {
+    var codec = "hevc"
+    var width = 1280
+    var height = 720
+    var fps = 30
+    var bitrate = 2_000_000
+
+    var codecType: AVVideoCodecType? {
+        switch codec {
+        case "hevc": return .hevc
+        case "h264": return .h264
+        default: return nil
+        }
+    }
+}
+
 final class Recorder: NSObject, AVCaptureFileOutputRecordingDelegate {
     private let session = AVCaptureSession()
     private let output = AVCaptureMovieFileOutput()
     private let done = DispatchSemaphore(value: 0)
     private var signalSources: [DispatchSourceSignal] = []
 
-    func record(outputURL: URL, duration: TimeInterval) throws {
+    func record(outputURL: URL, duration: TimeInterval, settings: VideoSettings) throws {
         session.sessionPreset = .high
 
         guard let screenInput = AVCaptureScreenInput(displayID: CGMainDisplayID()) else {
@@ -16,12 +32,12 @@ final class Recorder: NSObject, AVCaptureFileOutputRecordingDelegate {
         screenInput.capturesMouseClicks = true
         screenInput.capturesCursor = true
         let displayBounds = CGDisplayBounds(CGMainDisplayID())
-        let targetSize = CGSize(width: 1280, height: 720)
+        let targetSize = CGSize(width: settings.width, height: settings.height)
         let scale = min(targetSize.width / displayBounds.width, targetSize.height / displayBounds.height)
         if scale < 1 {
             screenInput.scaleFactor = scale
         }
-        let fps = 30
+        let fps = settings.fps
         screenInput.minFrameDuration = CMTimeMake(value: 1, timescale: Int32(fps))
 
         if session.canAddInput(screenInput) {
@@ -35,9 +51,9 @@ final class Recorder: NSObject, AVCaptureFileOutputRecordingDelegate {
             if let connection = output.connection(with: .video) {
                 output.setOutputSettings(
                     [
-                        AVVideoCodecKey: AVVideoCodecType.hevc,
+                        AVVideoCodecKey: settings.codecType ?? AVVideoCodecType.hevc,
                         AVVideoCompressionPropertiesKey: [
-                            AVVideoAverageBitRateKey: 2_000_000,
+                            AVVideoAverageBitRateKey: settings.bitrate,
                             AVVideoMaxKeyFrameIntervalKey: fps * 2
                         ]
                     ],
@@ -95,7 +111,25 @@ func ensureParentDir(for url: URL) throws {
 }
 
 func usage() -> String {
-    return "usage: aegis-native-recorder <output_path> <seconds>"
+    return "usage: aegis-native-recorder <output_path> <seconds> [--codec hevc|h264] [--width N] [--height N] [--fps N] [--bitrate N]"
+}
+
+func parseSettings(_ options: ArraySlice<String>) -> VideoSettings? {
+    var settings = VideoSettings()
+    var iterator = options.makeIterator()
+    while let flag = iterator.next() {
+        guard let value = iterator.next() else { return nil }
+        switch flag {
+        case "--codec": settings.codec = value
+        case "--width": guard let v = Int(value), v > 0 else { return nil }; settings.width = v
+        case "--height": guard let v = Int(value), v > 0 else { return nil }; settings.height = v
+        case "--fps": guard let v = Int(value), v > 0 else { return nil }; settings.fps = v
+        case "--bitrate": guard let v = Int(value), v > 0 else { return nil }; settings.bitrate = v
+        default: return nil
+        }
+    }
+    guard settings.codecType != nil else { return nil }
+    return settings
 }
 
 let args = CommandLine.arguments.dropFirst()
@@ -112,12 +146,17 @@ guard let duration = TimeInterval(secondsString), duration > 0 else {
     exit(1)
 }
 
+guard let settings = parseSettings(args.dropFirst(2)) else {
+    fputs("invalid video settings\n\(usage())\n", stderr)
+    exit(1)
+}
+
 let outputURL = URL(fileURLWithPath: outputPath)
 
 do {
     try ensureParentDir(for: outputURL)
     let recorder = Recorder()
-    try recorder.record(outputURL: outputURL, duration: duration)
+    try recorder.record(outputURL: outputURL, duration: duration, settings: settings)
 } catch {
     fputs("FAIL: \(error)\n", stderr)
     exit(1)
@@ -0,0 +1,260 @@
+mod supervisor;
+
+use aegis_client::{Client, Message};
+use aegis_config::{Config, ConfigLoader};
+use serde_json::{json, Value};
+use std::env;
+use std::net::TcpStream;
+use std::os::unix::process::{CommandExt, ExitStatusExt};
+use std::path::PathBuf;
+use std::process::{Child, Command, ExitStatus};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+use supervisor::Supervised;
+
+const USAGE: &str = "usage: aegis-agent [--config <file>] [--set <key=value>]... [save_dir]";
+
+/// How long the core server gets to bind its port, and to finalize the
+/// bundle after `stop`.
+const SERVER_TIMEOUT: Duration = Duration::from_secs(10);
+
+static SHUTDOWN: AtomicBool = AtomicBool::new(false);
+
+fn main() {
+    if let Err(err) = run() {
+        eprintln!("FAIL: {err}");
+        std::process::exit(1);
+    }
+}
+
+fn run() -> Result<(), String> {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let (loader, rest) = ConfigLoader::from_args(args.clone())?;
+    let config = loader.load()?.config;
+    // Children load the same layers, plus whatever was given on our command line
+    let shared_args = config_args(&args);
+    let mut rest = rest.into_iter();
+    let save_dir = rest.next();
+    if rest.next().is_some() || save_dir.as_deref().is_some_and(|dir| dir.starts_with("--")) {
+        return Err(USAGE.to_string());
+    }
+
+    install_signal_handlers();
+    let addr = config.server.default_addr.clone();
+    let mut server = Command::new(resolve_command("aegis-core-server"))
+        .args(&shared_args)
+        .arg(&config.app.platform)
+        .arg(&config.app.version)
+        .args(save_dir)
+        .process_group(0)
+        .spawn()
+        .map_err(|err| format!("start aegis-core-server: {err}"))?;
+    if let Err(err) = wait_for_server(&addr, &mut server) {
+        let _ = server.kill();
+        let _ = server.wait();
+        return Err(err);
+    }
+
+    // Agent events describe this session only; they are never spooled
+    let client = Client::new(addr);
+    let mut collectors: Vec<Supervised> = config
+        .agent
+        .collectors
+        .iter()
+        .filter(|(_, collector)| collector.enabled)
+        .map(|(name, collector)| {
+            let mut args = shared_args.clone();
+            args.extend(collector.args.iter().cloned());
+            let program = resolve_command(&collector.command_for(name));
+            Supervised::new(name.clone(), program, args)
+        })
+        .collect();
+    if collectors.is_empty() {
+        eprintln!("No collectors enabled (agent.collectors); running the core server only");
+    }
+    for collector in &mut collectors {
+        start(collector, &client, &config);
+    }
+
+    let server_status = loop {
+        if SHUTDOWN.load(Ordering::SeqCst) {
+            break None;
+        }
+        if let Ok(Some(status)) = server.try_wait() {
+            break Some(status);
+        }
+        for collector in &mut collectors {
+            if let Some(status) = collector.poll() {
+                crashed(collector, Some(status), None, &client, &config);
+            }
+            if collector.restart_due() {
+                start(collector, &client, &config);
+            }
+        }
+        thread::sleep(Duration::from_millis(200));
+    };
+
+    // Collectors first (last started, first stopped), then the server so
+    // their final events still land in the bundle
+    let timeout = Duration::from_millis(config.agent.shutdown_timeout_ms);
+    let record_stops = server_status.is_none();
+    for collector in collectors.iter_mut().rev() {
+        let pid = collector.pid();
+        let Some(status) = collector.stop(timeout) else {
+            continue;
+        };
+        if record_stops {
+            let mut payload = json!({ "name": collector.name, "pid": pid });
+            exit_fields(&mut payload, status);
+            send(&client, "collector_stopped", payload);
+        }
+    }
+
+    match server_status {
+        Some(status) if status.success() => {
+            eprintln!("Session stopped; collectors shut down");
+            Ok(())
+        }
+        Some(status) => Err(format!("core server exited: {status}")),
+        None => {
+            if let Err(err) = client.stop("agent") {
+                eprintln!("send stop: {err}");
+            }
+            let deadline = Instant::now() + SERVER_TIMEOUT;
+            while Instant::now() < deadline {
+                if let Ok(Some(_)) = server.try_wait() {
+                    return Ok(());
+                }
+                thread::sleep(Duration::from_millis(50));
+            }
+            let _ = server.kill();
+            let _ = server.wait();
+            Err("core server did not stop; killed".to_string())
+        }
+    }
+}
+
+/// The `--config`/`--set` pairs from our own arguments, in order.
+fn config_args(args: &[String]) -> Vec<String> {
+    let mut shared = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg == "--config" || arg == "--set" {
+            shared.push(arg.clone());
+            shared.extend(args.next().cloned());
+        }
+    }
+    shared
+}
+
+/// Bare names are looked up next to the agent binary first, then on PATH.
+fn resolve_command(command: &str) -> PathBuf {
+    if command.contains('/') {
+        return PathBuf::from(command);
+    }
+    if let Some(dir) = env::current_exe().ok().and_then(|exe| exe.parent().map(PathBuf::from)) {
+        let candidate = dir.join(command);
+        if candidate.exists() {
+            return candidate;
+        }
+    }
+    PathBuf::from(command)
+}
+
+fn wait_for_server(addr: &str, server: &mut Child) -> Result<(), String> {
+    let deadline = Instant::now() + SERVER_TIMEOUT;
+    loop {
+        if let Ok(Some(status)) = server.try_wait() {
+            return Err(format!("core server exited during startup: {status}"));
+        }
+        if TcpStream::connect(addr).is_ok() {
+            return Ok(());
+        }
+        if Instant::now() >= deadline {
+            return Err(format!("core server not listening on {addr}"));
+        }
+        thread::sleep(Duration::from_millis(100));
+    }
+}
+
+fn start(collector: &mut Supervised, client: &Client, config: &Config) {
+    let restarted = collector.starts > 0;
+    match collector.spawn() {
+        Ok(pid) if restarted => send(
+            client,
+            "collector_restarted",
+            json!({ "name": collector.name, "pid": pid, "restarts": collector.starts - 1 }),
+        ),
+        Ok(pid) => send(
+            client,
+            "collector_started",
+            json!({
+                "name": collector.name,
+                "pid": pid,
+                "command": collector.program.to_string_lossy(),
+            }),
+        ),
+        Err(err) => crashed(collector, None, Some(err), client, config),
+    }
+}
+
+/// Records an unexpected exit (or a failed start) and schedules a restart.
+fn crashed(
+    collector: &mut Supervised,
+    status: Option<ExitStatus>,
+    error: Option<String>,
+    client: &Client,
+    config: &Config,
+) {
+    let uptime = if status.is_some() { collector.uptime() } else { Duration::ZERO };
+    let delay = collector.schedule_restart(
+        uptime,
+        Duration::from_millis(config.agent.restart_backoff_ms),
+        Duration::from_millis(config.agent.restart_backoff_max_ms),
+    );
+    let mut payload = json!({
+        "name": collector.name,
+        "restart_in_ms": delay.as_millis() as u64,
+    });
+    if let Some(status) = status {
+        payload["uptime_ms"] = json!(uptime.as_millis() as u64);
+        exit_fields(&mut payload, status);
+    }
+    if let Some(error) = error.or_else(|| collector.last_error()) {
+        payload["last_error"] = json!(error);
+    }
+    eprintln!(
+        "collector {} crashed; restarting in {}ms",
+        collector.name,
+        delay.as_millis()
+    );
+    send(client, "collector_crashed", payload);
+}
+
+fn exit_fields(payload: &mut Value, status: ExitStatus) {
+    match (status.code(), status.signal()) {
+        (Some(code), _) => payload["exit_code"] = json!(code),
+        (None, Some(signal)) => payload["signal"] = json!(signal),
+        _ => {}
+    }
+}
+
+fn send(client: &Client, event_type: &str, payload: Value) {
+    if let Err(err) = client.send_now(&Message::new(event_type, payload)) {
+        eprintln!("send {event_type}: {err}");
+    }
+}
+
+extern "C" fn handle_shutdown(_signal: libc::c_int) {
+    SHUTDOWN.store(true, Ordering::SeqCst);
+}
+
+fn install_signal_handlers() {
+    let handler = handle_shutdown as extern "C" fn(libc::c_int) as libc::sighandler_t;
+    unsafe {
+        libc::signal(libc::SIGTERM, handler);
+        libc::signal(libc::SIGINT, handler);
+        libc::signal(libc::SIGHUP, handler);
+    }
+}
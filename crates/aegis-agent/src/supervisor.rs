@@ -0,0 +1,137 @@
+use std::io::{BufRead, BufReader};
+use std::os::unix::process::CommandExt;
+use std::path::PathBuf;
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// One collector process and its restart bookkeeping.
+pub struct Supervised {
+    pub name: String,
+    pub program: PathBuf,
+    args: Vec<String>,
+    child: Option<Child>,
+    started_at: Instant,
+    /// Last line the collector wrote to stderr, usually its `FAIL: ...`
+    last_error: Arc<Mutex<Option<String>>>,
+    /// Crashes since the collector last stayed up for a full backoff cap
+    failures: u32,
+    /// Successful starts so far, the first included
+    pub starts: u64,
+    restart_at: Option<Instant>,
+}
+
+impl Supervised {
+    pub fn new(name: String, program: PathBuf, args: Vec<String>) -> Self {
+        Self {
+            name,
+            program,
+            args,
+            child: None,
+            started_at: Instant::now(),
+            last_error: Arc::new(Mutex::new(None)),
+            failures: 0,
+            starts: 0,
+            restart_at: None,
+        }
+    }
+
+    /// Starts the collector in its own process group, so stopping it also
+    /// stops helpers it spawned (xprop, dbus-monitor, ffmpeg, ...) and a
+    /// terminal Ctrl+C reaches only the agent.
+    pub fn spawn(&mut self) -> Result<u32, String> {
+        self.restart_at = None;
+        let mut child = Command::new(&self.program)
+            .args(&self.args)
+            .stdin(Stdio::null())
+            .stderr(Stdio::piped())
+            .process_group(0)
+            .spawn()
+            .map_err(|err| format!("start {}: {err}", self.program.display()))?;
+
+        if let Some(stderr) = child.stderr.take() {
+            let name = self.name.clone();
+            let last_error = Arc::clone(&self.last_error);
+            thread::spawn(move || {
+                for line in BufReader::new(stderr).lines() {
+                    let Ok(line) = line else {
+                        break;
+                    };
+                    eprintln!("[{name}] {line}");
+                    if !line.trim().is_empty() {
+                        if let Ok(mut last) = last_error.lock() {
+                            *last = Some(line);
+                        }
+                    }
+                }
+            });
+        }
+
+        if let Ok(mut last) = self.last_error.lock() {
+            *last = None;
+        }
+        self.started_at = Instant::now();
+        self.starts += 1;
+        let pid = child.id();
+        self.child = Some(child);
+        Ok(pid)
+    }
+
+    pub fn pid(&self) -> Option<u32> {
+        self.child.as_ref().map(Child::id)
+    }
+
+    pub fn uptime(&self) -> Duration {
+        self.started_at.elapsed()
+    }
+
+    pub fn last_error(&self) -> Option<String> {
+        self.last_error.lock().ok().and_then(|last| last.clone())
+    }
+
+    /// Returns the exit status once the collector has exited.
+    pub fn poll(&mut self) -> Option<ExitStatus> {
+        let child = self.child.as_mut()?;
+        let status = child.try_wait().ok().flatten()?;
+        // Helpers the collector left behind would otherwise be orphaned
+        unsafe { libc::kill(-(child.id() as i32), libc::SIGTERM) };
+        self.child = None;
+        Some(status)
+    }
+
+    /// Schedules the next start after a crash (or a failed start) and
+    /// returns the delay. A collector that ran for at least `max` is
+    /// considered healthy again and restarts after `base`.
+    pub fn schedule_restart(&mut self, uptime: Duration, base: Duration, max: Duration) -> Duration {
+        if uptime >= max {
+            self.failures = 0;
+        }
+        self.failures = self.failures.saturating_add(1);
+        let factor = 1u32 << (self.failures - 1).min(16);
+        let delay = base.saturating_mul(factor).min(max);
+        self.restart_at = Some(Instant::now() + delay);
+        delay
+    }
+
+    pub fn restart_due(&self) -> bool {
+        self.child.is_none() && self.restart_at.is_some_and(|at| Instant::now() >= at)
+    }
+
+    /// SIGTERM to the process group, SIGKILL after `timeout`.
+    pub fn stop(&mut self, timeout: Duration) -> Option<ExitStatus> {
+        self.restart_at = None;
+        let mut child = self.child.take()?;
+        let group = child.id() as i32;
+        unsafe { libc::kill(-group, libc::SIGTERM) };
+        let deadline = Instant::now() + timeout;
+        while Instant::now() < deadline {
+            if let Ok(Some(status)) = child.try_wait() {
+                return Some(status);
+            }
+            thread::sleep(Duration::from_millis(50));
+        }
+        unsafe { libc::kill(-group, libc::SIGKILL) };
+        child.wait().ok()
+    }
+}
@@ -0,0 +1,78 @@
+use crate::{Client, Delivery, FileUpload, FocusChange, InputStats, Message, ShotUpload};
+use futures_channel::oneshot;
+use serde_json::Value;
+use std::sync::mpsc::{self, Sender};
+use std::thread;
+
+type Job = Box<dyn FnOnce(&Client) + Send>;
+
+/// [`Client`] for async code, independent of the runtime: calls are queued
+/// to a worker thread that owns the blocking client, and each returns a
+/// future for its result. Calls are delivered in the order they were made.
+#[derive(Clone)]
+pub struct AsyncClient {
+    jobs: Sender<Job>,
+}
+
+impl AsyncClient {
+    pub fn new(client: Client) -> Self {
+        let (jobs, queue) = mpsc::channel::<Job>();
+        thread::spawn(move || {
+            for job in queue {
+                job(&client);
+            }
+        });
+        Self { jobs }
+    }
+
+    pub async fn send(&self, message: Message) -> Result<Delivery, String> {
+        self.call(move |client| client.send(&message)).await
+    }
+
+    pub async fn send_now(&self, message: Message) -> Result<Option<u64>, String> {
+        self.call(move |client| client.send_now(&message)).await
+    }
+
+    pub async fn send_focus(&self, focus: FocusChange) -> Result<Delivery, String> {
+        self.call(move |client| client.send_focus(&focus)).await
+    }
+
+    pub async fn upload_file(&self, upload: FileUpload) -> Result<Delivery, String> {
+        self.call(move |client| client.upload_file(&upload)).await
+    }
+
+    pub async fn upload_shot(&self, shot: ShotUpload) -> Result<Delivery, String> {
+        self.call(move |client| client.upload_shot(&shot)).await
+    }
+
+    pub async fn send_input_stats(&self, stats: InputStats) -> Result<Delivery, String> {
+        self.call(move |client| client.send_input_stats(&stats)).await
+    }
+
+    pub async fn send_event(&self, message_type: String, payload: Value) -> Result<Delivery, String> {
+        self.call(move |client| client.send_event(&message_type, payload))
+            .await
+    }
+
+    pub async fn stop(&self, reason: String) -> Result<(), String> {
+        self.call(move |client| client.stop(&reason)).await
+    }
+
+    pub async fn flush(&self) -> Result<usize, String> {
+        self.call(Client::flush).await
+    }
+
+    async fn call<T, F>(&self, call: F) -> Result<T, String>
+    where
+        T: Send + 'static,
+        F: FnOnce(&Client) -> Result<T, String> + Send + 'static,
+    {
+        let (reply, result) = oneshot::channel();
+        self.jobs
+            .send(Box::new(move |client| {
+                let _ = reply.send(call(client));
+            }))
+            .map_err(|_| "client worker stopped")?;
+        result.await.map_err(|_| "client worker stopped")?
+    }
+}
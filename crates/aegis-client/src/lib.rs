@@ -0,0 +1,523 @@
+//! Delivery of collector messages to `aegis-core-server`: pooled
+//! long-lived connections, retries with backoff while the server is
+//! unreachable, and an on-disk spool for events that still could not be
+//! delivered. [`Client::pipeline`] sends many messages without waiting
+//! for each reply. [`AsyncClient`] offers the same calls as futures.
+
+mod async_client;
+mod messages;
+mod pipeline;
+mod spool;
+
+pub use async_client::AsyncClient;
+pub use messages::{FileUpload, FocusChange, InputStats, ShotUpload};
+pub use pipeline::{Pipeline, Replies};
+
+use aegis_config::Config;
+use chrono::{SecondsFormat, Utc};
+use serde::Serialize;
+use serde_json::{json, Value};
+use spool::{Spool, SpooledEvent};
+use std::io::{BufRead, BufReader, ErrorKind, Write};
+use std::net::TcpStream;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+#[derive(Serialize, Clone, Debug)]
+pub struct Message {
+    #[serde(rename = "type")]
+    pub message_type: String,
+    pub payload: Value,
+    /// When the event was observed (RFC 3339); the server's `ts` is when
+    /// it was written. [`Client`] fills it in when left empty.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub observed_at: Option<String>,
+    /// Identifies the message across resends; the server answers a
+    /// repeat of an id it has already applied with the original reply
+    /// instead of applying it again. [`Client`] fills it in when left
+    /// empty.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub msg_id: Option<String>,
+}
+
+impl Message {
+    pub fn new(message_type: impl Into<String>, payload: Value) -> Self {
+        Self {
+            message_type: message_type.into(),
+            payload,
+            observed_at: None,
+            msg_id: None,
+        }
+    }
+
+    /// Sets `observed_at`, for events observed before they are sent.
+    pub fn observed_at(mut self, observed_at: impl Into<String>) -> Self {
+        self.observed_at = Some(observed_at.into());
+        self
+    }
+
+    /// A copy with `observed_at` set to now and a fresh `msg_id`, unless
+    /// already set.
+    fn stamped(&self) -> Self {
+        let mut message = self.clone();
+        message
+            .observed_at
+            .get_or_insert_with(|| Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true));
+        message.msg_id.get_or_insert_with(next_msg_id);
+        message
+    }
+
+    /// A message whose payload is one of the typed payloads, e.g.
+    /// `Message::typed("app_focus_changed", &focus)`.
+    pub fn typed(message_type: &str, payload: &impl Serialize) -> Result<Self, String> {
+        let payload = serde_json::to_value(payload)
+            .map_err(|err| format!("encode {message_type}: {err}"))?;
+        Ok(Self::new(message_type, payload))
+    }
+}
+
+/// What happened to a message handed to [`Client::send`].
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Delivery {
+    /// Accepted by the server; `Some(seq)` when it became an event
+    Accepted(Option<u64>),
+    /// Server unreachable; kept in the spool for a later replay
+    Spooled,
+}
+
+enum SendError {
+    /// Could not connect, or the connection broke before a reply
+    Unavailable(String),
+    /// The server answered `ERR`; sending again will not help
+    Rejected(String),
+}
+
+impl SendError {
+    fn into_message(self) -> String {
+        match self {
+            SendError::Unavailable(err) => err,
+            SendError::Rejected(err) => format!("rejected: {err}"),
+        }
+    }
+}
+
+struct Connection {
+    stream: TcpStream,
+    reader: BufReader<TcpStream>,
+}
+
+impl Connection {
+    /// Whether an idle connection can still be used: the server has not
+    /// closed it and sent nothing unasked.
+    fn is_open(&self) -> bool {
+        if !self.reader.buffer().is_empty() || self.stream.set_nonblocking(true).is_err() {
+            return false;
+        }
+        let peeked = self.stream.peek(&mut [0u8; 1]);
+        let restored = self.stream.set_nonblocking(false).is_ok();
+        restored && matches!(peeked, Err(err) if err.kind() == ErrorKind::WouldBlock)
+    }
+}
+
+/// Builds a [`Client`]; the defaults try once, keep up to 4 idle
+/// connections and never spool.
+pub struct ClientBuilder {
+    addr: String,
+    attempts: u32,
+    backoff: Duration,
+    backoff_max: Duration,
+    pool_size: usize,
+    spool: Option<(PathBuf, u64)>,
+}
+
+impl ClientBuilder {
+    pub fn new(addr: impl Into<String>) -> Self {
+        Self {
+            addr: addr.into(),
+            attempts: 1,
+            backoff: Duration::ZERO,
+            backoff_max: Duration::ZERO,
+            pool_size: 4,
+            spool: None,
+        }
+    }
+
+    /// Connection attempts per message, with a delay that starts at
+    /// `backoff_ms` and doubles up to `backoff_max_ms`.
+    pub fn retry(mut self, attempts: u32, backoff_ms: u64, backoff_max_ms: u64) -> Self {
+        self.attempts = attempts.max(1);
+        self.backoff = Duration::from_millis(backoff_ms);
+        self.backoff_max = Duration::from_millis(backoff_max_ms.max(backoff_ms));
+        self
+    }
+
+    /// Idle connections kept open for reuse; threads sending at the same
+    /// time each get their own connection.
+    pub fn pool_size(mut self, pool_size: usize) -> Self {
+        self.pool_size = pool_size;
+        self
+    }
+
+    pub fn spool(mut self, dir: impl Into<PathBuf>, max_events: u64) -> Self {
+        self.spool = Some((dir.into(), max_events));
+        self
+    }
+
+    pub fn build(self) -> Client {
+        Client {
+            inner: Arc::new(Inner {
+                addr: self.addr,
+                attempts: self.attempts,
+                backoff: self.backoff,
+                backoff_max: self.backoff_max,
+                pool_size: self.pool_size,
+                idle: Mutex::new(Vec::new()),
+                spool: self
+                    .spool
+                    .map(|(dir, max_events)| Mutex::new(Spool::new(dir, max_events))),
+            }),
+        }
+    }
+}
+
+struct Inner {
+    addr: String,
+    attempts: u32,
+    backoff: Duration,
+    backoff_max: Duration,
+    pool_size: usize,
+    idle: Mutex<Vec<Connection>>,
+    /// Held while sending through the spool so replayed and new events
+    /// reach the server in capture order
+    spool: Option<Mutex<Spool>>,
+}
+
+/// A handle to `aegis-core-server`; clones share the connection pool and
+/// the spool, so one client can serve every thread of a collector.
+#[derive(Clone)]
+pub struct Client {
+    inner: Arc<Inner>,
+}
+
+impl Client {
+    /// A client that tries once and never spools.
+    pub fn new(addr: impl Into<String>) -> Self {
+        ClientBuilder::new(addr).build()
+    }
+
+    /// A client set up from the `server` and `client` config sections.
+    /// `name` picks the spool subdirectory, so each collector replays only
+    /// its own events. Collectors pass their `agent.collectors` key, and
+    /// use the same name in their heartbeats so coverage can be matched
+    /// to the collectors the agent runs.
+    pub fn from_config(config: &Config, name: &str) -> Self {
+        let client = &config.client;
+        let mut builder = ClientBuilder::new(config.server.default_addr.clone()).retry(
+            client.retry_attempts,
+            client.retry_backoff_ms,
+            client.retry_backoff_max_ms,
+        );
+        if client.spool_max_events > 0 {
+            builder = builder.spool(config.spool_dir().join(name), client.spool_max_events);
+        }
+        builder.build()
+    }
+
+    pub fn addr(&self) -> &str {
+        &self.inner.addr
+    }
+
+    pub fn send_focus(&self, focus: &FocusChange) -> Result<Delivery, String> {
+        self.send(&Message::typed("app_focus_changed", focus)?)
+    }
+
+    /// Has the server copy a file into the bundle. A spooled upload keeps
+    /// its own copy of the file, so the source may be removed right away.
+    pub fn upload_file(&self, upload: &FileUpload) -> Result<Delivery, String> {
+        self.send(&Message::typed("file_added", upload)?)
+    }
+
+    pub fn upload_shot(&self, shot: &ShotUpload) -> Result<Delivery, String> {
+        self.send(&Message::typed("shot_saved", shot)?)
+    }
+
+    pub fn send_input_stats(&self, stats: &InputStats) -> Result<Delivery, String> {
+        self.send(&Message::typed("input_stats", stats)?)
+    }
+
+    /// Any other event type.
+    ///
+    /// Delivery is at least once: a message whose reply is lost (the
+    /// connection broke after it was written) is sent again, and spooled
+    /// if the server stays unreachable. Every resend carries the message's
+    /// `msg_id`, so a server that already applied it only repeats its
+    /// reply. A server that crashed between writing the event and replying
+    /// has forgotten the id, so a session resumed after such a crash may
+    /// hold the event twice.
+    pub fn send_event(&self, message_type: &str, payload: Value) -> Result<Delivery, String> {
+        self.send(&Message::new(message_type, payload))
+    }
+
+    pub fn heartbeat(&self, collector: &str, interval_ms: u64) -> Result<(), String> {
+        let payload = json!({
+            "collector": collector,
+            "pid": std::process::id(),
+            "interval_ms": interval_ms,
+        });
+        self.send_now(&Message::new("heartbeat", payload)).map(|_| ())
+    }
+
+    /// Sends a heartbeat every `interval_ms` from a background thread; 0
+    /// disables heartbeats. Failures are reported on stderr and retried on
+    /// the next beat.
+    pub fn spawn_heartbeat(&self, collector: &'static str, interval_ms: u64) {
+        if interval_ms == 0 {
+            return;
+        }
+        let client = self.clone();
+        thread::spawn(move || loop {
+            if let Err(err) = client.heartbeat(collector, interval_ms) {
+                eprintln!("send heartbeat: {err}");
+            }
+            thread::sleep(Duration::from_millis(interval_ms));
+        });
+    }
+
+    /// Finalizes the session.
+    pub fn stop(&self, reason: &str) -> Result<(), String> {
+        self.send_now(&Message::new("stop", json!({ "reason": reason })))
+            .map(|_| ())
+    }
+
+    /// Delivers an event, spooling it if the server stays unreachable.
+    /// Older spooled events are replayed first so the server sees events
+    /// in capture order. Delivery is at least once, see
+    /// [`send_event`](Self::send_event).
+    pub fn send(&self, message: &Message) -> Result<Delivery, String> {
+        let message = &message.stamped();
+        let Some(ref spool) = self.inner.spool else {
+            return self
+                .deliver(message)
+                .map(Delivery::Accepted)
+                .map_err(SendError::into_message);
+        };
+        let spool = spool.lock().map_err(|_| "spool lock poisoned")?;
+        let result = if spool.entries()?.is_empty() {
+            self.deliver(message)
+        } else {
+            match self.replay(&spool) {
+                Ok(_) => self.deliver(message),
+                Err(err) => Err(SendError::Unavailable(err)),
+            }
+        };
+        match result {
+            Ok(seq) => Ok(Delivery::Accepted(seq)),
+            Err(SendError::Unavailable(err)) => {
+                spool
+                    .push(SpooledEvent {
+                        captured_at: message.observed_at.clone().unwrap_or_default(),
+                        message_type: message.message_type.clone(),
+                        payload: message.payload.clone(),
+                        msg_id: message.msg_id.clone(),
+                    })
+                    .map_err(|spool_err| format!("{err}; {spool_err}"))?;
+                Ok(Delivery::Spooled)
+            }
+            Err(err) => Err(err.into_message()),
+        }
+    }
+
+    /// Delivers a message that is only meaningful now (heartbeats, `stop`):
+    /// retried like any other, but never spooled.
+    pub fn send_now(&self, message: &Message) -> Result<Option<u64>, String> {
+        self.deliver(&message.stamped()).map_err(SendError::into_message)
+    }
+
+    /// Number of events waiting in the spool.
+    pub fn spooled(&self) -> Result<usize, String> {
+        match self.inner.spool {
+            Some(ref spool) => {
+                let spool = spool.lock().map_err(|_| "spool lock poisoned")?;
+                Ok(spool.entries()?.len())
+            }
+            None => Ok(0),
+        }
+    }
+
+    /// Replays spooled events in order and returns how many the server
+    /// accepted. Each replayed event keeps its capture time as
+    /// `observed_at`, and its payload carries `delayed: true`.
+    /// Events the server refuses are moved to `rejected/` in the spool.
+    pub fn flush(&self) -> Result<usize, String> {
+        match self.inner.spool {
+            Some(ref spool) => {
+                let spool = spool.lock().map_err(|_| "spool lock poisoned")?;
+                self.replay(&spool)
+            }
+            None => Ok(0),
+        }
+    }
+
+    fn replay(&self, spool: &Spool) -> Result<usize, String> {
+        let mut replayed = 0;
+        for path in spool.entries()? {
+            let event = Spool::read(&path)?;
+            let mut message = Message::new(event.message_type, delayed(event.payload))
+                .observed_at(event.captured_at);
+            message.msg_id = event.msg_id;
+            match self.deliver(&message) {
+                Ok(_) => {
+                    Spool::remove(&path)?;
+                    replayed += 1;
+                }
+                Err(SendError::Rejected(err)) => {
+                    eprintln!("spooled {} rejected: {err}", path.display());
+                    spool.reject(&path)?;
+                }
+                Err(SendError::Unavailable(err)) => {
+                    return Err(format!("replay spool {}: {err}", spool.dir().display()));
+                }
+            }
+        }
+        Ok(replayed)
+    }
+
+    fn deliver(&self, message: &Message) -> Result<Option<u64>, SendError> {
+        let mut line = serde_json::to_string(message)
+            .map_err(|err| SendError::Rejected(format!("encode message: {err}")))?;
+        line.push('\n');
+
+        let inner = &self.inner;
+        let mut backoff = inner.backoff;
+        let mut last_err = String::new();
+        for attempt in 0..inner.attempts {
+            if attempt > 0 {
+                thread::sleep(backoff);
+                backoff = (backoff * 2).min(inner.backoff_max);
+            }
+            match self.exchange(&line) {
+                Err(SendError::Unavailable(err)) => last_err = err,
+                result => return result,
+            }
+        }
+        Err(SendError::Unavailable(last_err))
+    }
+
+    /// Writes one line on a pooled connection and reads its reply. Idle
+    /// connections the server has closed are skipped; one that still fails
+    /// the write gets an immediate retry on a fresh connection, since the
+    /// server cannot have seen the line. A failure after the line was
+    /// written is left to [`deliver`](Self::deliver), whose resend the
+    /// server recognizes by `msg_id`.
+    fn exchange(&self, line: &str) -> Result<Option<u64>, SendError> {
+        let pooled = loop {
+            let Some(connection) = self.inner.idle.lock().ok().and_then(|mut idle| idle.pop())
+            else {
+                break None;
+            };
+            if connection.is_open() {
+                break Some(connection);
+            }
+        };
+        let (mut connection, reused) = match pooled {
+            Some(connection) => (connection, true),
+            None => (self.connect()?, false),
+        };
+        let result = match Self::write_line(&mut connection, line) {
+            Err(_) if reused => {
+                connection = self.connect()?;
+                Self::try_exchange(&mut connection, line)
+            }
+            Err(err) => Err(err),
+            Ok(()) => Self::read_reply(&mut connection.reader),
+        };
+        // `ERR` leaves the connection usable; a broken one is dropped
+        if !matches!(result, Err(SendError::Unavailable(_))) {
+            if let Ok(mut idle) = self.inner.idle.lock() {
+                if idle.len() < self.inner.pool_size {
+                    idle.push(connection);
+                }
+            }
+        }
+        result
+    }
+
+    fn connect(&self) -> Result<Connection, SendError> {
+        let addr = &self.inner.addr;
+        let stream = TcpStream::connect(addr)
+            .map_err(|err| SendError::Unavailable(format!("connect {addr}: {err}")))?;
+        let reader = BufReader::new(
+            stream
+                .try_clone()
+                .map_err(|err| SendError::Unavailable(format!("clone stream: {err}")))?,
+        );
+        Ok(Connection { stream, reader })
+    }
+
+    fn try_exchange(connection: &mut Connection, line: &str) -> Result<Option<u64>, SendError> {
+        Self::write_line(connection, line)?;
+        Self::read_reply(&mut connection.reader)
+    }
+
+    fn write_line(connection: &mut Connection, line: &str) -> Result<(), SendError> {
+        connection
+            .stream
+            .write_all(line.as_bytes())
+            .map_err(|err| SendError::Unavailable(format!("write message: {err}")))
+    }
+
+    fn read_reply(reader: &mut BufReader<TcpStream>) -> Result<Option<u64>, SendError> {
+        let mut response = String::new();
+        let read = reader
+            .read_line(&mut response)
+            .map_err(|err| SendError::Unavailable(format!("read response: {err}")))?;
+        if read == 0 {
+            return Err(SendError::Unavailable("connection closed".to_string()));
+        }
+        parse_reply(response.trim_end())
+    }
+}
+
+/// `OK <seq>`, `OK` or `ERR <reason>`.
+fn parse_reply(reply: &str) -> Result<Option<u64>, SendError> {
+    if let Some(reason) = reply.strip_prefix("ERR") {
+        return Err(SendError::Rejected(reason.trim_start().to_string()));
+    }
+    match reply.strip_prefix("OK").map(str::trim) {
+        Some("") => Ok(None),
+        Some(seq) => seq
+            .parse()
+            .map(Some)
+            .map_err(|_| SendError::Rejected(format!("unexpected response: {reply}"))),
+        None => Err(SendError::Rejected(format!("unexpected response: {reply}"))),
+    }
+}
+
+/// `<pid>-<start time>-<counter>`: unique per host without coordination,
+/// and stable across resends since it is assigned once per message.
+fn next_msg_id() -> String {
+    static PREFIX: OnceLock<String> = OnceLock::new();
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let prefix = PREFIX.get_or_init(|| {
+        let micros = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_micros())
+            .unwrap_or_default();
+        format!("{:x}-{micros:x}", std::process::id())
+    });
+    format!("{prefix}-{}", COUNTER.fetch_add(1, Ordering::SeqCst))
+}
+
+/// Marks a replayed payload; non-object payloads are wrapped as `value`.
+fn delayed(payload: Value) -> Value {
+    let mut payload = match payload {
+        Value::Object(map) => Value::Object(map),
+        Value::Null => json!({}),
+        other => json!({ "value": other }),
+    };
+    payload["delayed"] = json!(true);
+    payload
+}
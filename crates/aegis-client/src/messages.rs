@@ -0,0 +1,53 @@
+use serde::Serialize;
+use std::path::PathBuf;
+
+/// `app_focus_changed`
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct FocusChange {
+    pub app_id: String,
+    pub app_name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub window_title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pid: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exe: Option<String>,
+}
+
+/// `file_added`: the server copies `source_path` to `rel_path` in the
+/// bundle and records the event without `source_path`.
+#[derive(Serialize, Clone, Debug)]
+pub struct FileUpload {
+    pub source_path: PathBuf,
+    pub rel_path: String,
+    pub kind: String,
+}
+
+/// `shot_saved`, copied into the bundle like [`FileUpload`]. Without a
+/// `rel_path` the server numbers it as the next `files/shots/NNNNNN.<ext>`.
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct ShotUpload {
+    pub source_path: PathBuf,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rel_path: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trigger: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub format: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quality: Option<u32>,
+}
+
+/// `input_stats`: counts only, never key values.
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct InputStats {
+    pub interval_ms: u64,
+    pub key_count: u64,
+    pub backspace_count: u64,
+    pub paste_count: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub idle_bin_ms: Option<u64>,
+    /// One entry per `idle_bin_ms` slice: 1 if it had no input at all
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub idle_bins: Option<Vec<u8>>,
+}
@@ -0,0 +1,131 @@
+use crate::{Client, Delivery, Message, SendError};
+use std::io::{BufReader, Write};
+use std::net::{Shutdown, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+
+/// A message handed to [`Pipeline`], waiting for its reply.
+struct Queued {
+    message: Message,
+    /// Written to the connection; its reply is next on it
+    written: bool,
+    /// Spooled like [`Client::send`] if it cannot be delivered
+    spool: bool,
+}
+
+/// Sending half of [`Client::pipeline`]. Dropping it ends [`Replies`]
+/// once every queued message has its result.
+pub struct Pipeline {
+    stream: Option<TcpStream>,
+    broken: Arc<AtomicBool>,
+    queue: Sender<Queued>,
+}
+
+/// Results of the messages handed to [`Pipeline`], in the same order.
+pub struct Replies {
+    client: Client,
+    reader: Option<BufReader<TcpStream>>,
+    broken: Arc<AtomicBool>,
+    queue: Receiver<Queued>,
+}
+
+impl Client {
+    /// Writes messages on one connection without waiting for each reply.
+    /// Messages the connection did not answer, because it could not be
+    /// opened or broke on the way, are delivered again one by one with
+    /// retries and the spool, so each gets the same result
+    /// [`send`](Self::send) would give. Spooled events are replayed first.
+    pub fn pipeline(&self) -> (Pipeline, Replies) {
+        let connection = match self.flush() {
+            Ok(_) => self.connect().ok(),
+            Err(_) => None,
+        };
+        let (stream, reader) = match connection {
+            Some(connection) => (Some(connection.stream), Some(connection.reader)),
+            None => (None, None),
+        };
+        let broken = Arc::new(AtomicBool::new(false));
+        let (queue, queued) = mpsc::channel();
+        let pipeline = Pipeline {
+            stream,
+            broken: Arc::clone(&broken),
+            queue,
+        };
+        let replies = Replies {
+            client: self.clone(),
+            reader,
+            broken,
+            queue: queued,
+        };
+        (pipeline, replies)
+    }
+}
+
+impl Pipeline {
+    /// Queues an event; see [`Client::send`].
+    pub fn send(&mut self, message: &Message) {
+        self.push(message, true);
+    }
+
+    /// Queues a message that is only meaningful now; see
+    /// [`Client::send_now`].
+    pub fn send_now(&mut self, message: &Message) {
+        self.push(message, false);
+    }
+
+    fn push(&mut self, message: &Message, spool: bool) {
+        let message = message.stamped();
+        if self.broken.load(Ordering::SeqCst) {
+            self.stream = None;
+        }
+        let mut written = false;
+        let line = serde_json::to_string(&message).map(|line| line + "\n");
+        if let (Some(stream), Ok(line)) = (self.stream.as_mut(), line) {
+            written = stream.write_all(line.as_bytes()).is_ok();
+            if !written {
+                self.stream = None;
+            }
+        }
+        let _ = self.queue.send(Queued {
+            message,
+            written,
+            spool,
+        });
+    }
+}
+
+impl Drop for Pipeline {
+    fn drop(&mut self) {
+        // Lets the server finish the connection once every reply is written
+        if let Some(ref stream) = self.stream {
+            let _ = stream.shutdown(Shutdown::Write);
+        }
+    }
+}
+
+impl Iterator for Replies {
+    type Item = Result<Delivery, String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let queued = self.queue.recv().ok()?;
+        if queued.written {
+            if let Some(ref mut reader) = self.reader {
+                match Client::read_reply(reader) {
+                    Ok(seq) => return Some(Ok(Delivery::Accepted(seq))),
+                    Err(err @ SendError::Rejected(_)) => return Some(Err(err.into_message())),
+                    Err(SendError::Unavailable(_)) => {
+                        self.broken.store(true, Ordering::SeqCst);
+                        self.reader = None;
+                    }
+                }
+            }
+        }
+        let result = if queued.spool {
+            self.client.send(&queued.message)
+        } else {
+            self.client.send_now(&queued.message).map(Delivery::Accepted)
+        };
+        Some(result)
+    }
+}
@@ -0,0 +1,138 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// One event waiting for the server, with the time it was captured.
+#[derive(Serialize, Deserialize)]
+pub struct SpooledEvent {
+    pub captured_at: String,
+    #[serde(rename = "type")]
+    pub message_type: String,
+    pub payload: Value,
+    /// Missing in events spooled by older clients
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub msg_id: Option<String>,
+}
+
+/// A directory with one file per event. File names sort in capture order,
+/// and an event is only removed once the server has answered for it, so a
+/// crash during replay resends at most that one event.
+pub struct Spool {
+    dir: PathBuf,
+    max_events: u64,
+}
+
+impl Spool {
+    pub fn new(dir: PathBuf, max_events: u64) -> Self {
+        Self { dir, max_events }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Spooled event files, oldest first.
+    pub fn entries(&self) -> Result<Vec<PathBuf>, String> {
+        let read_dir = match fs::read_dir(&self.dir) {
+            Ok(read_dir) => read_dir,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(format!("read spool {}: {err}", self.dir.display())),
+        };
+        let mut entries = Vec::new();
+        for entry in read_dir {
+            let path = entry.map_err(|err| format!("read spool: {err}"))?.path();
+            if path.extension().is_some_and(|ext| ext == "json") {
+                entries.push(path);
+            }
+        }
+        entries.sort();
+        Ok(entries)
+    }
+
+    /// Stores an event. Files the server should copy into the bundle are
+    /// copied into the spool too, since collectors delete their temporary
+    /// files once a message is handed off.
+    pub fn push(&self, mut event: SpooledEvent) -> Result<(), String> {
+        if self.entries()?.len() as u64 >= self.max_events {
+            return Err(format!(
+                "spool {} is full ({} events)",
+                self.dir.display(),
+                self.max_events
+            ));
+        }
+        fs::create_dir_all(&self.dir)
+            .map_err(|err| format!("create spool {}: {err}", self.dir.display()))?;
+        let dir = fs::canonicalize(&self.dir)
+            .map_err(|err| format!("resolve spool {}: {err}", self.dir.display()))?;
+        let micros = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_micros())
+            .unwrap_or_default();
+        let name = format!(
+            "{micros:020}-{}-{:06}",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::SeqCst)
+        );
+
+        let source = event.payload.get("source_path").and_then(Value::as_str);
+        if let Some(source) = source.filter(|_| carries_file(&event.message_type)) {
+            let copy = dir.join(format!("{name}.data"));
+            fs::copy(source, &copy).map_err(|err| format!("spool {source}: {err}"))?;
+            event.payload["source_path"] = json!(copy.to_string_lossy());
+        }
+
+        let data =
+            serde_json::to_vec(&event).map_err(|err| format!("encode spooled event: {err}"))?;
+        // Written under a temporary name so a reader never sees half a file
+        let partial = dir.join(format!("{name}.partial"));
+        fs::write(&partial, data).map_err(|err| format!("write spool: {err}"))?;
+        fs::rename(&partial, dir.join(format!("{name}.json")))
+            .map_err(|err| format!("write spool: {err}"))
+    }
+
+    pub fn read(path: &Path) -> Result<SpooledEvent, String> {
+        let data = fs::read(path).map_err(|err| format!("read {}: {err}", path.display()))?;
+        serde_json::from_slice(&data).map_err(|err| format!("parse {}: {err}", path.display()))
+    }
+
+    /// Drops a replayed event and its file copy, if any.
+    pub fn remove(path: &Path) -> Result<(), String> {
+        match fs::remove_file(path.with_extension("data")) {
+            Err(err) if err.kind() != ErrorKind::NotFound => {
+                return Err(format!("remove {}: {err}", path.display()));
+            }
+            _ => {}
+        }
+        fs::remove_file(path).map_err(|err| format!("remove {}: {err}", path.display()))
+    }
+
+    /// Keeps an event the server refused out of the replay queue without
+    /// discarding it.
+    pub fn reject(&self, path: &Path) -> Result<(), String> {
+        let rejected = self.dir.join("rejected");
+        fs::create_dir_all(&rejected)
+            .map_err(|err| format!("create {}: {err}", rejected.display()))?;
+        for path in [path.with_extension("data"), path.to_path_buf()] {
+            let Some(name) = path.file_name() else {
+                continue;
+            };
+            match fs::rename(&path, rejected.join(name)) {
+                Err(err) if err.kind() != ErrorKind::NotFound => {
+                    return Err(format!("move {}: {err}", path.display()));
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+fn carries_file(message_type: &str) -> bool {
+    matches!(message_type, "file_added" | "shot_saved")
+}
@@ -0,0 +1,175 @@
+use crate::{build_message, Message};
+use aegis_client::{Client, Delivery, Pipeline};
+use serde_json::{json, Value};
+use std::io::{self, BufRead, Write};
+use std::sync::mpsc::{self, Sender};
+use std::thread;
+
+/// What the reader side should expect for one input line, in input order.
+enum Pending {
+    /// Handed to the pipeline; its result is next in the replies
+    Sent(usize),
+    /// Could not be turned into a message; never sent
+    Invalid(usize, String),
+}
+
+/// `send --stdin`: one message per input line through
+/// [`Client::pipeline`]. Lines are written without waiting for replies;
+/// results come back in order and are printed as `<line> OK [seq]`,
+/// `<line> SPOOLED` or `<line> ERR <reason>`.
+pub fn send_stdin(client: &Client) -> Result<(), String> {
+    let (pipeline, mut replies) = client.pipeline();
+    let (pending_tx, pending_rx) = mpsc::channel();
+    let writer = thread::spawn(move || write_lines(pipeline, pending_tx));
+
+    let (mut accepted, mut spooled, mut rejected) = (0usize, 0usize, 0usize);
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    for pending in pending_rx {
+        let (line_no, result) = match pending {
+            Pending::Sent(line_no) => (
+                line_no,
+                replies
+                    .next()
+                    .unwrap_or_else(|| Err("pipeline stopped".to_string())),
+            ),
+            Pending::Invalid(line_no, err) => (line_no, Err(err)),
+        };
+        let printed = match result {
+            Ok(Delivery::Accepted(Some(seq))) => {
+                accepted += 1;
+                writeln!(out, "{line_no} OK {seq}")
+            }
+            Ok(Delivery::Accepted(None)) => {
+                accepted += 1;
+                writeln!(out, "{line_no} OK")
+            }
+            Ok(Delivery::Spooled) => {
+                spooled += 1;
+                writeln!(out, "{line_no} SPOOLED")
+            }
+            Err(err) => {
+                rejected += 1;
+                writeln!(out, "{line_no} ERR {err}")
+            }
+        };
+        printed.map_err(|err| format!("write stdout: {err}"))?;
+    }
+
+    writer.join().map_err(|_| "writer thread panicked")??;
+    eprintln!(
+        "{} lines: {accepted} accepted, {spooled} spooled, {rejected} rejected",
+        accepted + spooled + rejected
+    );
+    if rejected > 0 {
+        return Err(format!("{rejected} lines rejected"));
+    }
+    Ok(())
+}
+
+/// Reads stdin and hands each message to the pipeline as soon as it is
+/// parsed. Blank lines and `#` comments are skipped.
+fn write_lines(mut pipeline: Pipeline, pending: Sender<Pending>) -> Result<(), String> {
+    for (index, line) in io::stdin().lock().lines().enumerate() {
+        let line = line.map_err(|err| format!("read stdin: {err}"))?;
+        let line_no = index + 1;
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        match parse_line(trimmed) {
+            // A stop only makes sense while the session is running; never spooled
+            Ok(message) if message.message_type == "stop" => pipeline.send_now(&message),
+            Ok(message) => pipeline.send(&message),
+            Err(err) => {
+                let _ = pending.send(Pending::Invalid(line_no, err));
+                continue;
+            }
+        }
+        let _ = pending.send(Pending::Sent(line_no));
+    }
+    Ok(())
+}
+
+/// A line is either a raw message (`{"type":..,"payload":..}`, optionally
+/// with `observed_at` and `msg_id`) or a subcommand with its arguments, e.g.
+/// `focus org.gnome.Terminal "Terminal"`.
+fn parse_line(line: &str) -> Result<Message, String> {
+    if line.starts_with('{') {
+        let value: Value =
+            serde_json::from_str(line).map_err(|err| format!("invalid JSON: {err}"))?;
+        let message_type = value
+            .get("type")
+            .and_then(Value::as_str)
+            .filter(|message_type| !message_type.is_empty())
+            .ok_or("message missing type")?;
+        return Ok(Message {
+            message_type: message_type.to_string(),
+            payload: value.get("payload").cloned().unwrap_or_else(|| json!({})),
+            observed_at: value
+                .get("observed_at")
+                .and_then(Value::as_str)
+                .map(str::to_string),
+            msg_id: value
+                .get("msg_id")
+                .and_then(Value::as_str)
+                .map(str::to_string),
+        });
+    }
+    let mut words = split_words(line)?.into_iter();
+    let command = words.next().ok_or("empty command")?;
+    build_message(&command, &mut words)
+}
+
+/// Splits on whitespace the way a shell would for plain arguments:
+/// single quotes are literal, double quotes allow `\"` and `\\`, and a
+/// backslash outside quotes escapes the next character.
+fn split_words(line: &str) -> Result<Vec<String>, String> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut chars = line.chars();
+    while let Some(ch) = chars.next() {
+        match ch {
+            ch if ch.is_whitespace() => {
+                if let Some(word) = word.take() {
+                    words.push(word);
+                }
+            }
+            '\'' => {
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(ch) => word.push(ch),
+                        None => return Err("unterminated single quote".to_string()),
+                    }
+                }
+            }
+            '"' => {
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(ch @ ('"' | '\\')) => word.push(ch),
+                            Some(ch) => {
+                                word.push('\\');
+                                word.push(ch);
+                            }
+                            None => return Err("unterminated double quote".to_string()),
+                        },
+                        Some(ch) => word.push(ch),
+                        None => return Err("unterminated double quote".to_string()),
+                    }
+                }
+            }
+            '\\' => {
+                let escaped = chars.next().ok_or("trailing backslash")?;
+                word.get_or_insert_with(String::new).push(escaped);
+            }
+            ch => word.get_or_insert_with(String::new).push(ch),
+        }
+    }
+    words.extend(word);
+    Ok(words)
+}
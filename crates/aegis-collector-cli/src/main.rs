@@ -1,14 +1,20 @@
-use serde::Serialize;
+mod batch;
+
+use aegis_client::{Client, Delivery, FileUpload, FocusChange, InputStats, Message, ShotUpload};
+use aegis_config::ConfigLoader;
 use serde_json::json;
 use std::env;
-use std::io::{BufRead, BufReader, Write};
-use std::net::TcpStream;
+use std::path::PathBuf;
+
+const USAGE: &str = "usage: aegis-collector-cli [--config <file>] [--set <key=value>]... <focus|file|shot|input|stop|flush|send --stdin> [args]";
 
-#[derive(Serialize)]
-struct Message {
-    #[serde(rename = "type")]
-    message_type: String,
-    payload: serde_json::Value,
+/// One subcommand with its arguments.
+enum Command {
+    Focus(FocusChange),
+    File(FileUpload),
+    Shot(ShotUpload),
+    Input(InputStats),
+    Stop(String),
 }
 
 fn main() {
@@ -19,94 +25,96 @@ fn main() {
 }
 
 fn run() -> Result<(), String> {
-    let mut args = env::args().skip(1);
-    let command =
-        args.next()
-            .ok_or("usage: aegis-collector-cli <focus|file|shot|input|stop> [args]")?;
-    let addr = env::var("AEGIS_CORE_ADDR").unwrap_or_else(|_| "127.0.0.1:7878".to_string());
+    let (loader, args) = ConfigLoader::from_args(env::args().skip(1))?;
+    let mut args = args.into_iter();
+    let command = args.next().ok_or(USAGE)?;
+    let config = loader.load()?.config;
+
+    let client = Client::from_config(&config, "cli");
+    if command == "send" {
+        return match args.next().as_deref() {
+            Some("--stdin") => batch::send_stdin(&client),
+            _ => Err(USAGE.to_string()),
+        };
+    }
+    if command == "flush" {
+        let replayed = client.flush()?;
+        eprintln!("replayed {replayed} spooled events");
+        return Ok(());
+    }
+
+    let delivery = match parse_command(&command, &mut args)? {
+        Command::Focus(focus) => client.send_focus(&focus)?,
+        Command::File(upload) => client.upload_file(&upload)?,
+        Command::Shot(shot) => client.upload_shot(&shot)?,
+        Command::Input(stats) => client.send_input_stats(&stats)?,
+        // A stop only makes sense while the session is running; never spooled
+        Command::Stop(reason) => return client.stop(&reason),
+    };
+    if delivery == Delivery::Spooled {
+        eprintln!("server unavailable; event spooled for replay");
+    }
+    Ok(())
+}
 
-    let message = match command.as_str() {
+/// Builds the message for one subcommand; used for `send --stdin` lines
+/// written in subcommand syntax.
+fn build_message(command: &str, args: &mut impl Iterator<Item = String>) -> Result<Message, String> {
+    match parse_command(command, args)? {
+        Command::Focus(focus) => Message::typed("app_focus_changed", &focus),
+        Command::File(upload) => Message::typed("file_added", &upload),
+        Command::Shot(shot) => Message::typed("shot_saved", &shot),
+        Command::Input(stats) => Message::typed("input_stats", &stats),
+        Command::Stop(reason) => Ok(Message::new("stop", json!({ "reason": reason }))),
+    }
+}
+
+fn parse_command(command: &str, args: &mut impl Iterator<Item = String>) -> Result<Command, String> {
+    let command = match command {
         "focus" => {
             let app_id = args.next().ok_or("missing app_id")?;
             let app_name = args.next().ok_or("missing app_name")?;
-            let window_title = args.next();
-            let mut payload = json!({
-                "app_id": app_id,
-                "app_name": app_name,
-            });
-            if let Some(title) = window_title {
-                payload["window_title"] = json!(title);
-            }
-            Message {
-                message_type: "app_focus_changed".to_string(),
-                payload,
-            }
+            Command::Focus(FocusChange {
+                app_id,
+                app_name,
+                window_title: args.next(),
+                ..FocusChange::default()
+            })
         }
         "file" => {
             let source_path = args.next().ok_or("missing source_path")?;
             let rel_path = args.next().ok_or("missing rel_path")?;
             let kind = args.next().ok_or("missing kind")?;
-            Message {
-                message_type: "file_added".to_string(),
-                payload: json!({
-                    "source_path": source_path,
-                    "rel_path": rel_path,
-                    "kind": kind,
-                }),
-            }
+            Command::File(FileUpload {
+                source_path: PathBuf::from(source_path),
+                rel_path,
+                kind,
+            })
         }
         "shot" => {
             let source_path = args.next().ok_or("missing source_path")?;
-            let rel_path = args.next().ok_or("missing rel_path")?;
-            Message {
-                message_type: "shot_saved".to_string(),
-                payload: json!({
-                    "source_path": source_path,
-                    "rel_path": rel_path,
-                }),
-            }
+            // Without a rel_path the server numbers the shot
+            Command::Shot(ShotUpload {
+                source_path: PathBuf::from(source_path),
+                rel_path: args.next(),
+                ..ShotUpload::default()
+            })
         }
         "input" => {
             let interval_ms = args.next().ok_or("missing interval_ms")?;
             let key_count = args.next().ok_or("missing key_count")?;
             let backspace_count = args.next().ok_or("missing backspace_count")?;
             let paste_count = args.next().ok_or("missing paste_count")?;
-            Message {
-                message_type: "input_stats".to_string(),
-                payload: json!({
-                    "interval_ms": interval_ms.parse::<u64>().map_err(|_| "invalid interval_ms")?,
-                    "key_count": key_count.parse::<u64>().map_err(|_| "invalid key_count")?,
-                    "backspace_count": backspace_count.parse::<u64>().map_err(|_| "invalid backspace_count")?,
-                    "paste_count": paste_count.parse::<u64>().map_err(|_| "invalid paste_count")?,
-                }),
-            }
-        }
-        "stop" => {
-            let reason = args.next().unwrap_or_else(|| "user".to_string());
-            Message {
-                message_type: "stop".to_string(),
-                payload: json!({ "reason": reason }),
-            }
+            Command::Input(InputStats {
+                interval_ms: interval_ms.parse().map_err(|_| "invalid interval_ms")?,
+                key_count: key_count.parse().map_err(|_| "invalid key_count")?,
+                backspace_count: backspace_count.parse().map_err(|_| "invalid backspace_count")?,
+                paste_count: paste_count.parse().map_err(|_| "invalid paste_count")?,
+                ..InputStats::default()
+            })
         }
-        _ => return Err("usage: aegis-collector-cli <focus|file|shot|input|stop> [args]".to_string()),
+        "stop" => Command::Stop(args.next().unwrap_or_else(|| "user".to_string())),
+        _ => return Err(format!("unknown command: {command}")),
     };
-
-    let mut stream =
-        TcpStream::connect(&addr).map_err(|err| format!("connect {addr}: {err}"))?;
-    serde_json::to_writer(&mut stream, &message)
-        .map_err(|err| format!("write message: {err}"))?;
-    stream
-        .write_all(b"\n")
-        .map_err(|err| format!("write newline: {err}"))?;
-
-    let mut reader = BufReader::new(stream);
-    let mut response = String::new();
-    reader
-        .read_line(&mut response)
-        .map_err(|err| format!("read response: {err}"))?;
-    if !response.starts_with("OK") {
-        return Err(format!("unexpected response: {response}"));
-    }
-
-    Ok(())
+    Ok(command)
 }
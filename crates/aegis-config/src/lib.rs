@@ -0,0 +1,607 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+
+const ENV_PREFIX: &str = "AEGIS__";
+const ENV_CONFIG_FILE: &str = "AEGIS_CONFIG";
+const ENV_CORE_ADDR: &str = "AEGIS_CORE_ADDR";
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    pub server: ServerConfig,
+    pub recording: RecordingConfig,
+    pub paths: PathsConfig,
+    pub app: AppConfig,
+    pub agent: AgentConfig,
+    pub client: ClientConfig,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct ServerConfig {
+    pub default_addr: String,
+    pub stop_wait_ms: u64,
+    pub stop_retry_count: u32,
+    pub stop_retry_interval_ms: u64,
+    /// How often collectors send `heartbeat`; 0 disables heartbeats.
+    pub heartbeat_interval_ms: u64,
+    /// Missed heartbeats before the server records `collector_lost`.
+    pub heartbeat_missed_limit: u32,
+    /// How far a collector's `observed_at` may run ahead of the server
+    /// clock before the message is rejected.
+    pub observed_max_ahead_ms: u64,
+    /// How far `observed_at` may lag behind the server clock; 0 accepts
+    /// any delay, which spooled replays need.
+    pub observed_max_behind_ms: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct RecordingConfig {
+    pub segment_duration_seconds: u64,
+    pub poll_interval_ms: u64,
+    /// `native` runs the platform recorder binary, `fake` writes
+    /// deterministic placeholder segments.
+    pub backend: String,
+    /// Overrides the recorder binary lookup for the `native` backend.
+    pub recorder_path: Option<String>,
+    pub video: VideoConfig,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct VideoConfig {
+    pub codec: String,
+    pub resolution: ResolutionConfig,
+    pub fps: u32,
+    pub bitrate_bps: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct ResolutionConfig {
+    pub width: u32,
+    pub height: u32,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct PathsConfig {
+    pub default_save_dir: String,
+    pub temp_dir: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct AppConfig {
+    pub platform: String,
+    pub version: String,
+}
+
+/// How `aegis-agent` supervises the collectors.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct AgentConfig {
+    /// Delay before the first restart of a crashed collector; doubles on
+    /// every consecutive crash up to `restart_backoff_max_ms`.
+    pub restart_backoff_ms: u64,
+    pub restart_backoff_max_ms: u64,
+    /// How long a collector gets to exit after SIGTERM before SIGKILL.
+    pub shutdown_timeout_ms: u64,
+    /// Keyed by name so a layer can toggle one collector
+    /// (`--set agent.collectors.screenshot.enabled=true`).
+    pub collectors: BTreeMap<String, CollectorConfig>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct CollectorConfig {
+    pub enabled: bool,
+    /// Binary to run; `null` means `aegis-<name>-collector`.
+    #[serde(default)]
+    pub command: Option<String>,
+    /// Extra arguments after the shared `--config`/`--set` options.
+    #[serde(default)]
+    pub args: Vec<String>,
+}
+
+/// How collectors deliver messages through `aegis-client`.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct ClientConfig {
+    /// Connection attempts per message before it is spooled.
+    pub retry_attempts: u32,
+    /// Delay after the first failed attempt; doubles up to
+    /// `retry_backoff_max_ms`.
+    pub retry_backoff_ms: u64,
+    pub retry_backoff_max_ms: u64,
+    /// Where undeliverable events wait for the server; `null` means
+    /// `aegis-spool` in the temp dir.
+    pub spool_dir: Option<String>,
+    /// Events kept per collector while the server is down; 0 disables
+    /// spooling.
+    pub spool_max_events: u64,
+}
+
+impl CollectorConfig {
+    fn new(enabled: bool) -> Self {
+        Self {
+            enabled,
+            command: None,
+            args: Vec::new(),
+        }
+    }
+
+    pub fn command_for(&self, name: &str) -> String {
+        self.command
+            .clone()
+            .unwrap_or_else(|| format!("aegis-{name}-collector"))
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            server: ServerConfig {
+                default_addr: "127.0.0.1:7878".to_string(),
+                stop_wait_ms: 300,
+                stop_retry_count: 10,
+                stop_retry_interval_ms: 200,
+                heartbeat_interval_ms: 10_000,
+                heartbeat_missed_limit: 3,
+                observed_max_ahead_ms: 5_000,
+                observed_max_behind_ms: 0,
+            },
+            recording: RecordingConfig {
+                segment_duration_seconds: 600,
+                poll_interval_ms: 200,
+                backend: "native".to_string(),
+                recorder_path: None,
+                video: VideoConfig {
+                    codec: "hevc".to_string(),
+                    resolution: ResolutionConfig {
+                        width: 1280,
+                        height: 720,
+                    },
+                    fps: 30,
+                    bitrate_bps: 2_000_000,
+                },
+            },
+            paths: PathsConfig {
+                default_save_dir: "~/Downloads".to_string(),
+                temp_dir: None,
+            },
+            app: AppConfig {
+                platform: default_platform().to_string(),
+                version: env!("CARGO_PKG_VERSION").to_string(),
+            },
+            agent: AgentConfig {
+                restart_backoff_ms: 1000,
+                restart_backoff_max_ms: 60_000,
+                shutdown_timeout_ms: 3000,
+                collectors: [
+                    ("clipboard", false),
+                    ("dns", false),
+                    ("file", false),
+                    ("focus", true),
+                    ("idle", true),
+                    ("input", true),
+                    ("process", false),
+                    ("screenshot", false),
+                ]
+                .into_iter()
+                .map(|(name, enabled)| (name.to_string(), CollectorConfig::new(enabled)))
+                .collect(),
+            },
+            client: ClientConfig {
+                retry_attempts: 3,
+                retry_backoff_ms: 200,
+                retry_backoff_max_ms: 2000,
+                spool_dir: None,
+                spool_max_events: 10_000,
+            },
+        }
+    }
+}
+
+impl Config {
+    /// Checks the value ranges and combinations that deserializing into
+    /// these types does not; every load runs it after merging the layers.
+    pub fn validate(&self) -> Result<(), String> {
+        let mut errors = Vec::new();
+
+        if self.server.default_addr.parse::<SocketAddr>().is_err() {
+            errors.push(format!(
+                "server.default_addr: invalid socket address {:?}",
+                self.server.default_addr
+            ));
+        }
+        if self.server.heartbeat_missed_limit == 0 {
+            errors.push("server.heartbeat_missed_limit: must be at least 1".to_string());
+        }
+        if self.recording.segment_duration_seconds == 0 {
+            errors.push("recording.segment_duration_seconds: must be at least 1".to_string());
+        }
+        if self.recording.poll_interval_ms < 10 {
+            errors.push("recording.poll_interval_ms: must be at least 10".to_string());
+        }
+
+        if !matches!(self.recording.backend.as_str(), "native" | "fake") {
+            errors.push(format!(
+                "recording.backend: unsupported backend {:?} (expected native or fake)",
+                self.recording.backend
+            ));
+        }
+        self.recording.video.check(&mut errors);
+
+        if self.paths.default_save_dir.trim().is_empty() {
+            errors.push("paths.default_save_dir: must not be empty".to_string());
+        }
+        if !matches!(self.app.platform.as_str(), "macos" | "windows" | "linux") {
+            errors.push(format!(
+                "app.platform: unsupported platform {:?} (expected macos, windows or linux)",
+                self.app.platform
+            ));
+        }
+        if self.app.version.trim().is_empty() {
+            errors.push("app.version: must not be empty".to_string());
+        }
+        self.agent.check(&mut errors);
+        if self.client.retry_attempts == 0 {
+            errors.push("client.retry_attempts: must be at least 1".to_string());
+        }
+        if self.client.retry_backoff_max_ms < self.client.retry_backoff_ms {
+            errors.push(
+                "client.retry_backoff_max_ms: must not be below retry_backoff_ms".to_string(),
+            );
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors.join("\n"))
+        }
+    }
+
+    pub fn save_dir(&self) -> PathBuf {
+        expand_path(&self.paths.default_save_dir)
+    }
+
+    pub fn temp_dir(&self) -> PathBuf {
+        match self.paths.temp_dir {
+            Some(ref dir) => expand_path(dir),
+            None => env::temp_dir(),
+        }
+    }
+
+    pub fn spool_dir(&self) -> PathBuf {
+        match self.client.spool_dir {
+            Some(ref dir) => expand_path(dir),
+            None => self.temp_dir().join("aegis-spool"),
+        }
+    }
+}
+
+impl AgentConfig {
+    fn check(&self, errors: &mut Vec<String>) {
+        if self.restart_backoff_ms == 0 {
+            errors.push("agent.restart_backoff_ms: must be at least 1".to_string());
+        }
+        if self.restart_backoff_max_ms < self.restart_backoff_ms {
+            errors.push(
+                "agent.restart_backoff_max_ms: must not be below restart_backoff_ms".to_string(),
+            );
+        }
+        for (name, collector) in &self.collectors {
+            let valid_name = !name.is_empty()
+                && name
+                    .chars()
+                    .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_');
+            if !valid_name {
+                errors.push(format!(
+                    "agent.collectors: invalid collector name {name:?} (use a-z, 0-9, - and _)"
+                ));
+            }
+            if collector.command.as_deref().is_some_and(|command| command.trim().is_empty()) {
+                errors.push(format!("agent.collectors.{name}.command: must not be empty"));
+            }
+        }
+    }
+}
+
+impl VideoConfig {
+    /// Arguments passed to a recorder after `<output_path> <seconds>`; see
+    /// `spec/recorder_protocol.md`.
+    pub fn recorder_args(&self) -> Vec<String> {
+        vec![
+            "--codec".to_string(),
+            self.codec.clone(),
+            "--width".to_string(),
+            self.resolution.width.to_string(),
+            "--height".to_string(),
+            self.resolution.height.to_string(),
+            "--fps".to_string(),
+            self.fps.to_string(),
+            "--bitrate".to_string(),
+            self.bitrate_bps.to_string(),
+        ]
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        let mut errors = Vec::new();
+        self.check(&mut errors);
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors.join("\n"))
+        }
+    }
+
+    fn check(&self, errors: &mut Vec<String>) {
+        if !matches!(self.codec.as_str(), "hevc" | "h264") {
+            errors.push(format!(
+                "recording.video.codec: unsupported codec {:?} (expected hevc or h264)",
+                self.codec
+            ));
+        }
+        let resolution = &self.resolution;
+        if resolution.width == 0 || resolution.height == 0 {
+            errors.push("recording.video.resolution: width and height must be non-zero".to_string());
+        } else if !resolution.width.is_multiple_of(2) || !resolution.height.is_multiple_of(2) {
+            errors.push("recording.video.resolution: width and height must be even".to_string());
+        }
+        if self.fps == 0 || self.fps > 120 {
+            errors.push("recording.video.fps: must be between 1 and 120".to_string());
+        }
+        if self.bitrate_bps < 100_000 || self.bitrate_bps > 100_000_000 {
+            errors.push(
+                "recording.video.bitrate_bps: must be between 100000 and 100000000".to_string(),
+            );
+        }
+
+        // Combinations the recorders cannot encode even though each value is
+        // valid on its own.
+        if self.codec == "h264" && (resolution.width > 4096 || resolution.height > 2304) {
+            errors.push("recording.video: h264 supports at most 4096x2304".to_string());
+        }
+        if resolution.width > 8192 || resolution.height > 4320 {
+            errors.push("recording.video: resolution above 8192x4320 is not supported".to_string());
+        }
+        if self.fps > 60 && u64::from(resolution.width) * u64::from(resolution.height) > 1920 * 1080
+        {
+            errors.push(
+                "recording.video: fps above 60 is only supported up to 1920x1080".to_string(),
+            );
+        }
+    }
+}
+
+/// Result of a layered load: the effective config plus the layers that
+/// contributed to it, in the order they were applied.
+pub struct LoadedConfig {
+    pub config: Config,
+    pub sources: Vec<String>,
+}
+
+/// Builds the effective config from, in increasing priority: built-in
+/// defaults, the system file, the user file, an explicit file
+/// (`--config` or `AEGIS_CONFIG`), environment variables and `--set`
+/// overrides.
+#[derive(Default)]
+pub struct ConfigLoader {
+    config_file: Option<PathBuf>,
+    overrides: Vec<String>,
+    skip_env: bool,
+}
+
+impl ConfigLoader {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn config_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.config_file = Some(path.into());
+        self
+    }
+
+    /// Adds a `section.key=value` override; `value` is parsed as JSON and
+    /// falls back to a plain string.
+    pub fn set(mut self, assignment: impl Into<String>) -> Self {
+        self.overrides.push(assignment.into());
+        self
+    }
+
+    pub fn skip_env(mut self) -> Self {
+        self.skip_env = true;
+        self
+    }
+
+    /// Pulls `--config <file>` and `--set <key=value>` out of `args`,
+    /// returning the remaining arguments untouched.
+    pub fn from_args(
+        args: impl IntoIterator<Item = String>,
+    ) -> Result<(Self, Vec<String>), String> {
+        let mut loader = Self::new();
+        let mut rest = Vec::new();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--config" => {
+                    let path = args.next().ok_or("--config requires a file path")?;
+                    loader = loader.config_file(path);
+                }
+                "--set" => {
+                    let assignment = args.next().ok_or("--set requires key=value")?;
+                    loader = loader.set(assignment);
+                }
+                _ => rest.push(arg),
+            }
+        }
+        Ok((loader, rest))
+    }
+
+    pub fn load(&self) -> Result<LoadedConfig, String> {
+        let defaults = serde_json::to_value(Config::default())
+            .map_err(|err| format!("serialize defaults: {err}"))?;
+        let mut merged = defaults;
+        let mut sources = vec!["defaults".to_string()];
+
+        let mut files = Vec::new();
+        if let Some(path) = system_config_path() {
+            files.push((path, false));
+        }
+        if let Some(path) = user_config_path() {
+            files.push((path, false));
+        }
+        let explicit = self.config_file.clone().or_else(|| {
+            if self.skip_env {
+                None
+            } else {
+                env::var_os(ENV_CONFIG_FILE).map(PathBuf::from)
+            }
+        });
+        if let Some(path) = explicit {
+            files.push((path, true));
+        }
+
+        for (path, required) in files {
+            if !path.exists() {
+                if required {
+                    return Err(format!("config file not found: {}", path.display()));
+                }
+                continue;
+            }
+            let layer = read_layer(&path)?;
+            merge_value(&mut merged, layer);
+            sources.push(path.display().to_string());
+        }
+
+        if !self.skip_env {
+            let mut env_vars: Vec<(String, String)> = env::vars().collect();
+            env_vars.sort();
+            for (name, value) in env_vars {
+                let key = if name == ENV_CORE_ADDR {
+                    "server.default_addr".to_string()
+                } else if let Some(rest) = name.strip_prefix(ENV_PREFIX) {
+                    rest.to_ascii_lowercase().replace("__", ".")
+                } else {
+                    continue;
+                };
+                apply_assignment(&mut merged, &key, &value)
+                    .map_err(|err| format!("env {name}: {err}"))?;
+                sources.push(format!("env {name}"));
+            }
+        }
+
+        for assignment in &self.overrides {
+            let (key, value) = assignment
+                .split_once('=')
+                .ok_or_else(|| format!("--set {assignment}: expected key=value"))?;
+            apply_assignment(&mut merged, key.trim(), value)
+                .map_err(|err| format!("--set {assignment}: {err}"))?;
+            sources.push(format!("--set {}", key.trim()));
+        }
+
+        let config: Config = serde_json::from_value(merged)
+            .map_err(|err| format!("invalid config: {err}"))?;
+        config.validate()?;
+        Ok(LoadedConfig { config, sources })
+    }
+}
+
+/// Parses and validates a single config file on top of the defaults,
+/// ignoring every other layer.
+pub fn check_file(path: &Path) -> Result<Config, String> {
+    let mut merged = serde_json::to_value(Config::default())
+        .map_err(|err| format!("serialize defaults: {err}"))?;
+    merge_value(&mut merged, read_layer(path)?);
+    let config: Config = serde_json::from_value(merged)
+        .map_err(|err| format!("{}: {err}", path.display()))?;
+    config.validate()?;
+    Ok(config)
+}
+
+pub fn system_config_path() -> Option<PathBuf> {
+    if cfg!(target_os = "windows") {
+        env::var_os("ProgramData").map(|dir| PathBuf::from(dir).join("Aegis").join("config.json"))
+    } else if cfg!(target_os = "macos") {
+        Some(PathBuf::from("/Library/Application Support/Aegis/config.json"))
+    } else {
+        Some(PathBuf::from("/etc/aegis/config.json"))
+    }
+}
+
+pub fn user_config_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("aegis").join("config.json"))
+}
+
+pub fn expand_path(path: &str) -> PathBuf {
+    if path == "~" {
+        if let Some(home) = dirs::home_dir() {
+            return home;
+        }
+    }
+    if let Some(rest) = path.strip_prefix("~/") {
+        if let Some(home) = dirs::home_dir() {
+            return home.join(rest);
+        }
+    }
+    PathBuf::from(path)
+}
+
+fn default_platform() -> &'static str {
+    match env::consts::OS {
+        "macos" => "macos",
+        "windows" => "windows",
+        _ => "linux",
+    }
+}
+
+fn read_layer(path: &Path) -> Result<Value, String> {
+    let content = fs::read_to_string(path)
+        .map_err(|err| format!("read config {}: {err}", path.display()))?;
+    let value: Value = serde_json::from_str(&content)
+        .map_err(|err| format!("parse config {}: {err}", path.display()))?;
+    if !value.is_object() {
+        return Err(format!("config {} must be a JSON object", path.display()));
+    }
+    Ok(value)
+}
+
+fn merge_value(base: &mut Value, layer: Value) {
+    match (base, layer) {
+        (Value::Object(base_map), Value::Object(layer_map)) => {
+            for (key, value) in layer_map {
+                match base_map.get_mut(&key) {
+                    Some(existing) => merge_value(existing, value),
+                    None => {
+                        base_map.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, layer) => *base = layer,
+    }
+}
+
+fn apply_assignment(root: &mut Value, key: &str, raw: &str) -> Result<(), String> {
+    let parts: Vec<&str> = key.split('.').collect();
+    if parts.iter().any(|part| part.is_empty()) {
+        return Err(format!("invalid key {key:?}"));
+    }
+    let value = serde_json::from_str(raw).unwrap_or_else(|_| Value::String(raw.to_string()));
+
+    let mut layer = value;
+    for part in parts.iter().rev() {
+        let mut map = Map::new();
+        map.insert(part.to_string(), layer);
+        layer = Value::Object(map);
+    }
+    merge_value(root, layer);
+    Ok(())
+}
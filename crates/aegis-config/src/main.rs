@@ -0,0 +1,58 @@
+use aegis_config::{check_file, system_config_path, user_config_path, ConfigLoader};
+use std::env;
+use std::path::PathBuf;
+
+const USAGE: &str =
+    "usage: aegis-config <dump|check> [--config <file>] [--set <key=value>]... [file]";
+
+fn main() {
+    if let Err(err) = run() {
+        eprintln!("FAIL: {err}");
+        std::process::exit(1);
+    }
+}
+
+fn run() -> Result<(), String> {
+    let (loader, rest) = ConfigLoader::from_args(env::args().skip(1))?;
+    let mut rest = rest.into_iter();
+    let command = rest.next().ok_or(USAGE)?;
+
+    match command.as_str() {
+        "dump" => {
+            if rest.next().is_some() {
+                return Err(USAGE.to_string());
+            }
+            let loaded = loader.load()?;
+            for source in &loaded.sources {
+                eprintln!("layer: {source}");
+            }
+            let output = serde_json::to_string_pretty(&loaded.config)
+                .map_err(|err| format!("serialize config: {err}"))?;
+            println!("{output}");
+        }
+        "check" => {
+            if let Some(file) = rest.next() {
+                check_file(&PathBuf::from(&file))?;
+                println!("OK {file}");
+                return Ok(());
+            }
+            let loaded = loader.load()?;
+            for source in &loaded.sources {
+                println!("layer: {source}");
+            }
+            for (label, path) in [
+                ("system", system_config_path()),
+                ("user", user_config_path()),
+            ] {
+                if let Some(path) = path {
+                    let state = if path.exists() { "found" } else { "absent" };
+                    println!("{label} file: {} ({state})", path.display());
+                }
+            }
+            println!("OK");
+        }
+        _ => return Err(USAGE.to_string()),
+    }
+
+    Ok(())
+}
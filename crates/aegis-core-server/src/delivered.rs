@@ -0,0 +1,76 @@
+use crate::Handled;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Condvar, Mutex};
+
+/// How many applied `msg_id`s are remembered; a client resends within
+/// seconds, so only recent ones matter.
+const REMEMBERED: usize = 65_536;
+
+enum State {
+    /// Being applied by another connection
+    Pending,
+    Done(Handled),
+}
+
+/// Replies of recently applied messages by `msg_id`, so a message resent
+/// after a lost reply is answered again instead of applied twice.
+#[derive(Default)]
+pub struct Delivered {
+    state: Mutex<(HashMap<String, State>, VecDeque<String>)>,
+    changed: Condvar,
+}
+
+/// What to do with an incoming message.
+pub enum Begin {
+    /// Not seen before: apply it, then call [`Delivered::finish`]
+    New,
+    /// Already applied; send this reply again
+    Repeat(Handled),
+}
+
+impl Delivered {
+    /// Claims `msg_id`. While another connection is still applying the
+    /// same id (the client gave up on it and resent), waits for its result.
+    pub fn begin(&self, msg_id: &str) -> Result<Begin, String> {
+        let mut state = self.state.lock().map_err(|_| "delivered lock poisoned")?;
+        loop {
+            match state.0.get(msg_id) {
+                None => {
+                    state.0.insert(msg_id.to_string(), State::Pending);
+                    return Ok(Begin::New);
+                }
+                Some(State::Done(reply)) => return Ok(Begin::Repeat(*reply)),
+                Some(State::Pending) => {
+                    state = self
+                        .changed
+                        .wait(state)
+                        .map_err(|_| "delivered lock poisoned")?;
+                }
+            }
+        }
+    }
+
+    /// Records the outcome of a message claimed with [`begin`](Self::begin).
+    /// A rejected message is forgotten, so a resend is applied afresh.
+    pub fn finish(&self, msg_id: &str, reply: Option<Handled>) {
+        let Ok(mut state) = self.state.lock() else {
+            return;
+        };
+        let (replies, order) = &mut *state;
+        match reply {
+            Some(reply) => {
+                replies.insert(msg_id.to_string(), State::Done(reply));
+                order.push_back(msg_id.to_string());
+                while order.len() > REMEMBERED {
+                    if let Some(oldest) = order.pop_front() {
+                        replies.remove(&oldest);
+                    }
+                }
+            }
+            None => {
+                replies.remove(msg_id);
+            }
+        }
+        self.changed.notify_all();
+    }
+}
@@ -0,0 +1,107 @@
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+struct Liveness {
+    pid: Option<u64>,
+    interval: Duration,
+    last_seen: Instant,
+    lost: bool,
+}
+
+/// Last heartbeat per collector name. Heartbeats themselves are not
+/// events; only the first one from a process and changes in liveness are.
+pub struct Heartbeats {
+    missed_limit: u32,
+    collectors: Mutex<BTreeMap<String, Liveness>>,
+}
+
+impl Heartbeats {
+    pub fn new(missed_limit: u32) -> Self {
+        Self {
+            missed_limit,
+            collectors: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Records a heartbeat and returns the event it implies, if any:
+    /// `heartbeat` for a collector (or a new process of it) heard for the
+    /// first time, `collector_recovered` after a `collector_lost`.
+    pub fn beat(&self, payload: &Value) -> Result<Option<(&'static str, Value)>, String> {
+        let name = payload
+            .get("collector")
+            .and_then(Value::as_str)
+            .filter(|name| !name.is_empty())
+            .ok_or("heartbeat missing collector")?;
+        let interval_ms = payload
+            .get("interval_ms")
+            .and_then(Value::as_u64)
+            .filter(|interval| *interval > 0)
+            .ok_or("heartbeat missing interval_ms")?;
+        let pid = payload.get("pid").and_then(Value::as_u64);
+
+        let mut collectors = self.collectors.lock().map_err(|_| "heartbeat lock poisoned")?;
+        let now = Instant::now();
+        let previous = collectors.insert(
+            name.to_string(),
+            Liveness {
+                pid,
+                interval: Duration::from_millis(interval_ms),
+                last_seen: now,
+                lost: false,
+            },
+        );
+        let event = match previous {
+            Some(previous) if previous.lost => {
+                let mut recovered = json!({
+                    "collector": name,
+                    "gap_ms": now.duration_since(previous.last_seen).as_millis() as u64,
+                });
+                if let Some(pid) = pid {
+                    recovered["pid"] = json!(pid);
+                }
+                Some(("collector_recovered", recovered))
+            }
+            Some(previous) if previous.pid == pid => None,
+            _ => Some(("heartbeat", payload.clone())),
+        };
+        Ok(event)
+    }
+
+    /// `collector_lost` events for collectors silent for longer than
+    /// `missed_limit` intervals; each loss is reported once.
+    pub fn check(&self) -> Vec<Value> {
+        let Ok(mut collectors) = self.collectors.lock() else {
+            return Vec::new();
+        };
+        let mut lost = Vec::new();
+        for (name, liveness) in collectors.iter_mut() {
+            let silent = liveness.last_seen.elapsed();
+            if liveness.lost || silent <= liveness.interval * self.missed_limit {
+                continue;
+            }
+            liveness.lost = true;
+            let mut payload = json!({
+                "collector": name,
+                "interval_ms": liveness.interval.as_millis() as u64,
+                "silent_ms": silent.as_millis() as u64,
+            });
+            if let Some(pid) = liveness.pid {
+                payload["pid"] = json!(pid);
+            }
+            lost.push(payload);
+        }
+        lost
+    }
+
+    /// Forgets a loss returned by [`check`](Self::check) whose event could
+    /// not be recorded, so the next check reports it again.
+    pub fn unmark_lost(&self, collector: &str) {
+        if let Ok(mut collectors) = self.collectors.lock() {
+            if let Some(liveness) = collectors.get_mut(collector) {
+                liveness.lost = false;
+            }
+        }
+    }
+}
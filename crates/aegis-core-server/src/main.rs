@@ -1,11 +1,27 @@
-use aegis_core::SessionWriter;
+mod delivered;
+mod heartbeat;
+mod observed;
+
+use aegis_config::ConfigLoader;
+use aegis_core::{sha256_hex, SessionWriter};
+use delivered::{Begin, Delivered};
+use heartbeat::Heartbeats;
+use observed::SkewLimits;
 use serde::Deserialize;
 use serde_json::Value;
 use std::env;
-use std::fs;
-use std::io::{BufRead, BufReader, Write};
-use std::net::{TcpListener, TcpStream};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, ErrorKind, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
 use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+/// The active writer, shared by all connections; `None` once a `stop`
+/// message has finalized the bundle.
+type SharedWriter = Arc<Mutex<Option<SessionWriter>>>;
 
 #[derive(Deserialize)]
 struct IncomingMessage {
@@ -13,6 +29,12 @@ struct IncomingMessage {
     message_type: String,
     #[serde(default)]
     payload: Value,
+    /// When the collector observed the event (RFC 3339), if it says so
+    #[serde(default)]
+    observed_at: Option<String>,
+    /// Client-assigned id; a resend of an applied id is not applied again
+    #[serde(default)]
+    msg_id: Option<String>,
 }
 
 fn main() {
@@ -23,16 +45,23 @@ fn main() {
 }
 
 fn run() -> Result<(), String> {
-    let mut args = env::args().skip(1);
+    let (loader, args) = ConfigLoader::from_args(env::args().skip(1))?;
+    let config = loader.load()?.config;
+    let mut args = args.into_iter();
     let platform =
         args.next()
-            .ok_or("usage: aegis-core-server <platform> <app_version> [save_dir] [addr]")?;
+            .ok_or("usage: aegis-core-server [--config <file>] [--set <key=value>]... <platform> <app_version> [save_dir | --resume <bundle_dir>] [addr]")?;
     let app_version = args.next().ok_or("missing app_version")?;
 
     let mut save_dir: Option<PathBuf> = None;
+    let mut resume: Option<PathBuf> = None;
     let mut addr: Option<String> = None;
-    for arg in args {
-        if save_dir.is_none() && addr.is_none() {
+    while let Some(arg) = args.next() {
+        if arg == "--resume" && save_dir.is_none() && resume.is_none() {
+            resume = Some(PathBuf::from(args.next().ok_or("missing bundle_dir")?));
+        } else if resume.is_some() && addr.is_none() {
+            addr = Some(arg);
+        } else if save_dir.is_none() && addr.is_none() {
             if looks_like_addr(&arg) {
                 addr = Some(arg);
             } else {
@@ -47,46 +76,93 @@ fn run() -> Result<(), String> {
         }
     }
 
-    let save_dir = save_dir.unwrap_or_else(default_save_dir);
-    let addr = addr
-        .or_else(|| env::var("AEGIS_CORE_ADDR").ok())
-        .unwrap_or_else(|| "127.0.0.1:7878".to_string());
-    let mut writer = SessionWriter::start_session(&save_dir, &platform, &app_version)
-        .map_err(|err| format!("start session: {err}"))?;
+    let addr = addr.unwrap_or_else(|| config.server.default_addr.clone());
+    // A bundle left unfinished by a crashed writer is continued in place;
+    // refused while another live process still writes it
+    let writer = match resume {
+        Some(bundle_dir) => SessionWriter::resume_session(&bundle_dir)
+            .map_err(|err| format!("resume session: {err}"))?,
+        None => {
+            let save_dir = save_dir.unwrap_or_else(|| config.save_dir());
+            SessionWriter::start_session(&save_dir, &platform, &app_version)
+                .map_err(|err| format!("start session: {err}"))?
+        }
+    };
 
     eprintln!(
         "Session started at {} (listening on {})",
         writer.session_dir().display(),
         addr
     );
+    let session_dir = writer.session_dir().to_path_buf();
 
     let listener =
         TcpListener::bind(&addr).map_err(|err| format!("bind {}: {err}", addr))?;
+    listener
+        .set_nonblocking(true)
+        .map_err(|err| format!("configure listener: {err}"))?;
 
-    loop {
-        let (stream, _) = listener
-            .accept()
-            .map_err(|err| format!("accept connection: {err}"))?;
-        let should_stop = handle_connection(stream, &mut writer)?;
-        if should_stop {
-            break;
+    // Collectors keep long-lived connections, so each one gets its own thread
+    let writer: SharedWriter = Arc::new(Mutex::new(Some(writer)));
+    let stopped = Arc::new(AtomicBool::new(false));
+    let heartbeats = Arc::new(Heartbeats::new(config.server.heartbeat_missed_limit));
+    spawn_watchdog(Arc::clone(&heartbeats), Arc::clone(&writer), Arc::clone(&stopped));
+    let state = Arc::new(ServerState {
+        session_dir,
+        writer,
+        heartbeats,
+        limits: SkewLimits::new(
+            config.server.observed_max_ahead_ms,
+            config.server.observed_max_behind_ms,
+        ),
+        delivered: Delivered::default(),
+        stopped,
+    });
+    while !state.stopped.load(Ordering::SeqCst) {
+        match listener.accept() {
+            Ok((stream, peer)) => {
+                stream
+                    .set_nonblocking(false)
+                    .map_err(|err| format!("configure connection: {err}"))?;
+                let state = Arc::clone(&state);
+                thread::spawn(move || {
+                    if let Err(err) = handle_connection(stream, peer, &state) {
+                        eprintln!("connection {peer}: {err}");
+                    }
+                });
+            }
+            Err(err) if err.kind() == io::ErrorKind::WouldBlock => {
+                thread::sleep(Duration::from_millis(50));
+            }
+            Err(err) => return Err(format!("accept connection: {err}")),
         }
     }
     Ok(())
 }
 
-fn looks_like_addr(arg: &str) -> bool {
-    arg.contains(':') && !arg.contains('/') && !arg.contains('\\')
+/// Records `collector_lost` once a collector's heartbeats stop; the next
+/// heartbeat from it records `collector_recovered`.
+fn spawn_watchdog(heartbeats: Arc<Heartbeats>, writer: SharedWriter, stopped: Arc<AtomicBool>) {
+    thread::spawn(move || {
+        while !stopped.load(Ordering::SeqCst) {
+            thread::sleep(Duration::from_millis(500));
+            for payload in heartbeats.check() {
+                let collector = payload["collector"].as_str().unwrap_or_default().to_string();
+                if let Err(err) = append_event(&writer, "collector_lost", payload, None) {
+                    if stopped.load(Ordering::SeqCst) {
+                        return;
+                    }
+                    // Try again on the next round rather than losing the event
+                    eprintln!("record collector_lost: {err}");
+                    heartbeats.unmark_lost(&collector);
+                }
+            }
+        }
+    });
 }
 
-fn default_save_dir() -> PathBuf {
-    let home = env::var_os("HOME")
-        .or_else(|| env::var_os("USERPROFILE"))
-        .map(PathBuf::from);
-    if let Some(home) = home {
-        return home.join("Downloads");
-    }
-    env::current_dir().unwrap_or_else(|_| PathBuf::from("."))
+fn looks_like_addr(arg: &str) -> bool {
+    arg.contains(':') && !arg.contains('/') && !arg.contains('\\')
 }
 
 fn parse_file_payload(payload: &Value) -> Result<(String, String, PathBuf), String> {
@@ -110,21 +186,19 @@ fn parse_file_payload(payload: &Value) -> Result<(String, String, PathBuf), Stri
     Ok((rel_path.to_string(), kind.to_string(), PathBuf::from(source_path)))
 }
 
-fn parse_shot_payload(payload: &Value) -> Result<(String, PathBuf), String> {
-    let rel_path = payload
-        .get("rel_path")
-        .and_then(|value| value.as_str())
-        .ok_or("shot_saved missing rel_path")?;
+/// `rel_path` is optional for shots: without one the server numbers them.
+fn parse_shot_payload(payload: &Value) -> Result<(Option<String>, PathBuf), String> {
+    let rel_path = payload.get("rel_path").and_then(|value| value.as_str());
     let source_path = payload
         .get("source_path")
         .and_then(|value| value.as_str())
         .ok_or("shot_saved missing source_path")?;
 
-    if !is_safe_rel_path(rel_path) {
+    if rel_path.is_some_and(|rel_path| !is_safe_rel_path(rel_path)) {
         return Err("shot_saved rel_path must be relative and not contain '..'".to_string());
     }
 
-    Ok((rel_path.to_string(), PathBuf::from(source_path)))
+    Ok((rel_path.map(str::to_string), PathBuf::from(source_path)))
 }
 
 fn is_safe_rel_path(path: &str) -> bool {
@@ -140,19 +214,120 @@ fn is_safe_rel_path(path: &str) -> bool {
     true
 }
 
+/// Files already in the bundle are evidence and never replaced. The same
+/// content again at `rel_path` is a resend of an upload that already
+/// landed, so it succeeds without copying.
 fn copy_into_bundle(session_dir: &Path, rel_path: &str, source_path: &Path) -> Result<(), String> {
-    if !source_path.exists() {
-        return Err(format!("source file missing: {}", source_path.display()));
-    }
+    let mut source = File::open(source_path)
+        .map_err(|err| format!("source file missing: {}: {err}", source_path.display()))?;
     let destination = session_dir.join(rel_path);
+    match copy_new(&mut source, &destination) {
+        Ok(()) => Ok(()),
+        Err(err) if err.kind() == ErrorKind::AlreadyExists => {
+            if same_content(&destination, source_path)? {
+                Ok(())
+            } else {
+                Err(format!("{rel_path} already exists in bundle"))
+            }
+        }
+        Err(err) => Err(format!("copy file: {err}")),
+    }
+}
+
+/// Copies a shot to the first free `files/shots/NNNNNN.<ext>`, skipping
+/// numbers already taken (e.g. by an earlier run of the collector).
+fn copy_next_shot(session_dir: &Path, source_path: &Path) -> Result<String, String> {
+    let extension = source_path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .ok_or("shot_saved source_path has no extension")?;
+    let mut source = File::open(source_path)
+        .map_err(|err| format!("source file missing: {}: {err}", source_path.display()))?;
+    let taken = fs::read_dir(session_dir.join("files/shots"))
+        .map(|entries| entries.count() as u64)
+        .unwrap_or(0);
+    let mut index = taken + 1;
+    loop {
+        let rel_path = format!("files/shots/{index:06}.{extension}");
+        match copy_new(&mut source, &session_dir.join(&rel_path)) {
+            Ok(()) => return Ok(rel_path),
+            Err(err) if err.kind() == ErrorKind::AlreadyExists => index += 1,
+            Err(err) => return Err(format!("copy file: {err}")),
+        }
+    }
+}
+
+/// Copies into a file that must not exist yet, so two writers can never
+/// both claim `destination`. A failed copy leaves nothing behind.
+fn copy_new(source: &mut File, destination: &Path) -> io::Result<()> {
     if let Some(parent) = destination.parent() {
-        fs::create_dir_all(parent).map_err(|err| format!("create dirs: {err}"))?;
+        fs::create_dir_all(parent)?;
+    }
+    let mut file = OpenOptions::new().write(true).create_new(true).open(destination)?;
+    if let Err(err) = io::copy(source, &mut file) {
+        drop(file);
+        let _ = fs::remove_file(destination);
+        return Err(err);
     }
-    fs::copy(source_path, destination).map_err(|err| format!("copy file: {err}"))?;
     Ok(())
 }
 
-fn handle_connection(mut stream: TcpStream, writer: &mut SessionWriter) -> Result<bool, String> {
+fn same_content(existing: &Path, source_path: &Path) -> Result<bool, String> {
+    let existing = fs::read(existing).map_err(|err| format!("read {}: {err}", existing.display()))?;
+    let source = fs::read(source_path).map_err(|err| format!("read {}: {err}", source_path.display()))?;
+    Ok(sha256_hex(&existing) == sha256_hex(&source))
+}
+
+/// The local source path only matters to the copy; other fields the
+/// collector attached (trigger, metadata, ...) are kept in the event.
+fn without_source_path(mut payload: Value) -> Value {
+    if let Some(map) = payload.as_object_mut() {
+        map.remove("source_path");
+    }
+    payload
+}
+
+fn append_event(
+    writer: &SharedWriter,
+    event_type: &str,
+    payload: Value,
+    observed_at: Option<String>,
+) -> Result<u64, String> {
+    let mut guard = writer.lock().map_err(|_| "writer lock poisoned")?;
+    let writer = guard.as_mut().ok_or("session already stopped")?;
+    writer
+        .append_observed_event(event_type, payload, observed_at)
+        .map_err(|err| format!("append event: {err}"))
+}
+
+/// What every connection works with.
+struct ServerState {
+    session_dir: PathBuf,
+    writer: SharedWriter,
+    heartbeats: Arc<Heartbeats>,
+    limits: SkewLimits,
+    delivered: Delivered,
+    stopped: Arc<AtomicBool>,
+}
+
+/// Outcome of one accepted message, and the reply it gets.
+#[derive(Clone, Copy)]
+enum Handled {
+    /// `OK <seq>`: the message became event `seq`
+    Appended(u64),
+    /// `OK`: accepted without a new event (repeated heartbeat)
+    Accepted,
+    /// `OK`, then the connection closes: the session was finalized
+    Stopped,
+}
+
+/// Replies to every line in order, so clients may pipeline messages. A
+/// rejected message gets `ERR <reason>` and the connection stays open.
+fn handle_connection(
+    mut stream: TcpStream,
+    peer: SocketAddr,
+    state: &ServerState,
+) -> Result<(), String> {
     let reader = BufReader::new(stream.try_clone().map_err(|err| err.to_string())?);
 
     for (index, line) in reader.lines().enumerate() {
@@ -160,58 +335,104 @@ fn handle_connection(mut stream: TcpStream, writer: &mut SessionWriter) -> Resul
         if line.trim().is_empty() {
             continue;
         }
-        let msg: IncomingMessage = serde_json::from_str(&line)
-            .map_err(|err| format!("parse message {}: {err}", index + 1))?;
-
-        if msg.message_type == "stop" {
-            let reason = msg
-                .payload
-                .get("reason")
-                .and_then(|value| value.as_str())
-                .unwrap_or("unknown");
-            writer
-                .stop_session(reason)
-                .map_err(|err| format!("stop session: {err}"))?;
-            stream
-                .write_all(b"OK\n")
-                .map_err(|err| format!("write response: {err}"))?;
-            return Ok(true);
+        let (reply, stop) = match handle_once(&line, index, state) {
+            Ok(Handled::Appended(seq)) => (format!("OK {seq}\n"), false),
+            Ok(Handled::Accepted) => ("OK\n".to_string(), false),
+            Ok(Handled::Stopped) => ("OK\n".to_string(), true),
+            Err(err) => {
+                eprintln!("connection {peer}: {err}");
+                (format!("ERR {err}\n"), false)
+            }
+        };
+        stream
+            .write_all(reply.as_bytes())
+            .map_err(|err| format!("write response: {err}"))?;
+        if stop {
+            state.stopped.store(true, Ordering::SeqCst);
+            return Ok(());
         }
+    }
 
-        if msg.message_type == "file_added" {
-            let (rel_path, kind, source_path) = parse_file_payload(&msg.payload)?;
-            copy_into_bundle(writer.session_dir(), &rel_path, &source_path)?;
-            writer
-                .append_event(
-                    "file_added",
-                    serde_json::json!({ "rel_path": rel_path, "kind": kind }),
-                )
-                .map_err(|err| format!("append event: {err}"))?;
-            stream
-                .write_all(b"OK\n")
-                .map_err(|err| format!("write response: {err}"))?;
-            continue;
-        }
+    Ok(())
+}
 
-        if msg.message_type == "shot_saved" {
-            let (rel_path, source_path) = parse_shot_payload(&msg.payload)?;
-            copy_into_bundle(writer.session_dir(), &rel_path, &source_path)?;
-            writer
-                .append_event("shot_saved", serde_json::json!({ "rel_path": rel_path }))
-                .map_err(|err| format!("append event: {err}"))?;
-            stream
-                .write_all(b"OK\n")
-                .map_err(|err| format!("write response: {err}"))?;
-            continue;
+/// Applies a message unless its `msg_id` was already applied, in which
+/// case the first reply is repeated: a client resends a message whose
+/// reply it did not get, which may have been applied all the same.
+fn handle_once(line: &str, index: usize, state: &ServerState) -> Result<Handled, String> {
+    let msg: IncomingMessage = serde_json::from_str(line)
+        .map_err(|err| format!("parse message {}: {err}", index + 1))?;
+    let Some(msg_id) = msg.msg_id.clone() else {
+        return handle_message(msg, state);
+    };
+    match state.delivered.begin(&msg_id)? {
+        Begin::Repeat(handled) => Ok(handled),
+        Begin::New => {
+            let result = handle_message(msg, state);
+            state.delivered.finish(&msg_id, result.as_ref().ok().copied());
+            result
         }
+    }
+}
 
-        writer
-            .append_event(&msg.message_type, msg.payload)
-            .map_err(|err| format!("append event: {err}"))?;
-        stream
-            .write_all(b"OK\n")
-            .map_err(|err| format!("write response: {err}"))?;
+/// Applies one message to the bundle.
+fn handle_message(msg: IncomingMessage, state: &ServerState) -> Result<Handled, String> {
+    let ServerState {
+        session_dir,
+        writer,
+        heartbeats,
+        limits,
+        ..
+    } = state;
+    // Checked before anything is copied into the bundle
+    let observed_at = match msg.observed_at.as_deref() {
+        Some(observed_at) if msg.message_type != "stop" => Some(limits.check(observed_at)?),
+        _ => None,
+    };
+
+    if msg.message_type == "stop" {
+        let reason = msg
+            .payload
+            .get("reason")
+            .and_then(|value| value.as_str())
+            .unwrap_or("unknown");
+        let mut guard = writer.lock().map_err(|_| "writer lock poisoned")?;
+        let mut active = guard.take().ok_or("session already stopped")?;
+        active
+            .stop_session(reason)
+            .map_err(|err| format!("stop session: {err}"))?;
+        return Ok(Handled::Stopped);
+    }
+
+    if msg.message_type == "heartbeat" {
+        return match heartbeats.beat(&msg.payload)? {
+            Some((event_type, payload)) => {
+                append_event(writer, event_type, payload, observed_at).map(Handled::Appended)
+            }
+            None => Ok(Handled::Accepted),
+        };
+    }
+
+    if msg.message_type == "file_added" {
+        let (rel_path, _kind, source_path) = parse_file_payload(&msg.payload)?;
+        copy_into_bundle(session_dir, &rel_path, &source_path)?;
+        return append_event(writer, "file_added", without_source_path(msg.payload), observed_at)
+            .map(Handled::Appended);
+    }
+
+    if msg.message_type == "shot_saved" {
+        let (rel_path, source_path) = parse_shot_payload(&msg.payload)?;
+        let rel_path = match rel_path {
+            Some(rel_path) => {
+                copy_into_bundle(session_dir, &rel_path, &source_path)?;
+                rel_path
+            }
+            None => copy_next_shot(session_dir, &source_path)?,
+        };
+        let mut payload = without_source_path(msg.payload);
+        payload["rel_path"] = Value::String(rel_path);
+        return append_event(writer, "shot_saved", payload, observed_at).map(Handled::Appended);
     }
 
-    Ok(false)
+    append_event(writer, &msg.message_type, msg.payload, observed_at).map(Handled::Appended)
 }
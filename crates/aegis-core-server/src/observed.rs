@@ -0,0 +1,39 @@
+use chrono::{DateTime, SecondsFormat, Utc};
+
+/// How far a collector's `observed_at` may be from the server clock.
+pub struct SkewLimits {
+    max_ahead_ms: i64,
+    /// 0: no limit
+    max_behind_ms: i64,
+}
+
+impl SkewLimits {
+    pub fn new(max_ahead_ms: u64, max_behind_ms: u64) -> Self {
+        Self {
+            max_ahead_ms: i64::try_from(max_ahead_ms).unwrap_or(i64::MAX),
+            max_behind_ms: i64::try_from(max_behind_ms).unwrap_or(i64::MAX),
+        }
+    }
+
+    /// Checks an RFC 3339 `observed_at` against the limits and returns it
+    /// in UTC with millisecond precision, as stored in the event.
+    pub fn check(&self, observed_at: &str) -> Result<String, String> {
+        let observed = DateTime::parse_from_rfc3339(observed_at)
+            .map_err(|_| format!("invalid observed_at: {observed_at}"))?
+            .with_timezone(&Utc);
+        let lag_ms = (Utc::now() - observed).num_milliseconds();
+        if -lag_ms > self.max_ahead_ms {
+            return Err(format!(
+                "observed_at {observed_at} is {}ms ahead of the server clock (limit {}ms)",
+                -lag_ms, self.max_ahead_ms
+            ));
+        }
+        if self.max_behind_ms > 0 && lag_ms > self.max_behind_ms {
+            return Err(format!(
+                "observed_at {observed_at} is {lag_ms}ms behind the server clock (limit {}ms)",
+                self.max_behind_ms
+            ));
+        }
+        Ok(observed.to_rfc3339_opts(SecondsFormat::Millis, true))
+    }
+}
@@ -0,0 +1,155 @@
+use crate::hash::{canonical_json, sha256_hex};
+use chrono::{DateTime, Utc};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::value::RawValue;
+use serde_json::Value;
+
+pub const SESSION_FILE: &str = "session.json";
+pub const EVENTS_FILE: &str = "events.jsonl";
+pub const MANIFEST_FILE: &str = "manifest.json";
+
+/// `schema_version` of bundles written by this version. It selects how
+/// event hashes are computed: 1 for the original `serde_json` based
+/// canonical JSON, 2 for RFC 8785 (JCS).
+pub const SCHEMA_VERSION: u32 = 2;
+
+/// `session.json`, written when the session stops.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Session {
+    /// Absent from bundles written before session IDs existed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session_id: Option<String>,
+    pub started_at: DateTime<Utc>,
+    pub ended_at: Option<DateTime<Utc>>,
+    pub platform: String,
+    pub app_version: String,
+    pub bundle_dir: String,
+}
+
+/// `manifest.json`, written last when the session stops.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Manifest {
+    pub schema_version: u32,
+    pub events_hash: String,
+    pub final_hash: String,
+    pub files: Vec<ManifestFile>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ManifestFile {
+    pub rel_path: String,
+    pub hash: String,
+}
+
+/// One line of `events.jsonl`. The payload stays unparsed until asked
+/// for, so scanning a long log for a few event types stays cheap.
+#[derive(Deserialize, Debug)]
+pub struct Event {
+    #[serde(default)]
+    pub session_id: Option<String>,
+    pub seq: u64,
+    pub ts: String,
+    #[serde(default)]
+    pub mono_us: Option<u64>,
+    #[serde(default)]
+    pub observed_at: Option<String>,
+    #[serde(rename = "type")]
+    pub event_type: String,
+    payload: Box<RawValue>,
+    pub prev_hash: String,
+    pub hash: String,
+    /// Line number in `events.jsonl`, for error messages
+    #[serde(skip)]
+    line: usize,
+}
+
+impl Event {
+    pub(crate) fn parse(line: &str, line_no: usize) -> Result<Self, String> {
+        let mut event: Event = serde_json::from_str(line)
+            .map_err(|err| format!("parse events line {line_no}: {err}"))?;
+        event.line = line_no;
+        Ok(event)
+    }
+
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    /// The payload exactly as written.
+    pub fn payload_raw(&self) -> &str {
+        self.payload.get()
+    }
+
+    pub fn payload(&self) -> Result<Value, String> {
+        self.payload_as()
+    }
+
+    /// The payload as a typed struct.
+    pub fn payload_as<T: DeserializeOwned>(&self) -> Result<T, String> {
+        serde_json::from_str(self.payload.get())
+            .map_err(|err| format!("{} payload at line {}: {err}", self.event_type, self.line))
+    }
+
+    pub fn timestamp(&self) -> Result<DateTime<Utc>, String> {
+        parse_utc(&self.ts).ok_or_else(|| format!("invalid ts at line {}", self.line))
+    }
+
+    pub fn observed_timestamp(&self) -> Result<Option<DateTime<Utc>>, String> {
+        self.observed_at
+            .as_deref()
+            .map(|observed_at| {
+                parse_utc(observed_at)
+                    .ok_or_else(|| format!("invalid observed_at at line {}", self.line))
+            })
+            .transpose()
+    }
+
+    /// The hash this event should carry given its fields, in a bundle of
+    /// `schema_version`.
+    pub fn expected_hash(&self, schema_version: u32) -> Result<String, String> {
+        let payload = self.payload()?;
+        HashInput {
+            session_id: self.session_id.as_deref(),
+            seq: self.seq,
+            ts: &self.ts,
+            mono_us: self.mono_us,
+            observed_at: self.observed_at.as_deref(),
+            event_type: &self.event_type,
+            payload: &payload,
+            prev_hash: &self.prev_hash,
+        }
+        .hash(schema_version)
+    }
+}
+
+/// The fields an event hash covers. Optional fields are only hashed when
+/// present, so events written before they existed keep their hash.
+#[derive(Serialize)]
+pub(crate) struct HashInput<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub session_id: Option<&'a str>,
+    pub seq: u64,
+    pub ts: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mono_us: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub observed_at: Option<&'a str>,
+    #[serde(rename = "type")]
+    pub event_type: &'a str,
+    pub payload: &'a Value,
+    pub prev_hash: &'a str,
+}
+
+impl HashInput<'_> {
+    pub fn hash(&self, schema_version: u32) -> Result<String, String> {
+        let value = serde_json::to_value(self).unwrap_or_default();
+        canonical_json(schema_version, &value).map(|json| sha256_hex(json.as_bytes()))
+    }
+}
+
+fn parse_utc(value: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(value)
+        .ok()
+        .map(|value| value.with_timezone(&Utc))
+}
@@ -0,0 +1,173 @@
+//! Canonical JSON and SHA-256 as used for event hashes and the manifest.
+//! The writer and every reader of bundles must go through these functions:
+//! a bundle only verifies if both sides serialize the hash input byte for
+//! byte the same way. The expected output for bundles of every schema
+//! version is pinned by the golden bundles under `spec/golden/`.
+//!
+//! Schema version 1 hashes [`canonical_json_string`], which is tied to
+//! `serde_json` formatting; version 2 hashes [`jcs_string`] (RFC 8785), which
+//! verifiers in other languages can reproduce.
+
+use serde_json::{Map, Value};
+use sha2::{Digest, Sha256};
+use std::fmt::Write;
+
+/// Canonical JSON of `value` as hashed in bundles of `schema_version`.
+pub fn canonical_json(schema_version: u32, value: &Value) -> Result<String, String> {
+    match schema_version {
+        1 => Ok(canonical_json_string(value)),
+        2 => Ok(jcs_string(value)),
+        other => Err(format!("unsupported schema_version {other}")),
+    }
+}
+
+/// Copy of `value` with the keys of every object sorted.
+pub fn canonicalize_value(value: &Value) -> Value {
+    match value {
+        Value::Array(items) => {
+            let mapped: Vec<Value> = items.iter().map(canonicalize_value).collect();
+            Value::Array(mapped)
+        }
+        Value::Object(map) => {
+            let mut keys: Vec<&String> = map.keys().collect();
+            keys.sort();
+            let mut new_map = Map::new();
+            for key in keys {
+                if let Some(value) = map.get(key) {
+                    new_map.insert(key.clone(), canonicalize_value(value));
+                }
+            }
+            Value::Object(new_map)
+        }
+        other => other.clone(),
+    }
+}
+
+/// Compact `serde_json` output of the canonicalized value: sorted keys, no
+/// whitespace, numbers as `serde_json` formats them.
+pub fn canonical_json_string(value: &Value) -> String {
+    let canonical = canonicalize_value(value);
+    serde_json::to_string(&canonical).unwrap_or_default()
+}
+
+/// RFC 8785 JSON Canonicalization Scheme: object keys sorted by UTF-16
+/// code units, numbers formatted like ECMAScript `Number.prototype.toString`,
+/// strings escaped like `JSON.stringify`. Numbers are IEEE 754 doubles, so
+/// integers beyond 2^53 lose precision, as in any JCS implementation.
+pub fn jcs_string(value: &Value) -> String {
+    let mut out = String::new();
+    write_jcs(&mut out, value);
+    out
+}
+
+fn write_jcs(out: &mut String, value: &Value) {
+    match value {
+        Value::Null => out.push_str("null"),
+        Value::Bool(value) => out.push_str(if *value { "true" } else { "false" }),
+        Value::Number(number) => out.push_str(&jcs_number(number.as_f64().unwrap_or_default())),
+        Value::String(value) => write_jcs_string(out, value),
+        Value::Array(items) => {
+            out.push('[');
+            for (index, item) in items.iter().enumerate() {
+                if index > 0 {
+                    out.push(',');
+                }
+                write_jcs(out, item);
+            }
+            out.push(']');
+        }
+        Value::Object(map) => {
+            let mut entries: Vec<(&String, &Value)> = map.iter().collect();
+            entries.sort_by(|(a, _), (b, _)| a.encode_utf16().cmp(b.encode_utf16()));
+            out.push('{');
+            for (index, (key, value)) in entries.into_iter().enumerate() {
+                if index > 0 {
+                    out.push(',');
+                }
+                write_jcs_string(out, key);
+                out.push(':');
+                write_jcs(out, value);
+            }
+            out.push('}');
+        }
+    }
+}
+
+fn write_jcs_string(out: &mut String, value: &str) {
+    out.push('"');
+    for ch in value.chars() {
+        match ch {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\u{8}' => out.push_str("\\b"),
+            '\u{c}' => out.push_str("\\f"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            ch if ch < ' ' => {
+                let _ = write!(out, "\\u{:04x}", ch as u32);
+            }
+            ch => out.push(ch),
+        }
+    }
+    out.push('"');
+}
+
+/// ECMAScript `Number.prototype.toString` for finite doubles (RFC 8785
+/// section 3.2.2.3). JSON cannot carry NaN or infinities.
+fn jcs_number(value: f64) -> String {
+    if value == 0.0 {
+        return "0".to_string();
+    }
+    if value < 0.0 {
+        return format!("-{}", jcs_number(-value));
+    }
+    // Shortest round-trip digit count, then the digits of that length
+    // closest to the value. The shortest form alone may pick the wrong one
+    // when two are equally close; exact formatting rounds half to even, as
+    // ECMAScript requires.
+    let shortest = format!("{value:e}");
+    let precision = shortest
+        .split_once('e')
+        .map_or(0, |(mantissa, _)| mantissa.len().saturating_sub(2));
+    let scientific = format!("{value:.precision$e}");
+    let (mantissa, exponent) = scientific.split_once('e').unwrap_or((&scientific, "0"));
+    let digits: String = mantissa.chars().filter(|ch| *ch != '.').collect();
+    let digits = digits.trim_end_matches('0');
+    let k = digits.len() as i32;
+    // Position of the decimal point relative to the digits
+    let n = exponent.parse::<i32>().unwrap_or_default() + 1;
+    if k <= n && n <= 21 {
+        format!("{digits}{}", "0".repeat((n - k) as usize))
+    } else if 0 < n && n <= 21 {
+        let (int, frac) = digits.split_at(n as usize);
+        format!("{int}.{frac}")
+    } else if -6 < n && n <= 0 {
+        format!("0.{}{digits}", "0".repeat(-n as usize))
+    } else {
+        let sign = if n - 1 < 0 { '-' } else { '+' };
+        let (first, rest) = digits.split_at(1);
+        let fraction = if rest.is_empty() {
+            String::new()
+        } else {
+            format!(".{rest}")
+        };
+        format!("{first}{fraction}e{sign}{}", (n - 1).abs())
+    }
+}
+
+pub fn sha256_hex(bytes: &[u8]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(bytes);
+    let digest = hasher.finalize();
+    bytes_to_hex(&digest)
+}
+
+/// Lowercase hex, as hashes appear in bundles.
+pub fn bytes_to_hex(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len() * 2);
+    for byte in bytes {
+        out.push_str(&format!("{:02x}", byte));
+    }
+    out
+}
@@ -1,15 +1,42 @@
+mod bundle;
+mod hash;
+mod lock;
+mod reader;
+
+pub use bundle::{
+    Event, Manifest, ManifestFile, Session, EVENTS_FILE, MANIFEST_FILE, SCHEMA_VERSION,
+    SESSION_FILE,
+};
+pub use hash::{
+    bytes_to_hex, canonical_json, canonical_json_string, canonicalize_value, jcs_string, sha256_hex,
+};
+pub use lock::{hostname, lock_state, remove_stale, BundleLock, LockInfo, LockState, LOCK_FILE};
+pub use reader::{BundleReader, Chain, Events};
+
+use bundle::HashInput;
+
 use chrono::{DateTime, SecondsFormat, Utc};
 use serde::Serialize;
-use serde_json::{Map, Value};
+use serde_json::Value;
 use sha2::{Digest, Sha256};
 use std::fs::{self, File, OpenOptions};
 use std::io::{self, BufWriter, Write};
 use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+/// How far wall-clock time may move against the monotonic clock between
+/// two events before a `clock_jump` event is recorded.
+const CLOCK_JUMP_THRESHOLD_US: i64 = 1_000_000;
 
 #[derive(Serialize)]
 struct EventRecord {
+    session_id: String,
     seq: u64,
     ts: String,
+    mono_us: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    observed_at: Option<String>,
     #[serde(rename = "type")]
     event_type: String,
     payload: Value,
@@ -17,36 +44,25 @@ struct EventRecord {
     hash: String,
 }
 
-#[derive(Serialize)]
-struct SessionRecord {
-    started_at: DateTime<Utc>,
-    ended_at: Option<DateTime<Utc>>,
-    platform: String,
-    app_version: String,
-    bundle_dir: String,
-}
-
-#[derive(Serialize)]
-struct ManifestRecord {
-    schema_version: u32,
-    events_hash: String,
-    final_hash: String,
-    files: Vec<ManifestFile>,
-}
-
-#[derive(Serialize)]
-struct ManifestFile {
-    rel_path: String,
-    hash: String,
-}
-
 pub struct SessionWriter {
+    session_id: String,
     session_dir: PathBuf,
+    /// Held until the session is stopped or the writer dropped
+    lock: Option<BundleLock>,
     events_writer: BufWriter<File>,
     events_hasher: Sha256,
     last_hash: Option<String>,
     seq: u64,
     started_at: DateTime<Utc>,
+    /// Monotonic time at `started_at`; `mono_us` counts from here
+    started: Instant,
+    /// `mono_us` at `started`: zero for a new session, the last event's
+    /// for a resumed one
+    mono_base: Duration,
+    /// Wall-clock minus monotonic time elapsed since the start, as of the
+    /// last `clock_jump` (0 before any)
+    clock_skew_us: i64,
+    last_ts: DateTime<Utc>,
     save_dir: PathBuf,
     platform: String,
     app_version: String,
@@ -59,24 +75,40 @@ impl SessionWriter {
         app_version: &str,
     ) -> io::Result<Self> {
         let started_at = Utc::now();
+        let started = Instant::now();
         let save_dir = save_dir.as_ref().to_path_buf();
-        let session_dir = save_dir
-            .join(format!("Evidence_{}", started_at.format("%Y%m%d_%H%M%S")));
-        fs::create_dir_all(session_dir.join("files"))?;
+        let session_dir = create_session_dir(&save_dir, &started_at)?;
+        let session_id = Uuid::new_v4().to_string();
+        let lock = BundleLock::acquire(
+            &session_dir,
+            &LockInfo {
+                pid: std::process::id(),
+                host: hostname(),
+                session_id: session_id.clone(),
+                acquired_at: started_at.to_rfc3339_opts(SecondsFormat::Micros, true),
+            },
+        )?;
+        fs::create_dir(session_dir.join("files"))?;
 
-        let events_path = session_dir.join("events.jsonl");
+        let events_path = session_dir.join(EVENTS_FILE);
         let events_file = OpenOptions::new()
-            .create(true)
+            .create_new(true)
             .append(true)
             .open(events_path)?;
 
         let mut writer = Self {
+            session_id,
             session_dir,
+            lock: Some(lock),
             events_writer: BufWriter::new(events_file),
             events_hasher: Sha256::new(),
             last_hash: None,
             seq: 1,
             started_at,
+            started,
+            mono_base: Duration::ZERO,
+            clock_skew_us: 0,
+            last_ts: started_at,
             save_dir,
             platform: platform.to_string(),
             app_version: app_version.to_string(),
@@ -88,29 +120,196 @@ impl SessionWriter {
                 "save_dir": writer.save_dir.to_string_lossy(),
                 "platform": writer.platform.clone(),
                 "app_version": writer.app_version.clone(),
+                "schema_version": SCHEMA_VERSION,
             }),
         )?;
 
         Ok(writer)
     }
 
-    pub fn append_event(&mut self, event_type: &str, payload: Value) -> io::Result<()> {
-        let ts = Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true);
+    /// Reopens the bundle of a session that did not finish, typically after
+    /// its writer crashed, and continues its hash chain. Fails with
+    /// `ErrorKind::WouldBlock` while a live process holds the bundle's
+    /// lock; a stale lock is replaced. The chain must verify and
+    /// `events.jsonl` must end in a complete line. Records
+    /// `session_resumed`, after a `clock_jump` covering the time the
+    /// session was not written.
+    pub fn resume_session(session_dir: impl AsRef<Path>) -> io::Result<Self> {
+        let session_dir = session_dir.as_ref().to_path_buf();
+        let reader = BundleReader::open(&session_dir).map_err(io::Error::other)?;
+        if session_dir.join(MANIFEST_FILE).exists() {
+            return Err(io::Error::other(format!(
+                "{} is already finished",
+                session_dir.display()
+            )));
+        }
+        let first = reader
+            .events()
+            .map_err(io::Error::other)?
+            .next()
+            .transpose()
+            .map_err(io::Error::other)?
+            .filter(|event| event.event_type == "session_started")
+            .ok_or_else(|| io::Error::other("events.jsonl does not start with session_started"))?;
+        let session_id = first
+            .session_id
+            .clone()
+            .ok_or_else(|| io::Error::other("session_started has no session_id"))?;
+
+        let resumed_at = Utc::now();
+        let started = Instant::now();
+        let lock = BundleLock::acquire(
+            &session_dir,
+            &LockInfo {
+                pid: std::process::id(),
+                host: hostname(),
+                session_id: session_id.clone(),
+                acquired_at: resumed_at.to_rfc3339_opts(SecondsFormat::Micros, true),
+            },
+        )?;
+
+        // Read under the lock, so no other writer appends in between
+        let schema_version = reader.schema_version().map_err(io::Error::other)?;
+        if schema_version != SCHEMA_VERSION {
+            return Err(io::Error::other(format!(
+                "cannot resume a schema_version {schema_version} bundle"
+            )));
+        }
+        let chain = reader.verify_chain().map_err(io::Error::other)?;
+        let last = reader
+            .events()
+            .map_err(io::Error::other)?
+            .last()
+            .transpose()
+            .map_err(io::Error::other)?
+            .ok_or_else(|| io::Error::other("events.jsonl is empty"))?;
+        if last.event_type == "session_stopped" {
+            return Err(io::Error::other(
+                "session was stopped but never finalized; nothing to resume",
+            ));
+        }
+        let events_path = session_dir.join(EVENTS_FILE);
+        let events = fs::read(&events_path)?;
+        if !events.ends_with(b"\n") {
+            return Err(io::Error::other("events.jsonl ends in a partial line"));
+        }
+
+        let payload = first.payload().map_err(io::Error::other)?;
+        let started_at = first.timestamp().map_err(io::Error::other)?;
+        let last_ts = last.timestamp().map_err(io::Error::other)?;
+        let last_mono_us = last.mono_us.unwrap_or_default();
+        let mut events_hasher = Sha256::new();
+        events_hasher.update(&events);
+        let events_file = OpenOptions::new().append(true).open(events_path)?;
+
+        let mut writer = Self {
+            session_id,
+            save_dir: session_dir.parent().unwrap_or(Path::new("")).to_path_buf(),
+            session_dir,
+            lock: Some(lock),
+            events_writer: BufWriter::new(events_file),
+            events_hasher,
+            last_hash: Some(chain.last_hash),
+            seq: chain.events + 1,
+            started_at,
+            started,
+            mono_base: Duration::from_micros(last_mono_us),
+            clock_skew_us: (last_ts - started_at)
+                .num_microseconds()
+                .unwrap_or(i64::MAX)
+                .saturating_sub(i64::try_from(last_mono_us).unwrap_or(i64::MAX)),
+            last_ts,
+            platform: payload["platform"].as_str().unwrap_or_default().to_string(),
+            app_version: payload["app_version"].as_str().unwrap_or_default().to_string(),
+        };
+
+        writer.append_event(
+            "session_resumed",
+            serde_json::json!({ "last_seq": chain.events }),
+        )?;
+
+        Ok(writer)
+    }
+
+    /// Appends one event to the hash chain and returns its `seq`.
+    pub fn append_event(&mut self, event_type: &str, payload: Value) -> io::Result<u64> {
+        self.append_observed_event(event_type, payload, None)
+    }
+
+    /// Like [`append_event`](Self::append_event), also recording when the
+    /// collector observed the event. `ts` stays the time of writing.
+    pub fn append_observed_event(
+        &mut self,
+        event_type: &str,
+        payload: Value,
+        observed_at: Option<String>,
+    ) -> io::Result<u64> {
+        let now = Utc::now();
+        let mono = self.mono_base + self.started.elapsed();
+        self.check_clock(now, mono)?;
+        self.write_event(event_type, payload, observed_at, now, mono)
+    }
+
+    /// Records `clock_jump` when wall-clock time moved against monotonic
+    /// time by more than the threshold (NTP step, manual change, system
+    /// suspend) or went backwards at all, so that `ts` only ever decreases
+    /// at a `clock_jump`.
+    fn check_clock(&mut self, now: DateTime<Utc>, mono: Duration) -> io::Result<()> {
+        let wall_us = (now - self.started_at).num_microseconds().unwrap_or(i64::MAX);
+        let mono_us = i64::try_from(mono.as_micros()).unwrap_or(i64::MAX);
+        let skew_us = wall_us.saturating_sub(mono_us);
+        let jump_us = skew_us - self.clock_skew_us;
+        if jump_us.abs() <= CLOCK_JUMP_THRESHOLD_US && now >= self.last_ts {
+            return Ok(());
+        }
+        self.clock_skew_us = skew_us;
+        self.write_event(
+            "clock_jump",
+            serde_json::json!({
+                "jump_ms": jump_us / 1000,
+                "skew_ms": skew_us / 1000,
+                "previous_ts": self.last_ts.to_rfc3339_opts(SecondsFormat::Micros, true),
+            }),
+            None,
+            now,
+            mono,
+        )
+        .map(|_| ())
+    }
+
+    fn write_event(
+        &mut self,
+        event_type: &str,
+        payload: Value,
+        observed_at: Option<String>,
+        now: DateTime<Utc>,
+        mono: Duration,
+    ) -> io::Result<u64> {
+        let ts = now.to_rfc3339_opts(SecondsFormat::Micros, true);
+        let mono_us = u64::try_from(mono.as_micros()).unwrap_or(u64::MAX);
         let payload = canonicalize_value(&payload);
         let prev_hash = self.last_hash.clone().unwrap_or_default();
 
-        let hash_input = serde_json::json!({
-            "seq": self.seq,
-            "ts": ts,
-            "type": event_type,
-            "payload": payload,
-            "prev_hash": prev_hash,
-        });
-        let hash = sha256_hex(canonical_json_string(&hash_input).as_bytes());
+        let hash = HashInput {
+            session_id: Some(&self.session_id),
+            seq: self.seq,
+            ts: &ts,
+            mono_us: Some(mono_us),
+            observed_at: observed_at.as_deref(),
+            event_type,
+            payload: &payload,
+            prev_hash: &prev_hash,
+        }
+        .hash(SCHEMA_VERSION)
+        .map_err(io::Error::other)?;
 
+        let seq = self.seq;
         let record = EventRecord {
-            seq: self.seq,
+            session_id: self.session_id.clone(),
+            seq,
             ts,
+            mono_us,
+            observed_at,
             event_type: event_type.to_string(),
             payload,
             prev_hash,
@@ -126,7 +325,8 @@ impl SessionWriter {
         self.events_hasher.update(line.as_bytes());
         self.events_hasher.update(b"\n");
         self.last_hash = Some(hash);
-        Ok(())
+        self.last_ts = now;
+        Ok(seq)
     }
 
     pub fn stop_session(&mut self, reason: &str) -> io::Result<()> {
@@ -135,7 +335,8 @@ impl SessionWriter {
             serde_json::json!({ "reason": reason }),
         )?;
 
-        let session_record = SessionRecord {
+        let session_record = Session {
+            session_id: Some(self.session_id.clone()),
             started_at: self.started_at,
             ended_at: Some(Utc::now()),
             platform: self.platform.clone(),
@@ -147,7 +348,7 @@ impl SessionWriter {
                 .to_string_lossy()
                 .to_string(),
         };
-        let session_path = self.session_dir.join("session.json");
+        let session_path = self.session_dir.join(SESSION_FILE);
         let mut session_file = File::create(&session_path)?;
         serde_json::to_writer_pretty(&mut session_file, &session_record)?;
         session_file.write_all(b"\n")?;
@@ -156,16 +357,16 @@ impl SessionWriter {
         let final_hash = self
             .last_hash
             .clone()
-            .unwrap_or_else(|| "".to_string());
+            .unwrap_or_default();
 
         let mut files = Vec::new();
         files.push(ManifestFile {
-            rel_path: "session.json".to_string(),
+            rel_path: SESSION_FILE.to_string(),
             hash: sha256_hex(&fs::read(&session_path)?),
         });
-        let events_path = self.session_dir.join("events.jsonl");
+        let events_path = self.session_dir.join(EVENTS_FILE);
         files.push(ManifestFile {
-            rel_path: "events.jsonl".to_string(),
+            rel_path: EVENTS_FILE.to_string(),
             hash: sha256_hex(&fs::read(&events_path)?),
         });
 
@@ -181,22 +382,49 @@ impl SessionWriter {
         }
         files.sort_by(|a, b| a.rel_path.cmp(&b.rel_path));
 
-        let manifest = ManifestRecord {
-            schema_version: 1,
+        let manifest = Manifest {
+            schema_version: SCHEMA_VERSION,
             events_hash,
             final_hash,
             files,
         };
-        let manifest_path = self.session_dir.join("manifest.json");
+        let manifest_path = self.session_dir.join(MANIFEST_FILE);
         let mut manifest_file = File::create(manifest_path)?;
         serde_json::to_writer_pretty(&mut manifest_file, &manifest)?;
         manifest_file.write_all(b"\n")?;
-        Ok(())
+        match self.lock.take() {
+            Some(lock) => lock.release(),
+            None => Ok(()),
+        }
     }
 
     pub fn session_dir(&self) -> &Path {
         &self.session_dir
     }
+
+    pub fn session_id(&self) -> &str {
+        &self.session_id
+    }
+}
+
+/// Creates `Evidence_<start time>` exclusively. Sessions started within
+/// the same second (a quick stop/start, two servers sharing a save dir)
+/// get `_2`, `_3`, ... instead of writing into each other's bundle.
+fn create_session_dir(save_dir: &Path, started_at: &DateTime<Utc>) -> io::Result<PathBuf> {
+    fs::create_dir_all(save_dir)?;
+    let base = format!("Evidence_{}", started_at.format("%Y%m%d_%H%M%S"));
+    let mut session_dir = save_dir.join(&base);
+    let mut attempt = 1;
+    loop {
+        match fs::create_dir(&session_dir) {
+            Ok(()) => return Ok(session_dir),
+            Err(err) if err.kind() == io::ErrorKind::AlreadyExists => {
+                attempt += 1;
+                session_dir = save_dir.join(format!("{base}_{attempt}"));
+            }
+            Err(err) => return Err(err),
+        }
+    }
 }
 
 fn collect_files(dir: &Path, base: &Path, out: &mut Vec<PathBuf>) -> io::Result<()> {
@@ -215,49 +443,8 @@ fn collect_files(dir: &Path, base: &Path, out: &mut Vec<PathBuf>) -> io::Result<
     Ok(())
 }
 
-fn canonicalize_value(value: &Value) -> Value {
-    match value {
-        Value::Array(items) => {
-            let mapped: Vec<Value> = items.iter().map(canonicalize_value).collect();
-            Value::Array(mapped)
-        }
-        Value::Object(map) => {
-            let mut keys: Vec<&String> = map.keys().collect();
-            keys.sort();
-            let mut new_map = Map::new();
-            for key in keys {
-                if let Some(value) = map.get(key) {
-                    new_map.insert(key.clone(), canonicalize_value(value));
-                }
-            }
-            Value::Object(new_map)
-        }
-        other => other.clone(),
-    }
-}
-
-fn canonical_json_string(value: &Value) -> String {
-    let canonical = canonicalize_value(value);
-    serde_json::to_string(&canonical).unwrap_or_default()
-}
-
 fn finalize_hasher(hasher: &Sha256) -> String {
     let clone = hasher.clone();
     let digest = clone.finalize();
     bytes_to_hex(&digest)
 }
-
-fn sha256_hex(bytes: &[u8]) -> String {
-    let mut hasher = Sha256::new();
-    hasher.update(bytes);
-    let digest = hasher.finalize();
-    bytes_to_hex(&digest)
-}
-
-fn bytes_to_hex(bytes: &[u8]) -> String {
-    let mut out = String::with_capacity(bytes.len() * 2);
-    for byte in bytes {
-        out.push_str(&format!("{:02x}", byte));
-    }
-    out
-}
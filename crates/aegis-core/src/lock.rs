@@ -0,0 +1,212 @@
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::{self, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+
+/// Name of the lock file inside a bundle directory.
+pub const LOCK_FILE: &str = ".aegis.lock";
+
+/// Who holds a bundle's lock.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct LockInfo {
+    pub pid: u32,
+    pub host: String,
+    pub session_id: String,
+    pub acquired_at: String,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum LockState {
+    /// No lock file: nobody is writing the bundle
+    Free,
+    /// Held by a running process, or by one on another host whose
+    /// liveness cannot be checked
+    Held(LockInfo),
+    /// Left behind by a process on this host that is gone, typically after
+    /// a crash; the bundle was never finalized
+    Stale(LockInfo),
+}
+
+/// Advisory lock on a bundle directory, held by its writer for its whole
+/// lifetime and released on drop. Processes that honour it never write
+/// the same bundle at once.
+#[derive(Debug)]
+pub struct BundleLock {
+    path: PathBuf,
+    info: LockInfo,
+}
+
+/// How long to wait for another process to finish writing a lock it has
+/// just created before giving up on reading it.
+const PARTIAL_LOCK_RETRIES: u32 = 50;
+
+impl BundleLock {
+    /// Takes the lock, replacing a stale one. Fails with
+    /// `ErrorKind::WouldBlock` while a live process holds it.
+    pub fn acquire(dir: &Path, info: &LockInfo) -> io::Result<Self> {
+        let path = dir.join(LOCK_FILE);
+        let mut partial_retries = 0;
+        loop {
+            // `create_new` is atomic on every filesystem, including those
+            // without hard links (FAT, exFAT, SMB), so only one creator wins
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(mut file) => {
+                    let written = serde_json::to_vec_pretty(info)
+                        .map_err(io::Error::from)
+                        .and_then(|data| file.write_all(&data));
+                    if let Err(err) = written {
+                        let _ = fs::remove_file(&path);
+                        return Err(err);
+                    }
+                    return Ok(Self {
+                        path,
+                        info: info.clone(),
+                    });
+                }
+                Err(err) if err.kind() != ErrorKind::AlreadyExists => return Err(err),
+                Err(_) => {}
+            }
+            match lock_state(dir) {
+                Ok(LockState::Held(holder)) => {
+                    return Err(io::Error::new(
+                        ErrorKind::WouldBlock,
+                        format!(
+                            "{} is locked by pid {} on {} (session {})",
+                            dir.display(),
+                            holder.pid,
+                            holder.host,
+                            holder.session_id
+                        ),
+                    ));
+                }
+                Ok(LockState::Stale(stale)) => {
+                    remove_stale(dir, &stale)?;
+                }
+                Ok(LockState::Free) => {}
+                // Created by another process that has not written it yet
+                Err(err)
+                    if err.kind() == ErrorKind::InvalidData
+                        && partial_retries < PARTIAL_LOCK_RETRIES =>
+                {
+                    partial_retries += 1;
+                    thread::sleep(Duration::from_millis(10));
+                }
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    /// Removes the lock; dropping does the same but ignores errors.
+    pub fn release(self) -> io::Result<()> {
+        let result = self.remove();
+        std::mem::forget(self);
+        result
+    }
+
+    /// Only removes the lock file while it is still this lock, so a lock
+    /// that was taken over in the meantime is left alone.
+    fn remove(&self) -> io::Result<()> {
+        match read_info(&self.path) {
+            Ok(Some(info)) if info == self.info => remove_if_exists(&self.path),
+            Ok(_) => Ok(()),
+            Err(err) => Err(err),
+        }
+    }
+}
+
+impl Drop for BundleLock {
+    fn drop(&mut self) {
+        let _ = self.remove();
+    }
+}
+
+/// Removes a stale lock if it is still the one `stale` describes and
+/// returns whether it did. The lock is first renamed to a name only this
+/// process uses, then checked: if another process replaced the stale lock
+/// in the meantime, the lock that was moved is put back instead.
+pub fn remove_stale(dir: &Path, stale: &LockInfo) -> io::Result<bool> {
+    let path = dir.join(LOCK_FILE);
+    let claimed = dir.join(format!("{LOCK_FILE}.stale.{}", std::process::id()));
+    match fs::rename(&path, &claimed) {
+        Ok(()) => {}
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(false),
+        Err(err) => return Err(err),
+    }
+    match read_info(&claimed) {
+        Ok(Some(info)) if info == *stale => {
+            fs::remove_file(&claimed)?;
+            Ok(true)
+        }
+        _ => {
+            fs::rename(&claimed, &path)?;
+            Ok(false)
+        }
+    }
+}
+
+/// Inspects a bundle's lock without taking it, e.g. to tell a crashed
+/// session (stale lock) from one still being written.
+pub fn lock_state(dir: &Path) -> io::Result<LockState> {
+    let Some(info) = read_info(&dir.join(LOCK_FILE))? else {
+        return Ok(LockState::Free);
+    };
+    if info.host == hostname() && !process_alive(info.pid) {
+        Ok(LockState::Stale(info))
+    } else {
+        Ok(LockState::Held(info))
+    }
+}
+
+pub fn hostname() -> String {
+    #[cfg(unix)]
+    {
+        let mut buf = [0u8; 256];
+        let ok = unsafe { libc::gethostname(buf.as_mut_ptr().cast(), buf.len()) } == 0;
+        let len = buf.iter().position(|byte| *byte == 0).unwrap_or(buf.len());
+        if ok && len > 0 {
+            return String::from_utf8_lossy(&buf[..len]).into_owned();
+        }
+    }
+    std::env::var("COMPUTERNAME")
+        .or_else(|_| std::env::var("HOSTNAME"))
+        .unwrap_or_else(|_| "unknown".to_string())
+}
+
+#[cfg(unix)]
+fn process_alive(pid: u32) -> bool {
+    let Ok(pid) = libc::pid_t::try_from(pid) else {
+        return false;
+    };
+    if unsafe { libc::kill(pid, 0) } == 0 {
+        return true;
+    }
+    // EPERM: the process exists but belongs to another user
+    io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+/// Without a cheap liveness check every lock counts as held, so a stale
+/// one has to be removed by hand.
+#[cfg(not(unix))]
+fn process_alive(_pid: u32) -> bool {
+    true
+}
+
+fn read_info(path: &Path) -> io::Result<Option<LockInfo>> {
+    let data = match fs::read(path) {
+        Ok(data) => data,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err),
+    };
+    serde_json::from_slice(&data).map(Some).map_err(|err| {
+        io::Error::new(ErrorKind::InvalidData, format!("parse {LOCK_FILE}: {err}"))
+    })
+}
+
+fn remove_if_exists(path: &Path) -> io::Result<()> {
+    match fs::remove_file(path) {
+        Err(err) if err.kind() != ErrorKind::NotFound => Err(err),
+        _ => Ok(()),
+    }
+}
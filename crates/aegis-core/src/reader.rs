@@ -0,0 +1,204 @@
+use crate::bundle::{Event, Manifest, Session, EVENTS_FILE, MANIFEST_FILE, SESSION_FILE};
+use crate::hash::sha256_hex;
+use serde::de::DeserializeOwned;
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, Lines};
+use std::path::{Component, Path, PathBuf};
+
+/// Read access to a bundle directory. Nothing is read up front, so a
+/// bundle whose session never finished (no `session.json` or
+/// `manifest.json`) can still be opened and its events inspected.
+pub struct BundleReader {
+    dir: PathBuf,
+}
+
+/// Result of a successful hash-chain check.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Chain {
+    pub events: u64,
+    /// `hash` of the last event; empty when there are no events
+    pub last_hash: String,
+}
+
+impl BundleReader {
+    pub fn open(dir: impl AsRef<Path>) -> Result<Self, String> {
+        let dir = dir.as_ref();
+        if !dir.is_dir() {
+            return Err(format!("not a bundle directory: {}", dir.display()));
+        }
+        Ok(Self {
+            dir: dir.to_path_buf(),
+        })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn session(&self) -> Result<Session, String> {
+        self.read_json(SESSION_FILE)
+    }
+
+    pub fn manifest(&self) -> Result<Manifest, String> {
+        self.read_json(MANIFEST_FILE)
+    }
+
+    /// The bundle's `schema_version`, which selects how event hashes are
+    /// computed. Taken from `manifest.json`; a session that never finished
+    /// has none, so then from its `session_started` event, which records
+    /// it from version 2 on. Bundles with neither are version 1.
+    pub fn schema_version(&self) -> Result<u32, String> {
+        if self.dir.join(MANIFEST_FILE).exists() {
+            return Ok(self.manifest()?.schema_version);
+        }
+        Ok(self.started_schema_version()?.unwrap_or(1))
+    }
+
+    /// Events in file order; blank lines are skipped.
+    pub fn events(&self) -> Result<Events, String> {
+        let path = self.dir.join(EVENTS_FILE);
+        let file = File::open(&path).map_err(|err| format!("open events: {err}"))?;
+        Ok(Events {
+            lines: BufReader::new(file).lines(),
+            line_no: 0,
+        })
+    }
+
+    /// SHA-256 of `events.jsonl` as stored, for `manifest.events_hash`.
+    pub fn events_hash(&self) -> Result<String, String> {
+        self.read_file(EVENTS_FILE).map(|data| sha256_hex(&data))
+    }
+
+    /// Path of a file in the bundle. `rel_path` must stay inside the
+    /// bundle: absolute paths and `..` are refused.
+    pub fn file_path(&self, rel_path: &str) -> Result<PathBuf, String> {
+        let path = Path::new(rel_path);
+        let escapes = path
+            .components()
+            .any(|component| !matches!(component, Component::Normal(_) | Component::CurDir));
+        if rel_path.is_empty() || escapes {
+            return Err(format!("invalid rel_path: {rel_path}"));
+        }
+        Ok(self.dir.join(path))
+    }
+
+    pub fn open_file(&self, rel_path: &str) -> Result<File, String> {
+        File::open(self.file_path(rel_path)?).map_err(|err| format!("open {rel_path}: {err}"))
+    }
+
+    pub fn read_file(&self, rel_path: &str) -> Result<Vec<u8>, String> {
+        fs::read(self.file_path(rel_path)?).map_err(|err| format!("read {rel_path}: {err}"))
+    }
+
+    /// Checks that `seq` counts up from 1, that every event links to the
+    /// previous one and matches its own hash, and that every event belongs
+    /// to the session named in `session.json` (if the session finished).
+    /// Hashes are computed as [`schema_version`](Self::schema_version)
+    /// says, which must agree with the `session_started` event.
+    pub fn verify_chain(&self) -> Result<Chain, String> {
+        let schema_version = self.schema_version()?;
+        if let Some(started) = self.started_schema_version()? {
+            if started != schema_version {
+                return Err(format!(
+                    "schema_version mismatch: manifest says {schema_version}, session_started says {started}"
+                ));
+            }
+        }
+        let mut session_id = if self.dir.join(SESSION_FILE).exists() {
+            self.session()?.session_id
+        } else {
+            None
+        };
+        let mut expected_seq: u64 = 1;
+        let mut last_hash = String::new();
+
+        for event in self.events()? {
+            let event = event?;
+            let line = event.line();
+            if event.seq != expected_seq {
+                return Err(format!(
+                    "seq discontinuity at line {line}: expected {expected_seq}, got {}",
+                    event.seq
+                ));
+            }
+
+            match (event.session_id.as_deref(), session_id.as_deref()) {
+                (Some(event_session), Some(expected)) if event_session != expected => {
+                    return Err(format!("session_id mismatch at line {line}"));
+                }
+                (Some(event_session), None) if expected_seq == 1 => {
+                    session_id = Some(event_session.to_string());
+                }
+                (Some(_), None) => return Err(format!("unexpected session_id at line {line}")),
+                (None, Some(_)) => return Err(format!("missing session_id at line {line}")),
+                _ => {}
+            }
+
+            if expected_seq == 1 {
+                if !event.prev_hash.is_empty() {
+                    return Err(format!("prev_hash should be empty at line {line}"));
+                }
+            } else if event.prev_hash != last_hash {
+                return Err(format!("prev_hash mismatch at line {line}"));
+            }
+            if event.expected_hash(schema_version)? != event.hash {
+                return Err(format!("hash mismatch at line {line}"));
+            }
+
+            last_hash = event.hash;
+            expected_seq += 1;
+        }
+
+        Ok(Chain {
+            events: expected_seq - 1,
+            last_hash,
+        })
+    }
+
+    /// `schema_version` recorded in the first event, if any.
+    fn started_schema_version(&self) -> Result<Option<u32>, String> {
+        let Some(first) = self.events()?.next().transpose()? else {
+            return Ok(None);
+        };
+        if first.event_type != "session_started" {
+            return Ok(None);
+        }
+        let payload = first.payload()?;
+        match payload.get("schema_version") {
+            None => Ok(None),
+            Some(version) => version
+                .as_u64()
+                .and_then(|version| u32::try_from(version).ok())
+                .map(Some)
+                .ok_or_else(|| format!("invalid schema_version in session_started: {version}")),
+        }
+    }
+
+    fn read_json<T: DeserializeOwned>(&self, name: &str) -> Result<T, String> {
+        let data = self.read_file(name)?;
+        serde_json::from_slice(&data).map_err(|err| format!("parse {name}: {err}"))
+    }
+}
+
+/// Iterator over the events of a bundle, parsing one line at a time.
+pub struct Events {
+    lines: Lines<BufReader<File>>,
+    line_no: usize,
+}
+
+impl Iterator for Events {
+    type Item = Result<Event, String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let line = match self.lines.next()? {
+                Ok(line) => line,
+                Err(err) => return Some(Err(format!("read events line: {err}"))),
+            };
+            self.line_no += 1;
+            if !line.trim().is_empty() {
+                return Some(Event::parse(&line, self.line_no));
+            }
+        }
+    }
+}
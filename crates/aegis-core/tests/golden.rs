@@ -0,0 +1,44 @@
+use aegis_core::BundleReader;
+use std::path::PathBuf;
+
+/// `final_hash` of each bundle in `spec/golden/`, pinned here as well so
+/// that regenerating a golden bundle cannot hide a hashing change.
+const GOLDEN: &[(&str, u32, &str)] = &[
+    ("1-ts-seconds", 1, "fdd443e134b1e71a2e70cc9ea52dda312cde61a7854a8efd1a144597227ac051"),
+    ("2-observed-at", 1, "e0d2c7fe2f2648ab84ba1de5055f1422d04abdf8ceff846428716059560843cc"),
+    ("3-mono-us", 1, "d7abc625002e436e7df780ea8c21d4254ba284a0c0de0c64f14dd612b3fa5c36"),
+    ("4-session-id", 1, "0de14b079661e62ad20013dd6cd9dc2f6b879171df9f7edbc1418311182b45b0"),
+    ("5-jcs", 2, "3a3dea45e60da701555573d6a0c2a59c4592d8858a5fd1451e19af74640b14c2"),
+];
+
+fn golden_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../../spec/golden")
+}
+
+#[test]
+fn golden_bundles_verify() {
+    for (name, schema_version, final_hash) in GOLDEN {
+        let reader = BundleReader::open(golden_dir().join(name)).unwrap();
+        let manifest = reader.manifest().unwrap();
+        assert_eq!(manifest.schema_version, *schema_version, "{name}");
+        assert_eq!(manifest.final_hash, *final_hash, "{name}");
+
+        let chain = reader
+            .verify_chain()
+            .unwrap_or_else(|err| panic!("{name}: {err}"));
+        assert!(chain.events > 0, "{name}");
+        assert_eq!(chain.last_hash, manifest.final_hash, "{name}");
+        assert_eq!(reader.events_hash().unwrap(), manifest.events_hash, "{name}");
+    }
+}
+
+#[test]
+fn every_golden_bundle_is_listed() {
+    let mut names: Vec<String> = std::fs::read_dir(golden_dir())
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+        .collect();
+    names.sort();
+    let listed: Vec<&str> = GOLDEN.iter().map(|(name, _, _)| *name).collect();
+    assert_eq!(names, listed);
+}
@@ -0,0 +1,56 @@
+use aegis_core::{canonical_json, jcs_string};
+use serde_json::{json, Value};
+use std::fs;
+use std::path::PathBuf;
+
+fn jcs_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../../spec/jcs")
+}
+
+/// Each `spec/jcs/<name>.json` canonicalizes to `<name>.expected`, byte
+/// for byte (the file ends with a newline).
+#[test]
+fn rfc8785_vectors() {
+    let mut checked = 0;
+    for entry in fs::read_dir(jcs_dir()).unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().is_none_or(|ext| ext != "json") {
+            continue;
+        }
+        let input: Value = serde_json::from_slice(&fs::read(&path).unwrap()).unwrap();
+        let expected = fs::read(path.with_extension("expected")).unwrap();
+        let actual = canonical_json(2, &input).unwrap() + "\n";
+        assert_eq!(
+            actual.as_bytes(),
+            expected.as_slice(),
+            "{}",
+            path.display()
+        );
+        checked += 1;
+    }
+    assert_eq!(checked, 3);
+}
+
+#[test]
+fn jcs_numbers() {
+    let cases = [
+        (json!(-0.0), "0"),
+        (json!(1e21), "1e+21"),
+        (json!(1e20), "100000000000000000000"),
+        (json!(1e-7), "1e-7"),
+        (json!(0.000001), "0.000001"),
+        (json!(5e-324), "5e-324"),
+        // Shortest round-trip digits that end on a tie are still the
+        // correctly rounded ones
+        (json!(1424953923781206.2), "1424953923781206.2"),
+        (json!(u64::MAX), "18446744073709552000"),
+    ];
+    for (value, expected) in cases {
+        assert_eq!(jcs_string(&value), expected, "{value}");
+    }
+}
+
+#[test]
+fn unknown_schema_version_is_refused() {
+    assert!(canonical_json(3, &json!({})).is_err());
+}
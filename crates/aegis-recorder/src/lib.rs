@@ -0,0 +1,362 @@
+//! Screen recording backends behind [`ScreenRecorder`]: the platform
+//! recorder binary run once per segment (`spec/recorder_protocol.md`) and
+//! [`FakeRecorder`] for sessions without a display.
+
+use aegis_config::{expand_path, Config};
+use serde_json::Value;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// A finished segment as reported by a recorder backend.
+pub struct SegmentInfo {
+    pub output_path: PathBuf,
+    pub metadata: Value,
+}
+
+/// One way of producing screen recording segments. The recorder loop starts
+/// a segment, polls until it ends on its own or asks it to stop, then
+/// collects the segment for the bundle.
+pub trait ScreenRecorder: Send {
+    /// Short backend description recorded in `recording_config`.
+    fn name(&self) -> String;
+
+    /// Extension (without dot) of the files this backend writes.
+    fn file_extension(&self) -> &str;
+
+    fn start_segment(&mut self, output_path: &Path, duration: Duration) -> Result<(), String>;
+
+    /// Returns true once the current segment has ended.
+    fn is_finished(&mut self) -> bool;
+
+    /// Asks the current segment to end, forcing it after `timeout`. Safe to
+    /// call when no segment is running.
+    fn stop(&mut self, timeout: Duration);
+
+    /// Waits for the current segment to end and reports what it produced.
+    fn finish_segment(&mut self) -> Option<SegmentInfo>;
+}
+
+pub fn create_recorder(config: &Config) -> Result<Box<dyn ScreenRecorder>, String> {
+    match config.recording.backend.as_str() {
+        "native" => {
+            let program = match config.recording.recorder_path {
+                Some(ref path) => expand_path(path),
+                None => find_native_recorder()?,
+            };
+            if !program.exists() {
+                return Err(format!("recorder not found: {}", program.display()));
+            }
+            Ok(Box::new(SubprocessRecorder::new(
+                program,
+                NATIVE_EXTENSION,
+                config.recording.video.recorder_args(),
+            )))
+        }
+        "fake" => Ok(Box::new(FakeRecorder::new())),
+        other => Err(format!("unknown recorder backend: {other}")),
+    }
+}
+
+/// Runs an external recorder binary per segment following
+/// `spec/recorder_protocol.md`.
+pub struct SubprocessRecorder {
+    program: PathBuf,
+    extension: String,
+    video_args: Vec<String>,
+    child: Option<Child>,
+    output_path: Option<PathBuf>,
+    exit_code: Option<i32>,
+    forced: bool,
+}
+
+impl SubprocessRecorder {
+    /// `extension` picks the container: recorders write whatever the
+    /// output path's extension names.
+    pub fn new(program: PathBuf, extension: &str, video_args: Vec<String>) -> Self {
+        Self {
+            program,
+            extension: extension.to_string(),
+            video_args,
+            child: None,
+            output_path: None,
+            exit_code: None,
+            forced: false,
+        }
+    }
+
+    fn reap(&mut self) -> bool {
+        match self.child.as_mut().map(|child| child.try_wait()) {
+            Some(Ok(Some(status))) => {
+                self.exit_code = status.code();
+                self.child = None;
+                true
+            }
+            Some(_) => false,
+            None => true,
+        }
+    }
+}
+
+impl ScreenRecorder for SubprocessRecorder {
+    fn name(&self) -> String {
+        self.program
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_else(|| self.program.display().to_string())
+    }
+
+    fn file_extension(&self) -> &str {
+        &self.extension
+    }
+
+    fn start_segment(&mut self, output_path: &Path, duration: Duration) -> Result<(), String> {
+        let mut cmd = Command::new(&self.program);
+        cmd.arg(output_path.to_string_lossy().to_string())
+            .arg(duration.as_secs().max(1).to_string())
+            .args(&self.video_args);
+        cmd.stdin(Stdio::piped());
+        cmd.stdout(Stdio::null());
+        cmd.stderr(Stdio::null());
+
+        let child = cmd
+            .spawn()
+            .map_err(|err| format!("start recorder {}: {err}", self.program.display()))?;
+        self.child = Some(child);
+        self.output_path = Some(output_path.to_path_buf());
+        self.exit_code = None;
+        self.forced = false;
+        Ok(())
+    }
+
+    fn is_finished(&mut self) -> bool {
+        self.reap()
+    }
+
+    fn stop(&mut self, timeout: Duration) {
+        let Some(child) = self.child.as_mut() else {
+            return;
+        };
+        if let Some(mut stdin) = child.stdin.take() {
+            let _ = stdin.write_all(b"\n");
+        }
+
+        let wait_start = Instant::now();
+        while wait_start.elapsed() < timeout {
+            if self.reap() {
+                return;
+            }
+            thread::sleep(Duration::from_millis(100));
+        }
+
+        if let Some(mut child) = self.child.take() {
+            let _ = child.kill();
+            self.exit_code = child.wait().ok().and_then(|status| status.code());
+            self.forced = true;
+        }
+    }
+
+    fn finish_segment(&mut self) -> Option<SegmentInfo> {
+        if let Some(mut child) = self.child.take() {
+            self.exit_code = child.wait().ok().and_then(|status| status.code());
+        }
+        let output_path = self.output_path.take()?;
+        Some(SegmentInfo {
+            output_path,
+            metadata: serde_json::json!({
+                "backend": "native",
+                "exit_code": self.exit_code,
+                "forced_stop": self.forced,
+            }),
+        })
+    }
+}
+
+/// Writes small deterministic segment files instead of capturing the
+/// screen, so sessions can be exercised without a display or permissions.
+/// Segment `n` always contains the same bytes.
+#[derive(Default)]
+pub struct FakeRecorder {
+    segments: u64,
+    output_path: Option<PathBuf>,
+    deadline: Option<Instant>,
+}
+
+impl FakeRecorder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl ScreenRecorder for FakeRecorder {
+    fn name(&self) -> String {
+        "fake".to_string()
+    }
+
+    fn file_extension(&self) -> &str {
+        "bin"
+    }
+
+    fn start_segment(&mut self, output_path: &Path, duration: Duration) -> Result<(), String> {
+        self.segments += 1;
+        if let Some(parent) = output_path.parent() {
+            fs::create_dir_all(parent).map_err(|err| format!("create dirs: {err}"))?;
+        }
+        let content = format!("AEGIS FAKE SEGMENT {:06}\n", self.segments);
+        fs::write(output_path, content).map_err(|err| format!("write fake segment: {err}"))?;
+        self.output_path = Some(output_path.to_path_buf());
+        self.deadline = Some(Instant::now() + duration);
+        Ok(())
+    }
+
+    fn is_finished(&mut self) -> bool {
+        self.deadline
+            .map(|deadline| Instant::now() >= deadline)
+            .unwrap_or(true)
+    }
+
+    fn stop(&mut self, _timeout: Duration) {
+        self.deadline = None;
+    }
+
+    fn finish_segment(&mut self) -> Option<SegmentInfo> {
+        self.deadline = None;
+        let output_path = self.output_path.take()?;
+        Some(SegmentInfo {
+            output_path,
+            metadata: serde_json::json!({
+                "backend": "fake",
+                "segment": self.segments,
+            }),
+        })
+    }
+}
+
+/// Platform recorder binary following `spec/recorder_protocol.md`.
+const NATIVE_RECORDER: &str = if cfg!(target_os = "linux") {
+    "aegis-linux-recorder"
+} else {
+    "aegis-native-recorder"
+};
+
+/// Container of [`NATIVE_RECORDER`]'s segments: AVFoundation writes
+/// QuickTime, the Linux recorder's ffmpeg writes MP4.
+const NATIVE_EXTENSION: &str = if cfg!(target_os = "linux") {
+    "mp4"
+} else {
+    "mov"
+};
+
+fn find_native_recorder() -> Result<PathBuf, String> {
+    if let Some(explicit) = std::env::var_os("AEGIS_NATIVE_RECORDER") {
+        return Ok(PathBuf::from(explicit));
+    }
+
+    if let Ok(exe_path) = std::env::current_exe() {
+        if let Some(parent) = exe_path.parent() {
+            let candidate = parent.join(NATIVE_RECORDER);
+            if candidate.exists() {
+                return Ok(candidate);
+            }
+            let resources = parent.join("../Resources").join(NATIVE_RECORDER);
+            if resources.exists() {
+                return Ok(resources);
+            }
+        }
+    }
+
+    if let Ok(path_var) = std::env::var("PATH") {
+        for entry in path_var.split(':') {
+            let candidate = Path::new(entry).join(NATIVE_RECORDER);
+            if candidate.exists() {
+                return Ok(candidate);
+            }
+        }
+    }
+
+    let manifest_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let workspace_root = manifest_dir
+        .parent()
+        .and_then(|path| path.parent())
+        .map(PathBuf::from);
+    if let Some(root) = workspace_root {
+        let candidates = [
+            root.join("collectors/macos/native_recorder/.build/release/aegis-native-recorder"),
+            root.join("target/release").join(NATIVE_RECORDER),
+            root.join("target/debug").join(NATIVE_RECORDER),
+        ];
+        for candidate in candidates {
+            if candidate.file_name().map(|name| name == NATIVE_RECORDER).unwrap_or(false)
+                && candidate.exists()
+            {
+                return Ok(candidate);
+            }
+        }
+    }
+
+    Err(format!("{NATIVE_RECORDER} not found. Set AEGIS_NATIVE_RECORDER or build it (macOS: collectors/macos/native_recorder, Linux: cargo build -p aegis-linux-recorder)."))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("aegis-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn fake_recorder_writes_deterministic_segments() {
+        let dir = temp_dir("fake-recorder");
+        let mut recorder: Box<dyn ScreenRecorder> = Box::new(FakeRecorder::new());
+        assert_eq!(recorder.name(), "fake");
+        assert!(recorder.finish_segment().is_none());
+
+        // A segment that runs out on its own
+        let first = dir.join("files/screen_000001.bin");
+        recorder.start_segment(&first, Duration::ZERO).unwrap();
+        assert!(recorder.is_finished());
+        let segment = recorder.finish_segment().unwrap();
+        assert_eq!(segment.output_path, first);
+        assert_eq!(segment.metadata, serde_json::json!({"backend": "fake", "segment": 1}));
+        assert_eq!(fs::read_to_string(&first).unwrap(), "AEGIS FAKE SEGMENT 000001\n");
+
+        // A segment ended by stop
+        let second = dir.join("files/screen_000002.bin");
+        recorder.start_segment(&second, Duration::from_secs(3600)).unwrap();
+        assert!(!recorder.is_finished());
+        recorder.stop(Duration::from_secs(1));
+        assert!(recorder.is_finished());
+        let segment = recorder.finish_segment().unwrap();
+        assert_eq!(segment.output_path, second);
+        assert_eq!(segment.metadata["segment"], 2);
+        assert_eq!(fs::read_to_string(&second).unwrap(), "AEGIS FAKE SEGMENT 000002\n");
+        assert!(recorder.finish_segment().is_none());
+
+        // Same bytes for the same segment number in a fresh recorder
+        let again = dir.join("again.bin");
+        let mut fresh = FakeRecorder::new();
+        fresh.start_segment(&again, Duration::ZERO).unwrap();
+        assert_eq!(fs::read(&again).unwrap(), fs::read(&first).unwrap());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn native_segments_use_the_platform_container() {
+        let mut config = Config::default();
+        config.recording.recorder_path = Some("/bin/sh".to_string());
+        let recorder = create_recorder(&config).unwrap();
+        assert_eq!(recorder.name(), "sh");
+        let expected = if cfg!(target_os = "linux") { "mp4" } else { "mov" };
+        assert_eq!(recorder.file_extension(), expected);
+
+        config.recording.backend = "fake".to_string();
+        assert_eq!(create_recorder(&config).unwrap().file_extension(), "bin");
+    }
+}
@@ -0,0 +1,67 @@
+use aegis_core::BundleReader;
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+
+/// Matches the writer's threshold, plus slack for reading the wall and
+/// monotonic clocks at slightly different moments.
+const CLOCK_JUMP_LIMIT_US: i64 = 1_000_000 + 1_000;
+
+/// Checks event times in bundles that carry `mono_us`: it must never
+/// decrease, and `ts` may only go backwards or move against `mono_us` by
+/// more than the writer's threshold at a `clock_jump` event. Older bundles
+/// without `mono_us` are not checked. Each `clock_jump` is reported as a
+/// warning, since `ts` around it does not match elapsed time.
+pub fn check_clock(reader: &BundleReader) -> Result<(), String> {
+    // Wall-clock time at `mono_us` 0, from the first event
+    let mut origin: Option<DateTime<Utc>> = None;
+    let mut skew_us = 0i64;
+    let mut last: Option<(DateTime<Utc>, u64)> = None;
+    for event in reader.events()? {
+        let event = event?;
+        let line_no = event.line();
+        let mono_us = match (event.mono_us, origin) {
+            (Some(mono_us), _) => mono_us,
+            (None, None) => return Ok(()),
+            (None, Some(_)) => return Err(format!("missing mono_us at line {line_no}")),
+        };
+        let ts = event.timestamp()?;
+        let origin = *origin.get_or_insert(ts - micros(mono_us));
+        let event_skew_us = (ts - origin)
+            .num_microseconds()
+            .unwrap_or(i64::MAX)
+            .saturating_sub(i64::try_from(mono_us).unwrap_or(i64::MAX));
+
+        if event.event_type == "clock_jump" {
+            let jump_ms = event
+                .payload()?
+                .get("jump_ms")
+                .and_then(Value::as_i64)
+                .unwrap_or_default();
+            eprintln!("WARN: line {line_no}: clock_jump of {jump_ms}ms");
+            skew_us = event_skew_us;
+        } else {
+            if let Some((last_ts, _)) = last {
+                if ts < last_ts {
+                    return Err(format!("ts goes backwards at line {line_no} without clock_jump"));
+                }
+            }
+            if (event_skew_us - skew_us).abs() > CLOCK_JUMP_LIMIT_US {
+                return Err(format!(
+                    "ts moved {}ms against mono_us at line {line_no} without clock_jump",
+                    (event_skew_us - skew_us) / 1000
+                ));
+            }
+        }
+        if let Some((_, last_mono_us)) = last {
+            if mono_us < last_mono_us {
+                return Err(format!("mono_us goes backwards at line {line_no}"));
+            }
+        }
+        last = Some((ts, mono_us));
+    }
+    Ok(())
+}
+
+fn micros(value: u64) -> chrono::Duration {
+    chrono::Duration::microseconds(i64::try_from(value).unwrap_or(i64::MAX))
+}
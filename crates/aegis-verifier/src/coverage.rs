@@ -0,0 +1,188 @@
+use aegis_core::BundleReader;
+use chrono::{DateTime, Utc};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+
+/// Heartbeat history of one collector.
+#[derive(Default)]
+struct Liveness {
+    first_seen: Option<DateTime<Utc>>,
+    /// Closed gaps, from the last heartbeat before `collector_lost` to
+    /// `collector_recovered`
+    gaps: Vec<(DateTime<Utc>, DateTime<Utc>)>,
+    /// Start of a gap still open at the end of the session
+    lost_since: Option<DateTime<Utc>>,
+}
+
+pub struct CollectorCoverage {
+    name: String,
+    monitored_ms: i64,
+    gaps: usize,
+    gap_ms: i64,
+    lost_at_end: bool,
+    heard: bool,
+}
+
+/// Per-collector coverage from `heartbeat` / `collector_lost` /
+/// `collector_recovered` events. Collectors the agent started but which
+/// never sent a heartbeat are listed with no coverage.
+pub fn coverage(reader: &BundleReader) -> Result<Vec<CollectorCoverage>, String> {
+    let mut collectors: BTreeMap<String, Liveness> = BTreeMap::new();
+    let mut session_end = None;
+    for event in reader.events()? {
+        let event = event?;
+        let ts = event.timestamp()?;
+        session_end = Some(ts);
+
+        let line = event.line();
+        let payload = event.payload()?;
+        let field = |name: &str| payload.get(name).and_then(Value::as_str).map(str::to_string);
+        match event.event_type.as_str() {
+            "heartbeat" => {
+                if let Some(name) = field("collector") {
+                    collectors.entry(name).or_default().first_seen.get_or_insert(ts);
+                }
+            }
+            // The agent's view: a collector it started should be heard from
+            "collector_started" => {
+                if let Some(name) = field("name") {
+                    collectors.entry(name).or_default();
+                }
+            }
+            "collector_lost" => {
+                let name = field("collector")
+                    .ok_or_else(|| format!("collector_lost missing collector (line {line})"))?;
+                let liveness = collectors.entry(name.clone()).or_default();
+                let Some(first_seen) = liveness.first_seen else {
+                    return Err(format!("collector_lost for {name} before any heartbeat (line {line})"));
+                };
+                if liveness.lost_since.is_some() {
+                    return Err(format!("collector_lost for {name} while already lost (line {line})"));
+                }
+                // The gap starts at the last heartbeat, not when it was noticed
+                let silent_ms = payload.get("silent_ms").and_then(Value::as_i64).unwrap_or(0);
+                let since = ts - chrono::Duration::milliseconds(silent_ms);
+                liveness.lost_since = Some(since.max(first_seen));
+            }
+            "collector_recovered" => {
+                let name = field("collector").ok_or_else(|| {
+                    format!("collector_recovered missing collector (line {line})")
+                })?;
+                let liveness = collectors.entry(name.clone()).or_default();
+                let since = liveness.lost_since.take().ok_or_else(|| {
+                    format!("collector_recovered for {name} without collector_lost (line {line})")
+                })?;
+                liveness.gaps.push((since, ts));
+            }
+            _ => {}
+        }
+    }
+
+    let Some(session_end) = session_end else {
+        return Ok(Vec::new());
+    };
+    let report = collectors
+        .into_iter()
+        .map(|(name, liveness)| {
+            let Some(first_seen) = liveness.first_seen else {
+                return CollectorCoverage {
+                    name,
+                    monitored_ms: 0,
+                    gaps: 0,
+                    gap_ms: 0,
+                    lost_at_end: false,
+                    heard: false,
+                };
+            };
+            let mut gaps = liveness.gaps;
+            let lost_at_end = liveness.lost_since.is_some();
+            if let Some(since) = liveness.lost_since {
+                gaps.push((since, session_end));
+            }
+            CollectorCoverage {
+                name,
+                monitored_ms: (session_end - first_seen).num_milliseconds(),
+                gaps: gaps.len(),
+                gap_ms: gaps.iter().map(|(start, end)| (*end - *start).num_milliseconds()).sum(),
+                lost_at_end,
+                heard: true,
+            }
+        })
+        .collect();
+    Ok(report)
+}
+
+impl CollectorCoverage {
+    /// Fraction of the time since the first heartbeat that the collector
+    /// was known to be alive.
+    fn ratio(&self) -> f64 {
+        if !self.heard {
+            return 0.0;
+        }
+        if self.monitored_ms <= 0 {
+            return 1.0;
+        }
+        (self.monitored_ms - self.gap_ms).max(0) as f64 / self.monitored_ms as f64
+    }
+
+    fn to_json(&self) -> Value {
+        json!({
+            "collector": self.name,
+            "heartbeats": self.heard,
+            "monitored_ms": self.monitored_ms,
+            "gaps": self.gaps,
+            "gap_ms": self.gap_ms,
+            "lost_at_end": self.lost_at_end,
+            "coverage": (self.ratio() * 10000.0).round() / 10000.0,
+        })
+    }
+}
+
+/// `coverage <bundle_path> [--json]`: verifies the bundle, then prints the
+/// coverage of every collector.
+pub fn report(mut args: impl Iterator<Item = String>) -> Result<(), String> {
+    let bundle_path = args.next().ok_or("missing bundle path")?;
+    let as_json = match args.next().as_deref() {
+        None => false,
+        Some("--json") => true,
+        Some(other) => return Err(format!("unknown option: {other}")),
+    };
+    crate::verify(std::iter::once(bundle_path.clone()))?;
+
+    let report = coverage(&BundleReader::open(&bundle_path)?)?;
+    if as_json {
+        let entries: Vec<Value> = report.iter().map(CollectorCoverage::to_json).collect();
+        println!("{}", Value::Array(entries));
+        return Ok(());
+    }
+    if report.is_empty() {
+        println!("no collector heartbeats in bundle");
+        return Ok(());
+    }
+    println!("{:<14} {:>10} {:>5} {:>10} {:>9}", "collector", "monitored", "gaps", "lost", "coverage");
+    for entry in &report {
+        if !entry.heard {
+            println!("{:<14} {:>10} {:>5} {:>10} {:>9}", entry.name, "-", "-", "-", "none");
+            continue;
+        }
+        let mut coverage = format!("{:.1}%", entry.ratio() * 100.0);
+        if entry.lost_at_end {
+            coverage.push('*');
+        }
+        println!(
+            "{:<14} {:>9}s {:>5} {:>9}s {:>9}",
+            entry.name,
+            entry.monitored_ms / 1000,
+            entry.gaps,
+            entry.gap_ms / 1000,
+            coverage
+        );
+    }
+    if report.iter().any(|entry| entry.lost_at_end) {
+        println!("* still lost when the session ended");
+    }
+    if report.iter().any(|entry| !entry.heard) {
+        println!("none: started by the agent but never sent a heartbeat");
+    }
+    Ok(())
+}
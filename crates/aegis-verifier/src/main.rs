@@ -1,212 +1,139 @@
-use serde_json::{Map, Value};
-use sha2::{Digest, Sha256};
+mod clock;
+mod coverage;
+mod observed;
+mod term;
+
+use aegis_core::{
+    canonical_json, lock_state, remove_stale, BundleReader, LockInfo, LockState, EVENTS_FILE,
+    LOCK_FILE, MANIFEST_FILE, SCHEMA_VERSION, SESSION_FILE,
+};
 use std::env;
-use std::fs::{self, File};
-use std::io::{BufRead, BufReader};
+use std::fs;
 use std::path::{Path, PathBuf};
 
+const USAGE: &str = "usage: aegis-verifier verify <bundle_path>\n       aegis-verifier coverage <bundle_path> [--json]\n       aegis-verifier replay <bundle_path> <cast_rel_path> [--speed N] [--max-idle SECS]\n       aegis-verifier lock <bundle_path> [--clear-stale]\n       aegis-verifier canonical <json_file> [--schema N]";
+
 fn main() {
-    if let Err(err) = run() {
+    let mut args = env::args().skip(1);
+    let result = match args.next().unwrap_or_default().as_str() {
+        "verify" => verify(args).map(|()| println!("PASS")),
+        "coverage" => coverage::report(args),
+        "replay" => term::replay(args),
+        "lock" => lock(args),
+        "canonical" => canonical(args),
+        _ => Err(USAGE.to_string()),
+    };
+    if let Err(err) = result {
         eprintln!("FAIL: {err}");
         std::process::exit(1);
     }
-    println!("PASS");
 }
 
-fn run() -> Result<(), String> {
-    let mut args = env::args().skip(1);
-    let command = args.next().unwrap_or_default();
-    if command != "verify" {
-        return Err("usage: aegis-verifier verify <bundle_path>".to_string());
-    }
+fn verify(mut args: impl Iterator<Item = String>) -> Result<(), String> {
     let bundle_path = args.next().ok_or("missing bundle path")?;
     let bundle_path = PathBuf::from(bundle_path);
 
-    let session_path = bundle_path.join("session.json");
-    let events_path = bundle_path.join("events.jsonl");
-    let manifest_path = bundle_path.join("manifest.json");
+    // A bundle still being written, or whose writer crashed, has no
+    // manifest yet; say so instead of reporting the missing file
+    match lock_state(&bundle_path).map_err(|err| format!("read lock: {err}"))? {
+        LockState::Free => {}
+        LockState::Held(info) => {
+            return Err(format!("bundle is still being written: {}", describe_lock(&info)));
+        }
+        LockState::Stale(info) => {
+            return Err(format!(
+                "session did not finish: stale lock from {}",
+                describe_lock(&info)
+            ));
+        }
+    }
 
-    ensure_exists(&session_path)?;
-    ensure_exists(&events_path)?;
-    ensure_exists(&manifest_path)?;
+    let reader = BundleReader::open(&bundle_path)?;
+    for name in [SESSION_FILE, EVENTS_FILE, MANIFEST_FILE] {
+        ensure_exists(&bundle_path.join(name))?;
+    }
 
-    let (last_hash, events_count) = verify_event_sequence(&events_path)?;
-    if events_count == 0 {
+    let chain = reader.verify_chain()?;
+    if chain.events == 0 {
         return Err("events.jsonl is empty".to_string());
     }
 
-    let manifest = read_manifest(&manifest_path)?;
-    verify_manifest_files(&bundle_path, &manifest)?;
-
-    let events_hash = sha256_hex(&fs::read(&events_path).map_err(|err| err.to_string())?);
-    let manifest_events_hash = get_manifest_string(&manifest, "events_hash")?;
-    if events_hash != manifest_events_hash {
+    let manifest = reader.manifest()?;
+    for entry in &manifest.files {
+        if !reader.file_path(&entry.rel_path)?.exists() {
+            return Err(format!("missing file listed in manifest: {}", entry.rel_path));
+        }
+    }
+    if reader.events_hash()? != manifest.events_hash {
         return Err("events_hash mismatch".to_string());
     }
-
-    let manifest_final_hash = get_manifest_string(&manifest, "final_hash")?;
-    if manifest_final_hash != last_hash {
+    if manifest.final_hash != chain.last_hash {
         return Err("final_hash mismatch".to_string());
     }
 
-    Ok(())
-}
-
-fn ensure_exists(path: &Path) -> Result<(), String> {
-    if path.exists() {
-        Ok(())
-    } else {
-        Err(format!("missing required file: {}", path.display()))
-    }
-}
-
-fn read_manifest(path: &Path) -> Result<Value, String> {
-    let content = fs::read_to_string(path).map_err(|err| err.to_string())?;
-    serde_json::from_str(&content).map_err(|err| format!("parse manifest: {err}"))
-}
-
-fn get_manifest_string(manifest: &Value, key: &str) -> Result<String, String> {
-    manifest
-        .get(key)
-        .and_then(|value| value.as_str())
-        .map(|value| value.to_string())
-        .ok_or_else(|| format!("manifest missing {key}"))
-}
-
-fn verify_manifest_files(bundle_path: &Path, manifest: &Value) -> Result<(), String> {
-    let files = manifest
-        .get("files")
-        .and_then(|value| value.as_array())
-        .ok_or("manifest missing files")?;
-
-    for entry in files {
-        let rel_path = entry
-            .get("rel_path")
-            .and_then(|value| value.as_str())
-            .ok_or("manifest file missing rel_path")?;
-        let full_path = bundle_path.join(rel_path);
-        if !full_path.exists() {
-            return Err(format!("missing file listed in manifest: {rel_path}"));
-        }
-    }
+    term::verify_casts(&reader)?;
+    clock::check_clock(&reader)?;
+    coverage::coverage(&reader)?;
+    observed::check_observed(&reader)?;
 
     Ok(())
 }
 
-fn verify_event_sequence(path: &Path) -> Result<(String, u64), String> {
-    let file = File::open(path).map_err(|err| format!("open events: {err}"))?;
-    let reader = BufReader::new(file);
-    let mut expected_seq: u64 = 1;
-    let mut last_hash = String::new();
-    let mut count = 0;
-
-    for (index, line) in reader.lines().enumerate() {
-        let line = line.map_err(|err| format!("read events line: {err}"))?;
-        if line.trim().is_empty() {
-            continue;
-        }
-        let value: Value = serde_json::from_str(&line)
-            .map_err(|err| format!("parse events line {}: {err}", index + 1))?;
-        let seq = value
-            .get("seq")
-            .and_then(|v| v.as_u64())
-            .ok_or_else(|| format!("missing seq at line {}", index + 1))?;
-
-        if seq != expected_seq {
-            return Err(format!(
-                "seq discontinuity at line {}: expected {}, got {}",
-                index + 1,
-                expected_seq,
-                seq
-            ));
-        }
-
-        let ts = value
-            .get("ts")
-            .and_then(|v| v.as_str())
-            .ok_or_else(|| format!("missing ts at line {}", index + 1))?;
-        let event_type = value
-            .get("type")
-            .and_then(|v| v.as_str())
-            .ok_or_else(|| format!("missing type at line {}", index + 1))?;
-        let payload = value
-            .get("payload")
-            .ok_or_else(|| format!("missing payload at line {}", index + 1))?;
-        let prev_hash = value
-            .get("prev_hash")
-            .and_then(|v| v.as_str())
-            .ok_or_else(|| format!("missing prev_hash at line {}", index + 1))?;
-        let hash = value
-            .get("hash")
-            .and_then(|v| v.as_str())
-            .ok_or_else(|| format!("missing hash at line {}", index + 1))?;
-
-        if expected_seq == 1 {
-            if !prev_hash.is_empty() {
-                return Err(format!("prev_hash should be empty at line {}", index + 1));
-            }
-        } else if prev_hash != last_hash {
-            return Err(format!("prev_hash mismatch at line {}", index + 1));
-        }
-
-        let payload = canonicalize_value(payload);
-        let hash_input = serde_json::json!({
-            "seq": seq,
-            "ts": ts,
-            "type": event_type,
-            "payload": payload,
-            "prev_hash": prev_hash,
-        });
-        let expected_hash = sha256_hex(canonical_json_string(&hash_input).as_bytes());
-        if expected_hash != hash {
-            return Err(format!("hash mismatch at line {}", index + 1));
-        }
-
-        last_hash = hash.to_string();
-        expected_seq += 1;
-        count += 1;
-    }
-
-    Ok((last_hash, count))
-}
-
-fn canonicalize_value(value: &Value) -> Value {
-    match value {
-        Value::Array(items) => {
-            let mapped: Vec<Value> = items.iter().map(canonicalize_value).collect();
-            Value::Array(mapped)
-        }
-        Value::Object(map) => {
-            let mut keys: Vec<&String> = map.keys().collect();
-            keys.sort();
-            let mut new_map = Map::new();
-            for key in keys {
-                if let Some(value) = map.get(key) {
-                    new_map.insert(key.clone(), canonicalize_value(value));
-                }
+/// Reports who holds a bundle's lock; `--clear-stale` removes a lock
+/// whose process is gone.
+fn lock(mut args: impl Iterator<Item = String>) -> Result<(), String> {
+    let bundle_path = PathBuf::from(args.next().ok_or(USAGE)?);
+    let clear_stale = match args.next().as_deref() {
+        None => false,
+        Some("--clear-stale") => true,
+        Some(_) => return Err(USAGE.to_string()),
+    };
+    match lock_state(&bundle_path).map_err(|err| format!("read lock: {err}"))? {
+        LockState::Free => println!("unlocked"),
+        LockState::Held(info) => println!("locked by {}", describe_lock(&info)),
+        LockState::Stale(info) if clear_stale => {
+            let removed = remove_stale(&bundle_path, &info)
+                .map_err(|err| format!("remove {LOCK_FILE}: {err}"))?;
+            if removed {
+                println!("removed stale lock from {}", describe_lock(&info));
+            } else {
+                println!("stale lock was replaced while clearing it; run again");
             }
-            Value::Object(new_map)
         }
-        other => other.clone(),
+        LockState::Stale(info) => println!("stale lock from {}", describe_lock(&info)),
     }
+    Ok(())
 }
 
-fn canonical_json_string(value: &Value) -> String {
-    let canonical = canonicalize_value(value);
-    serde_json::to_string(&canonical).unwrap_or_default()
+/// Prints the canonical JSON that event hashes of the given schema version
+/// (default: the current one) are computed over, e.g. to compare another
+/// implementation against.
+fn canonical(mut args: impl Iterator<Item = String>) -> Result<(), String> {
+    let path = args.next().ok_or(USAGE)?;
+    let schema_version = match (args.next().as_deref(), args.next()) {
+        (None, _) => SCHEMA_VERSION,
+        (Some("--schema"), Some(value)) => value
+            .parse()
+            .map_err(|_| format!("invalid value for --schema: {value}"))?,
+        _ => return Err(USAGE.to_string()),
+    };
+    let content = fs::read(&path).map_err(|err| format!("read {path}: {err}"))?;
+    let value = serde_json::from_slice(&content).map_err(|err| format!("parse {path}: {err}"))?;
+    println!("{}", canonical_json(schema_version, &value)?);
+    Ok(())
 }
 
-fn sha256_hex(bytes: &[u8]) -> String {
-    let mut hasher = Sha256::new();
-    hasher.update(bytes);
-    let digest = hasher.finalize();
-    bytes_to_hex(&digest)
+fn describe_lock(info: &LockInfo) -> String {
+    format!(
+        "pid {} on {} (session {}, since {})",
+        info.pid, info.host, info.session_id, info.acquired_at
+    )
 }
 
-fn bytes_to_hex(bytes: &[u8]) -> String {
-    let mut out = String::with_capacity(bytes.len() * 2);
-    for byte in bytes {
-        out.push_str(&format!("{:02x}", byte));
+fn ensure_exists(path: &Path) -> Result<(), String> {
+    if path.exists() {
+        Ok(())
+    } else {
+        Err(format!("missing required file: {}", path.display()))
     }
-    out
 }
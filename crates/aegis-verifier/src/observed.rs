@@ -0,0 +1,41 @@
+use aegis_core::BundleReader;
+use chrono::{DateTime, Utc};
+use std::collections::BTreeMap;
+
+/// Checks the collector-reported `observed_at` of each event against the
+/// server's `ts`. Neither finding breaks the bundle, so both are warnings:
+/// an event observed after it was written means the collector clock runs
+/// ahead, and an event type whose `observed_at` goes backwards means its
+/// timeline cannot be ordered by capture time.
+pub fn check_observed(reader: &BundleReader) -> Result<(), String> {
+    let mut last_observed: BTreeMap<String, DateTime<Utc>> = BTreeMap::new();
+    for event in reader.events()? {
+        let event = event?;
+        let Some(observed) = event.observed_timestamp()? else {
+            continue;
+        };
+        let ts = event.timestamp()?;
+        let event_type = event.event_type.as_str();
+
+        // A `ts` in whole seconds is truncated, so it may trail the
+        // millisecond `observed_at` of the same second
+        let slack_ms = if event.ts.contains('.') { 0 } else { 999 };
+        let ahead_ms = (observed - ts).num_milliseconds();
+        if ahead_ms > slack_ms {
+            eprintln!(
+                "WARN: line {}: {event_type} observed_at runs {ahead_ms}ms ahead of ts",
+                event.line()
+            );
+        }
+        if let Some(previous) = last_observed.insert(event_type.to_string(), observed) {
+            if observed < previous {
+                eprintln!(
+                    "WARN: line {}: {event_type} observed_at goes back {}ms",
+                    event.line(),
+                    (previous - observed).num_milliseconds()
+                );
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,183 @@
+use aegis_core::{sha256_hex, BundleReader};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::thread;
+use std::time::Duration;
+
+/// What the event log says about one terminal cast file.
+#[derive(Default)]
+struct CastRecord {
+    chunks: Vec<(u64, u64, u64, String)>,
+    ended: Option<Value>,
+}
+
+/// Checks every cast referenced by `terminal_chunk` / `terminal_session_ended`
+/// events: the chunks must cover the file contiguously from offset 0, each
+/// chunk hash must match, and the file must be a well-formed asciicast v2
+/// recording.
+pub fn verify_casts(reader: &BundleReader) -> Result<(), String> {
+    for (rel_path, record) in read_cast_records(reader)? {
+        verify_cast(reader, &rel_path, &record)?;
+    }
+    Ok(())
+}
+
+fn read_cast_records(reader: &BundleReader) -> Result<BTreeMap<String, CastRecord>, String> {
+    let mut records: BTreeMap<String, CastRecord> = BTreeMap::new();
+    for event in reader.events()? {
+        let event = event?;
+        let event_type = event.event_type.as_str();
+        if event_type != "terminal_chunk" && event_type != "terminal_session_ended" {
+            continue;
+        }
+        let payload = event.payload()?;
+        let rel_path = payload
+            .get("rel_path")
+            .and_then(Value::as_str)
+            .ok_or_else(|| format!("{event_type} missing rel_path"))?
+            .to_string();
+        let record = records.entry(rel_path.clone()).or_default();
+        if event_type == "terminal_session_ended" {
+            record.ended = Some(payload);
+            continue;
+        }
+        let field = |name: &str| {
+            payload
+                .get(name)
+                .and_then(Value::as_u64)
+                .ok_or_else(|| format!("terminal_chunk for {rel_path} missing {name}"))
+        };
+        let sha256 = payload
+            .get("sha256")
+            .and_then(Value::as_str)
+            .ok_or_else(|| format!("terminal_chunk for {rel_path} missing sha256"))?;
+        record
+            .chunks
+            .push((field("index")?, field("offset")?, field("length")?, sha256.to_string()));
+    }
+    Ok(records)
+}
+
+fn verify_cast(reader: &BundleReader, rel_path: &str, record: &CastRecord) -> Result<(), String> {
+    let Ok(content) = reader.read_file(rel_path) else {
+        if record.ended.is_some() {
+            return Err(format!("missing terminal cast: {rel_path}"));
+        }
+        // The bundle was closed while the terminal session was still open
+        eprintln!("WARN: terminal session {rel_path} did not end; cast not in bundle");
+        return Ok(());
+    };
+
+    let mut expected_offset = 0u64;
+    for (position, (index, offset, length, sha256)) in record.chunks.iter().enumerate() {
+        if *index != position as u64 || *offset != expected_offset {
+            return Err(format!("terminal chunk gap in {rel_path} at chunk {index}"));
+        }
+        let end = offset + length;
+        let bytes = content
+            .get(*offset as usize..end as usize)
+            .ok_or_else(|| format!("terminal chunk {index} beyond end of {rel_path}"))?;
+        if sha256_hex(bytes) != *sha256 {
+            return Err(format!("terminal chunk {index} hash mismatch in {rel_path}"));
+        }
+        expected_offset = end;
+    }
+    if expected_offset != content.len() as u64 {
+        return Err(format!(
+            "{rel_path} has {} bytes not covered by terminal chunks",
+            content.len() as u64 - expected_offset
+        ));
+    }
+    if let Some(ended) = record.ended.as_ref() {
+        if ended.get("sha256").and_then(Value::as_str) != Some(sha256_hex(&content).as_str()) {
+            return Err(format!("terminal cast hash mismatch: {rel_path}"));
+        }
+    }
+
+    parse_cast(&content).map_err(|err| format!("{rel_path}: {err}"))?;
+    Ok(())
+}
+
+/// Parses asciicast v2 into `(time, code, data)` events, requiring
+/// non-decreasing timestamps.
+fn parse_cast(content: &[u8]) -> Result<Vec<(f64, String, String)>, String> {
+    let text = std::str::from_utf8(content).map_err(|_| "cast is not UTF-8".to_string())?;
+    let mut lines = text.lines();
+    let header: Value = lines
+        .next()
+        .and_then(|line| serde_json::from_str(line).ok())
+        .ok_or("missing asciicast header")?;
+    if header.get("version").and_then(Value::as_u64) != Some(2) {
+        return Err("not an asciicast v2 file".to_string());
+    }
+
+    let mut events = Vec::new();
+    let mut last_time = 0.0;
+    for (number, line) in lines.enumerate() {
+        let event: Value = serde_json::from_str(line)
+            .map_err(|err| format!("cast event {}: {err}", number + 1))?;
+        let (Some(time), Some(code), Some(data)) = (
+            event.get(0).and_then(Value::as_f64),
+            event.get(1).and_then(Value::as_str),
+            event.get(2).and_then(Value::as_str),
+        ) else {
+            return Err(format!("malformed cast event {}", number + 1));
+        };
+        if time < last_time {
+            return Err(format!("cast event {} goes back in time", number + 1));
+        }
+        last_time = time;
+        events.push((time, code.to_string(), data.to_string()));
+    }
+    Ok(events)
+}
+
+/// `replay <bundle_path> <cast_rel_path> [--speed N] [--max-idle SECS]`:
+/// verifies one cast against its chunk events, then plays its output to
+/// stdout. `--speed 0` prints without delays.
+pub fn replay(mut args: impl Iterator<Item = String>) -> Result<(), String> {
+    let bundle_path = args.next().ok_or("missing bundle path")?;
+    let rel_path = args.next().ok_or("missing cast path")?;
+    let mut speed = 1.0f64;
+    let mut max_idle = 2.0f64;
+    while let Some(flag) = args.next() {
+        let value = args.next().ok_or_else(|| format!("{flag} requires a value"))?;
+        let parsed = value
+            .parse::<f64>()
+            .ok()
+            .filter(|parsed| *parsed >= 0.0)
+            .ok_or_else(|| format!("invalid value for {flag}: {value}"))?;
+        match flag.as_str() {
+            "--speed" => speed = parsed,
+            "--max-idle" => max_idle = parsed,
+            _ => return Err(format!("unknown option: {flag}")),
+        }
+    }
+
+    let reader = BundleReader::open(&bundle_path)?;
+    let records = read_cast_records(&reader)?;
+    let record = records
+        .get(&rel_path)
+        .ok_or_else(|| format!("no terminal events for {rel_path}"))?;
+    verify_cast(&reader, &rel_path, record)?;
+
+    let content = reader.read_file(&rel_path)?;
+    let mut stdout = std::io::stdout();
+    let mut last_time = 0.0;
+    for (time, code, data) in parse_cast(&content)? {
+        if code != "o" {
+            continue;
+        }
+        if speed > 0.0 {
+            let pause = ((time - last_time) / speed).min(max_idle);
+            thread::sleep(Duration::from_secs_f64(pause.max(0.0)));
+        }
+        last_time = time;
+        stdout
+            .write_all(data.as_bytes())
+            .and_then(|()| stdout.flush())
+            .map_err(|err| format!("write stdout: {err}"))?;
+    }
+    Ok(())
+}
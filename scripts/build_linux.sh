@@ -4,13 +4,24 @@ set -e
 ROOT_DIR="$(cd "$(dirname "$0")/.." && pwd)"
 DIST_DIR="$ROOT_DIR/dist/linux"
 
+# aegis-agent 在自身所在目录查找采集器，因此一并输出
+COLLECTORS="aegis-focus-collector aegis-input-collector aegis-idle-collector aegis-file-collector aegis-process-collector aegis-dns-collector aegis-clipboard-collector aegis-screenshot-collector"
+
 mkdir -p "$DIST_DIR"
 
 cd "$ROOT_DIR"
-cargo build --release -p aegis-core-server -p aegis-collector-cli -p aegis-verifier
+cargo build --release -p aegis-core-server -p aegis-collector-cli -p aegis-verifier -p aegis-config -p aegis-agent
+for collector in $COLLECTORS; do
+    cargo build --release -p "$collector"
+done
 
 cp "target/release/aegis-core-server" "$DIST_DIR/"
 cp "target/release/aegis-collector-cli" "$DIST_DIR/"
 cp "target/release/aegis-verifier" "$DIST_DIR/"
+cp "target/release/aegis-config" "$DIST_DIR/"
+cp "target/release/aegis-agent" "$DIST_DIR/"
+for collector in $COLLECTORS; do
+    cp "target/release/$collector" "$DIST_DIR/"
+done
 
 echo "Artifacts in $DIST_DIR"
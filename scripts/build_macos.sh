@@ -7,11 +7,12 @@ DIST_DIR="$ROOT_DIR/dist/macos"
 mkdir -p "$DIST_DIR"
 
 cd "$ROOT_DIR"
-cargo build --release -p aegis-core-server -p aegis-collector-cli -p aegis-verifier
+cargo build --release -p aegis-core-server -p aegis-collector-cli -p aegis-verifier -p aegis-config
 
 cp "target/release/aegis-core-server" "$DIST_DIR/"
 cp "target/release/aegis-collector-cli" "$DIST_DIR/"
 cp "target/release/aegis-verifier" "$DIST_DIR/"
+cp "target/release/aegis-config" "$DIST_DIR/"
 
 if command -v swift >/dev/null 2>&1; then
   cd "$ROOT_DIR/collectors/macos/native_recorder"
@@ -4,10 +4,11 @@ $DistDir = Join-Path $RootDir "dist/windows"
 New-Item -ItemType Directory -Force -Path $DistDir | Out-Null
 
 Set-Location $RootDir
-cargo build --release -p aegis-core-server -p aegis-collector-cli -p aegis-verifier
+cargo build --release -p aegis-core-server -p aegis-collector-cli -p aegis-verifier -p aegis-config
 
 Copy-Item -Force "target/release/aegis-core-server.exe" $DistDir
 Copy-Item -Force "target/release/aegis-collector-cli.exe" $DistDir
 Copy-Item -Force "target/release/aegis-verifier.exe" $DistDir
+Copy-Item -Force "target/release/aegis-config.exe" $DistDir
 
 Write-Host "Artifacts in $DistDir"
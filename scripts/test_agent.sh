@@ -0,0 +1,135 @@
+#!/bin/bash
+set -e
+
+echo "=== AEGISTRACE aegis-agent 监管测试 ==="
+echo ""
+
+ROOT_DIR="$(cd "$(dirname "$0")/.." && pwd)"
+cd "$ROOT_DIR"
+
+if [ "$(uname)" != "Linux" ]; then
+    echo "⚠ 非 Linux 平台，跳过测试"
+    exit 0
+fi
+
+cargo build -q -p aegis-agent -p aegis-core-server -p aegis-process-collector -p aegis-collector-cli -p aegis-verifier
+BIN="$ROOT_DIR/target/debug"
+
+TMP_DIR="$(mktemp -d)"
+trap 'kill $AGENT_PID 2>/dev/null || true; rm -rf "$TMP_DIR"' EXIT
+
+# 桩采集器：crasher 启动后立即失败；steady 带一个子进程长期运行
+mkdir -p "$TMP_DIR/bin"
+cat > "$TMP_DIR/bin/crasher" <<'STUB'
+#!/bin/sh
+echo "FAIL: boom" >&2
+exit 3
+STUB
+ln -s "$(command -v sleep)" "$TMP_DIR/bin/steady-helper"
+cat > "$TMP_DIR/bin/steady" <<STUB
+#!/bin/sh
+"$TMP_DIR/bin/steady-helper" 1000 &
+wait
+STUB
+chmod +x "$TMP_DIR/bin/crasher" "$TMP_DIR/bin/steady"
+
+write_config() {
+    cat > "$TMP_DIR/config.json" <<JSON
+{
+  "server": { "default_addr": "127.0.0.1:$1" },
+  "paths": { "default_save_dir": "$TMP_DIR/$2" },
+  "app": { "platform": "linux" },
+  "agent": {
+    "restart_backoff_ms": 200,
+    "restart_backoff_max_ms": 800,
+    "shutdown_timeout_ms": 2000,
+    "collectors": {
+      "focus": { "enabled": false },
+      "input": { "enabled": false },
+      "idle": { "enabled": false },
+      "process": { "enabled": true, "args": ["--backend", "proc", "--poll-ms", "200"] },
+      "crasher": { "enabled": true, "command": "$TMP_DIR/bin/crasher" },
+      "steady": { "enabled": true, "command": "$TMP_DIR/bin/steady" }
+    }
+  }
+}
+JSON
+}
+
+check_event() {
+    if ! grep "\"$1\"" "$EVENTS" | grep -qF "$2"; then
+        echo "❌ 缺少 $1（$2）"
+        cat "$EVENTS"
+        exit 1
+    fi
+}
+
+echo "1. 启动 agent，等待崩溃重启..."
+write_config 7898 run1
+"$BIN/aegis-agent" --config "$TMP_DIR/config.json" 2>"$TMP_DIR/agent.log" &
+AGENT_PID=$!
+sleep 3
+
+echo "2. SIGTERM 按顺序关闭..."
+kill -TERM "$AGENT_PID"
+wait "$AGENT_PID"
+AGENT_PID=""
+
+BUNDLE=$(ls -d "$TMP_DIR"/run1/Evidence_* | head -1)
+EVENTS="$BUNDLE/events.jsonl"
+check_event collector_started '"name":"process"'
+check_event collector_started '"name":"steady"'
+check_event collector_crashed '"last_error":"FAIL: boom"'
+check_event collector_crashed '"exit_code":3'
+check_event collector_restarted '"name":"crasher"'
+check_event collector_stopped '"name":"steady"'
+check_event session_stopped '"reason":"agent"'
+if grep -q '"collector_crashed".*"name":"steady"' "$EVENTS"; then
+    echo "❌ 正常运行的采集器被记录为崩溃"
+    exit 1
+fi
+# 退避：间隔依次翻倍，封顶 restart_backoff_max_ms
+DELAYS=$(grep '"collector_crashed"' "$EVENTS" | grep -o '"restart_in_ms":[0-9]*' | cut -d: -f2 | head -4 | tr '\n' ' ')
+if [ "$DELAYS" != "200 400 800 800 " ]; then
+    echo "❌ 重启退避不符合预期: $DELAYS"
+    exit 1
+fi
+if pgrep -f "$TMP_DIR/bin/steady-helper" >/dev/null; then
+    echo "❌ 采集器的子进程未随进程组退出"
+    exit 1
+fi
+STOPPED_SEQ=$(grep -n '"collector_stopped"' "$EVENTS" | tail -1 | cut -d: -f1)
+SESSION_SEQ=$(grep -n '"session_stopped"' "$EVENTS" | cut -d: -f1)
+if [ "$STOPPED_SEQ" -gt "$SESSION_SEQ" ]; then
+    echo "❌ 应先停止采集器再结束会话"
+    exit 1
+fi
+"$BIN/aegis-verifier" verify "$BUNDLE"
+echo "✓ 启动、崩溃退避重启与有序关闭均已记录"
+
+echo "3. 外部结束会话时 agent 随之退出..."
+write_config 7899 run2
+"$BIN/aegis-agent" --config "$TMP_DIR/config.json" 2>>"$TMP_DIR/agent.log" &
+AGENT_PID=$!
+sleep 1.5
+"$BIN/aegis-collector-cli" --config "$TMP_DIR/config.json" stop "test"
+for _ in $(seq 1 50); do
+    kill -0 "$AGENT_PID" 2>/dev/null || break
+    sleep 0.1
+done
+if kill -0 "$AGENT_PID" 2>/dev/null; then
+    echo "❌ 会话结束后 agent 未退出"
+    exit 1
+fi
+wait "$AGENT_PID"
+AGENT_PID=""
+if pgrep -f "$TMP_DIR/bin/steady-helper" >/dev/null; then
+    echo "❌ 会话结束后采集器仍在运行"
+    exit 1
+fi
+BUNDLE=$(ls -d "$TMP_DIR"/run2/Evidence_* | head -1)
+"$BIN/aegis-verifier" verify "$BUNDLE"
+echo "✓ 会话结束后采集器全部关闭"
+
+echo ""
+echo "=== aegis-agent 监管测试通过 ==="
@@ -0,0 +1,140 @@
+#!/bin/bash
+set -e
+
+echo "=== AEGISTRACE 证据包目录锁测试 ==="
+echo ""
+
+ROOT_DIR="$(cd "$(dirname "$0")/.." && pwd)"
+cd "$ROOT_DIR"
+
+cargo build -q -p aegis-core-server -p aegis-collector-cli -p aegis-verifier
+BIN="$ROOT_DIR/target/debug"
+
+export AEGIS_CORE_ADDR="127.0.0.1:7907"
+TMP_DIR="$(mktemp -d)"
+trap 'kill -9 $SERVER_PID 2>/dev/null || true; rm -rf "$TMP_DIR"' EXIT
+
+echo "1. 会话进行中..."
+"$BIN/aegis-core-server" linux test "$TMP_DIR/live" 2> /dev/null &
+SERVER_PID=$!
+sleep 1
+BUNDLE=$(ls -d "$TMP_DIR"/live/Evidence_* | head -1)
+LOCK="$BUNDLE/.aegis.lock"
+if [ ! -f "$LOCK" ] || ! grep -q "\"pid\": $SERVER_PID" "$LOCK" || ! grep -q "\"host\": \"$(hostname)\"" "$LOCK"; then
+    echo "❌ 写入中的证据包应有记录 pid 与主机名的 .aegis.lock"
+    cat "$LOCK" 2>/dev/null
+    exit 1
+fi
+if ! "$BIN/aegis-verifier" lock "$BUNDLE" | grep -q "^locked by pid $SERVER_PID on $(hostname)"; then
+    echo "❌ lock 命令应报告持有锁的进程"
+    exit 1
+fi
+"$BIN/aegis-verifier" lock "$BUNDLE" --clear-stale > /dev/null
+if [ ! -f "$LOCK" ]; then
+    echo "❌ --clear-stale 不应删除存活进程持有的锁"
+    exit 1
+fi
+if "$BIN/aegis-verifier" verify "$BUNDLE" 2> "$TMP_DIR/verify.txt" \
+    || ! grep -q "bundle is still being written: pid $SERVER_PID" "$TMP_DIR/verify.txt"; then
+    echo "❌ 写入中的证据包应拒绝校验"
+    cat "$TMP_DIR/verify.txt"
+    exit 1
+fi
+echo "✓ 会话期间持有锁，校验与清理均被拒绝"
+
+echo "2. 正常结束会话..."
+"$BIN/aegis-collector-cli" stop "done"
+wait "$SERVER_PID"
+if [ -e "$LOCK" ] || ls -A "$BUNDLE" | grep -q "^\.aegis"; then
+    echo "❌ 会话结束后应释放锁"
+    ls -A "$BUNDLE"
+    exit 1
+fi
+"$BIN/aegis-verifier" verify "$BUNDLE" > /dev/null
+if ! "$BIN/aegis-verifier" lock "$BUNDLE" | grep -q "^unlocked"; then
+    echo "❌ 结束的证据包应未加锁"
+    exit 1
+fi
+echo "✓ 结束后锁已释放，校验通过"
+
+echo "3. core server 崩溃..."
+"$BIN/aegis-core-server" linux test "$TMP_DIR/crashed" 2> /dev/null &
+SERVER_PID=$!
+sleep 1
+"$BIN/aegis-collector-cli" focus org.example.App "Example"
+kill -9 "$SERVER_PID"
+wait "$SERVER_PID" 2>/dev/null || true
+BUNDLE=$(ls -d "$TMP_DIR"/crashed/Evidence_* | head -1)
+if ! "$BIN/aegis-verifier" lock "$BUNDLE" | grep -q "^stale lock from pid $SERVER_PID"; then
+    echo "❌ 进程已退出的锁应判定为过期"
+    "$BIN/aegis-verifier" lock "$BUNDLE"
+    exit 1
+fi
+if "$BIN/aegis-verifier" verify "$BUNDLE" 2> "$TMP_DIR/verify.txt" \
+    || ! grep -q "session did not finish: stale lock from pid $SERVER_PID" "$TMP_DIR/verify.txt"; then
+    echo "❌ 崩溃的会话应报告过期锁"
+    cat "$TMP_DIR/verify.txt"
+    exit 1
+fi
+"$BIN/aegis-verifier" lock "$BUNDLE" --clear-stale | grep -q "^removed stale lock"
+if [ -e "$BUNDLE/.aegis.lock" ]; then
+    echo "❌ --clear-stale 应删除过期锁"
+    exit 1
+fi
+echo "✓ 崩溃后留下的锁被识别为过期，可以清理"
+
+echo "4. 其他主机持有的锁..."
+cat > "$BUNDLE/.aegis.lock" <<EOF
+{"pid": $SERVER_PID, "host": "elsewhere.invalid", "session_id": "x", "acquired_at": "2026-01-01T00:00:00Z"}
+EOF
+"$BIN/aegis-verifier" lock "$BUNDLE" --clear-stale > "$TMP_DIR/lock.txt"
+if ! grep -q "^locked by pid $SERVER_PID on elsewhere.invalid" "$TMP_DIR/lock.txt" || [ ! -f "$BUNDLE/.aegis.lock" ]; then
+    echo "❌ 无法确认其他主机上的进程状态，应视为仍被持有"
+    cat "$TMP_DIR/lock.txt"
+    exit 1
+fi
+echo "✓ 其他主机的锁不会被当作过期清理"
+
+echo "5. 继续写入未完成的证据包..."
+if "$BIN/aegis-core-server" linux test --resume "$BUNDLE" 2> "$TMP_DIR/resume.txt" \
+    || ! grep -q "is locked by pid $SERVER_PID on elsewhere.invalid" "$TMP_DIR/resume.txt"; then
+    echo "❌ 被其他主机持有的证据包应拒绝继续写入"
+    cat "$TMP_DIR/resume.txt"
+    exit 1
+fi
+"$BIN/aegis-core-server" linux test "$TMP_DIR/resumed" 2> /dev/null &
+SERVER_PID=$!
+sleep 1
+"$BIN/aegis-collector-cli" focus org.example.Before "Before"
+kill -9 "$SERVER_PID"
+wait "$SERVER_PID" 2>/dev/null || true
+CRASHED_PID=$SERVER_PID
+BUNDLE=$(ls -d "$TMP_DIR"/resumed/Evidence_* | head -1)
+"$BIN/aegis-core-server" linux test --resume "$BUNDLE" 2> /dev/null &
+SERVER_PID=$!
+sleep 1
+if ! grep -q "\"pid\": $SERVER_PID" "$BUNDLE/.aegis.lock"; then
+    echo "❌ 继续写入时应替换 pid $CRASHED_PID 留下的过期锁"
+    cat "$BUNDLE/.aegis.lock"
+    exit 1
+fi
+if "$BIN/aegis-core-server" linux test --resume "$BUNDLE" 127.0.0.1:7908 2> "$TMP_DIR/resume.txt" \
+    || ! grep -q "is locked by pid $SERVER_PID" "$TMP_DIR/resume.txt"; then
+    echo "❌ 存活进程正在写入的证据包应拒绝第二个写入方"
+    cat "$TMP_DIR/resume.txt"
+    exit 1
+fi
+"$BIN/aegis-collector-cli" focus org.example.After "After"
+"$BIN/aegis-collector-cli" stop "done"
+wait "$SERVER_PID"
+if ! grep -q '"type":"session_resumed"' "$BUNDLE/events.jsonl" \
+    || [ "$(grep -c '"type":"session_started"' "$BUNDLE/events.jsonl")" -ne 1 ]; then
+    echo "❌ 应在原哈希链上记录 session_resumed"
+    cat "$BUNDLE/events.jsonl"
+    exit 1
+fi
+"$BIN/aegis-verifier" verify "$BUNDLE" > /dev/null
+echo "✓ 过期锁被替换后继续写入，第二个写入方被拒绝，校验通过"
+
+echo ""
+echo "=== 证据包目录锁测试通过 ==="
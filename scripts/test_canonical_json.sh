@@ -0,0 +1,49 @@
+#!/bin/bash
+set -e
+
+echo "=== AEGISTRACE 规范化 JSON（RFC 8785）测试 ==="
+echo ""
+
+ROOT_DIR="$(cd "$(dirname "$0")/.." && pwd)"
+cd "$ROOT_DIR"
+
+cargo build -q -p aegis-verifier
+BIN="$ROOT_DIR/target/debug"
+
+TMP_DIR="$(mktemp -d)"
+trap 'rm -rf "$TMP_DIR"' EXIT
+
+echo "1. RFC 8785 测试向量（schema_version 2）..."
+for input in spec/jcs/*.json; do
+    name=$(basename "$input" .json)
+    "$BIN/aegis-verifier" canonical "$input" --schema 2 > "$TMP_DIR/$name.out"
+    if ! cmp -s "$TMP_DIR/$name.out" "spec/jcs/$name.expected"; then
+        echo "❌ $name 输出与 RFC 8785 不一致"
+        echo "期望: $(cat "spec/jcs/$name.expected")"
+        echo "实际: $(cat "$TMP_DIR/$name.out")"
+        exit 1
+    fi
+    echo "✓ $name"
+done
+
+echo "2. schema_version 1 保持原算法..."
+# v1 按 UTF-8 字节序排序键（U+FB33 在 U+1F600 之前），数字沿用 serde_json 格式（-0.0、1e-6）
+"$BIN/aegis-verifier" canonical spec/jcs/rfc8785_sorting.json --schema 1 > "$TMP_DIR/v1.out"
+"$BIN/aegis-verifier" canonical spec/jcs/rfc8785_numbers.json --schema 1 >> "$TMP_DIR/v1.out"
+if ! grep -qF '"Hebrew Letter Dalet With Dagesh","😀"' "$TMP_DIR/v1.out" \
+    || ! grep -qF '[0.0,-0.0,5e-324,' "$TMP_DIR/v1.out" \
+    || ! grep -qF ',1e-6,' "$TMP_DIR/v1.out"; then
+    echo "❌ schema_version 1 的规范化结果不应改变"
+    cat "$TMP_DIR/v1.out"
+    exit 1
+fi
+if "$BIN/aegis-verifier" canonical spec/jcs/rfc8785_sample.json --schema 3 2> "$TMP_DIR/err.txt" \
+    || ! grep -q "unsupported schema_version 3" "$TMP_DIR/err.txt"; then
+    echo "❌ 未知的 schema_version 应报错"
+    cat "$TMP_DIR/err.txt"
+    exit 1
+fi
+echo "✓ v1 输出不变，未知版本被拒绝"
+
+echo ""
+echo "=== 规范化 JSON 测试通过 ==="
@@ -0,0 +1,123 @@
+#!/bin/bash
+set -e
+
+echo "=== AEGISTRACE 采集器客户端重试与暂存测试 ==="
+echo ""
+
+ROOT_DIR="$(cd "$(dirname "$0")/.." && pwd)"
+cd "$ROOT_DIR"
+
+cargo build -q -p aegis-core-server -p aegis-collector-cli -p aegis-verifier
+BIN="$ROOT_DIR/target/debug"
+
+export AEGIS_CORE_ADDR="127.0.0.1:7902"
+TMP_DIR="$(mktemp -d)"
+SPOOL="$TMP_DIR/spool"
+trap 'kill $SERVER_PID 2>/dev/null || true; rm -rf "$TMP_DIR"' EXIT
+
+cli() {
+    "$BIN/aegis-collector-cli" --set "client.spool_dir=\"$SPOOL\"" \
+        --set client.retry_attempts=2 --set client.retry_backoff_ms=100 "$@"
+}
+
+echo "1. core server 未启动时发送事件..."
+cli focus first.app "First" 2> "$TMP_DIR/err.txt"
+cli focus second.app "Second" 2>> "$TMP_DIR/err.txt"
+if [ "$(grep -c "spooled" "$TMP_DIR/err.txt")" -ne 2 ] || [ "$(ls "$SPOOL/cli"/*.json | wc -l)" -ne 2 ]; then
+    echo "❌ 事件应写入暂存目录"
+    cat "$TMP_DIR/err.txt"
+    exit 1
+fi
+if cli stop "offline" 2>/dev/null || ls "$SPOOL/cli"/*.json | xargs grep -q '"stop"'; then
+    echo "❌ stop 不应成功，也不应被暂存"
+    exit 1
+fi
+echo "✓ 2 条事件已暂存，stop 未暂存"
+
+echo "2. core server 恢复后发送新事件，先补发暂存事件..."
+sleep 1.1
+"$BIN/aegis-core-server" linux test "$TMP_DIR/out" &
+SERVER_PID=$!
+sleep 1
+cli focus third.app "Third"
+if ls "$SPOOL/cli"/*.json >/dev/null 2>&1; then
+    echo "❌ 补发后暂存目录应为空"
+    exit 1
+fi
+
+echo "3. 重试期间 core server 启动..."
+cli stop "restart"
+wait "$SERVER_PID"
+(sleep 0.5; exec "$BIN/aegis-core-server" linux test "$TMP_DIR/out2") &
+SERVER_PID=$!
+"$BIN/aegis-collector-cli" --set "client.spool_dir=\"$SPOOL\"" \
+    --set client.retry_attempts=10 --set client.retry_backoff_ms=200 focus retried.app "Retried"
+if ls "$SPOOL/cli"/*.json >/dev/null 2>&1; then
+    echo "❌ 重试成功的事件不应暂存"
+    exit 1
+fi
+echo "✓ 退避重试期间 core server 启动，事件直接送达"
+
+echo "4. 丢失应答后重发同一 msg_id..."
+python3 - "$AEGIS_CORE_ADDR" <<'PY'
+import socket, sys
+host, port = sys.argv[1].rsplit(":", 1)
+line = b'{"type":"note","payload":{"n":1},"msg_id":"test-1"}\n'
+
+# 第一次发送后不读应答就断开，模拟应答丢失
+sock = socket.create_connection((host, int(port)))
+sock.sendall(line)
+sock.close()
+
+replies = []
+for _ in range(2):
+    sock = socket.create_connection((host, int(port)))
+    sock.sendall(line)
+    replies.append(sock.makefile().readline().strip())
+    sock.close()
+assert replies[0].startswith("OK ") and replies[0] == replies[1], replies
+PY
+cli stop "done"
+wait "$SERVER_PID"
+BUNDLE2=$(ls -d "$TMP_DIR"/out2/Evidence_* | head -1)
+if [ "$(grep -c '"type":"note"' "$BUNDLE2/events.jsonl")" -ne 1 ]; then
+    echo "❌ 同一 msg_id 只应写入一次"
+    grep '"type":"note"' "$BUNDLE2/events.jsonl"
+    exit 1
+fi
+echo "✓ 重发的消息得到原来的 seq，事件只写入一次"
+
+echo "5. 检查补发事件..."
+BUNDLE=$(ls -d "$TMP_DIR"/out/Evidence_* | head -1)
+EVENTS="$BUNDLE/events.jsonl"
+ORDER=$(grep app_focus_changed "$EVENTS" | grep -o '"app_id":"[a-z]*' | cut -d'"' -f4 | tr '\n' ' ')
+if [ "$ORDER" != "first second third " ]; then
+    echo "❌ 补发顺序错误: $ORDER"
+    cat "$EVENTS"
+    exit 1
+fi
+if [ "$(grep -c '"delayed":true' "$EVENTS")" -ne 2 ] || grep '"third.app"' "$EVENTS" | grep -q delayed; then
+    echo "❌ 只有补发的事件应标记 delayed"
+    cat "$EVENTS"
+    exit 1
+fi
+python3 - "$EVENTS" <<'EOF'
+import json, sys
+from datetime import datetime
+for line in open(sys.argv[1]):
+    event = json.loads(line)
+    payload = event["payload"]
+    if payload.get("delayed"):
+        observed = datetime.fromisoformat(event["observed_at"].replace("Z", "+00:00"))
+        ts = datetime.fromisoformat(event["ts"].replace("Z", "+00:00"))
+        if (ts - observed).total_seconds() < 1:
+            sys.exit(f"observed_at should be the original capture time: {line}")
+EOF
+echo "✓ 补发事件按原顺序写入，带 delayed，observed_at 为原始采集时间"
+
+echo "6. 验证证据包..."
+"$BIN/aegis-verifier" verify "$BUNDLE"
+"$BIN/aegis-verifier" verify "$BUNDLE2"
+
+echo ""
+echo "=== 采集器客户端重试与暂存测试通过 ==="
@@ -0,0 +1,120 @@
+#!/bin/bash
+set -e
+
+echo "=== AEGISTRACE Linux 剪贴板采集测试 ==="
+echo ""
+
+ROOT_DIR="$(cd "$(dirname "$0")/.." && pwd)"
+cd "$ROOT_DIR"
+
+if [ "$(uname)" != "Linux" ]; then
+    echo "⚠ 非 Linux 平台，跳过测试"
+    exit 0
+fi
+
+cargo build -q -p aegis-core-server -p aegis-clipboard-collector -p aegis-collector-cli -p aegis-verifier
+BIN="$ROOT_DIR/target/debug"
+
+export AEGIS_CORE_ADDR="127.0.0.1:7896"
+TMP_DIR="$(mktemp -d)"
+trap 'kill $XVFB_PID $SERVER_PID $COLLECTOR_PID 2>/dev/null || true; rm -rf "$TMP_DIR"' EXIT
+
+if command -v Xvfb >/dev/null 2>&1 && command -v xclip >/dev/null 2>&1 && command -v xprop >/dev/null 2>&1; then
+    echo "使用 Xvfb + xclip"
+    export DISPLAY=":97"
+    Xvfb "$DISPLAY" -screen 0 1280x720x24 >/dev/null 2>&1 &
+    XVFB_PID=$!
+    sleep 1
+    ROOT_ID=$(xwininfo -root | awk '/Window id:/ {print $4}')
+    xprop -root -f WM_CLASS 8s -set WM_CLASS "Editor"
+    xprop -root -f _NET_ACTIVE_WINDOW 32x -set _NET_ACTIVE_WINDOW "$ROOT_ID"
+    set_clipboard() {
+        printf '%s' "$2" | xclip -selection clipboard -t "$1"
+    }
+else
+    # 没有 X 环境时用桩程序模拟 xclip / xprop
+    echo "未找到 Xvfb/xclip，使用桩程序"
+    export DISPLAY=":stub"
+    export FAKE_CLIP="$TMP_DIR/clip"
+    mkdir -p "$TMP_DIR/bin" "$FAKE_CLIP"
+    cat > "$TMP_DIR/bin/xclip" <<'STUB'
+#!/bin/sh
+# xclip -selection clipboard -o -t <target>
+for target; do :; done
+case "$target" in
+    TARGETS) printf 'TARGETS\nTIMESTAMP\n'; cat "$FAKE_CLIP/type" 2>/dev/null ;;
+    TIMESTAMP) cat "$FAKE_CLIP/stamp" 2>/dev/null ;;
+    *) [ "$(cat "$FAKE_CLIP/type" 2>/dev/null)" = "$target" ] || exit 1; cat "$FAKE_CLIP/data" ;;
+esac
+STUB
+    cat > "$TMP_DIR/bin/xprop" <<'STUB'
+#!/bin/sh
+case "$1" in
+    -root) echo "_NET_ACTIVE_WINDOW(WINDOW): window id # 0x1200007" ;;
+    -id) echo 'WM_CLASS(STRING) = "editor", "Editor"' ;;
+esac
+STUB
+    chmod +x "$TMP_DIR/bin/xclip" "$TMP_DIR/bin/xprop"
+    export PATH="$TMP_DIR/bin:$PATH"
+    set_clipboard() {
+        printf '%s' "$2" > "$FAKE_CLIP/data"
+        echo "$1" > "$FAKE_CLIP/type"
+        date +%s%N > "$FAKE_CLIP/stamp"
+    }
+fi
+
+"$BIN/aegis-core-server" linux test "$TMP_DIR" &
+SERVER_PID=$!
+sleep 1
+
+"$BIN/aegis-clipboard-collector" --backend x11 --poll-ms 100 --store text &
+COLLECTOR_PID=$!
+sleep 0.5
+
+echo "1. 复制内容..."
+set_clipboard UTF8_STRING "top-secret-clipboard"
+sleep 0.5
+set_clipboard image/png "PNGDATA"
+sleep 0.5
+
+kill "$COLLECTOR_PID"
+"$BIN/aegis-collector-cli" stop "test"
+wait "$SERVER_PID"
+
+BUNDLE=$(ls -d "$TMP_DIR"/Evidence_* | head -1)
+EVENTS="$BUNDLE/events.jsonl"
+
+echo "2. 检查 clipboard_changed..."
+EXPECTED_HASH=$(printf '%s' "top-secret-clipboard" | sha256sum | cut -d' ' -f1)
+COUNT=$(grep -c '"clipboard_changed"' "$EVENTS" || true)
+if [ "$COUNT" -ne 2 ]; then
+    echo "❌ 期望 2 条 clipboard_changed，实际 $COUNT"
+    cat "$EVENTS"
+    exit 1
+fi
+for expected in "\"sha256\":\"$EXPECTED_HASH\"" '"size":20' '"source_app":"Editor"' '"content_type":"image/png"'; do
+    if ! grep '"clipboard_changed"' "$EVENTS" | grep -qF "$expected"; then
+        echo "❌ 缺少字段: $expected"
+        cat "$EVENTS"
+        exit 1
+    fi
+done
+if grep -q "top-secret-clipboard" "$EVENTS"; then
+    echo "❌ 事件中出现明文"
+    exit 1
+fi
+echo "✓ 只记录类型、大小与哈希"
+
+echo "3. 检查按策略保存的内容..."
+STORED=$(ls "$BUNDLE/files/clipboard" 2>/dev/null | wc -l)
+if [ "$STORED" -ne 1 ] || [ "$(cat "$BUNDLE"/files/clipboard/*.txt)" != "top-secret-clipboard" ]; then
+    echo "❌ --store text 应只保存文本内容（实际 $STORED 个文件）"
+    exit 1
+fi
+echo "✓ 仅文本内容保存到 files/clipboard/"
+
+echo "4. 验证证据包..."
+"$BIN/aegis-verifier" verify "$BUNDLE"
+
+echo ""
+echo "=== 剪贴板采集测试通过 ==="
@@ -0,0 +1,137 @@
+#!/bin/bash
+set -e
+
+echo "=== AEGISTRACE 事件时间精度与单调性测试 ==="
+echo ""
+
+ROOT_DIR="$(cd "$(dirname "$0")/.." && pwd)"
+cd "$ROOT_DIR"
+
+cargo build -q -p aegis-core-server -p aegis-collector-cli -p aegis-verifier
+BIN="$ROOT_DIR/target/debug"
+
+export AEGIS_CORE_ADDR="127.0.0.1:7904"
+TMP_DIR="$(mktemp -d)"
+trap 'kill $SERVER_PID 2>/dev/null || true; rm -rf "$TMP_DIR"' EXIT
+
+"$BIN/aegis-core-server" linux test "$TMP_DIR/out" &
+SERVER_PID=$!
+sleep 1
+
+echo "1. 同一秒内写入多条事件..."
+for i in $(seq 1 20); do
+    echo "{\"type\":\"note\",\"payload\":{\"n\":$i}}"
+done | "$BIN/aegis-collector-cli" send --stdin > /dev/null
+"$BIN/aegis-collector-cli" stop "done"
+wait "$SERVER_PID"
+
+BUNDLE=$(ls -d "$TMP_DIR"/out/Evidence_* | head -1)
+python3 - "$BUNDLE/events.jsonl" <<'PY'
+import json, re, sys
+events = [json.loads(line) for line in open(sys.argv[1])]
+for event in events:
+    if not re.fullmatch(r"\d{4}-\d\d-\d\dT\d\d:\d\d:\d\d\.\d{6}Z", event["ts"]):
+        sys.exit(f"ts should have microsecond precision: {event['ts']}")
+stamps = [(event["ts"], event["mono_us"]) for event in events]
+if len(set(stamps)) != len(stamps):
+    sys.exit("events in the same second should still have distinct times")
+monos = [event["mono_us"] for event in events]
+if monos != sorted(monos):
+    sys.exit(f"mono_us should not decrease: {monos}")
+if any(event["type"] == "clock_jump" for event in events):
+    sys.exit("no clock_jump expected without a clock change")
+PY
+"$BIN/aegis-verifier" verify "$BUNDLE" > "$TMP_DIR/verify.txt" 2>&1
+if grep -q WARN "$TMP_DIR/verify.txt"; then
+    echo "❌ 时钟未变化时不应有警告"
+    cat "$TMP_DIR/verify.txt"
+    exit 1
+fi
+echo "✓ ts 精确到微秒，mono_us 单调递增，校验通过"
+
+# 复制证据包并按给定方式修改事件，重新计算哈希链与 manifest，
+# 模拟写入时墙上时钟发生跳变
+rewrite() {
+    local name="$1" mode="$2"
+    rm -rf "$TMP_DIR/$name"
+    cp -r "$BUNDLE" "$TMP_DIR/$name"
+    python3 - "$TMP_DIR/$name" "$mode" <<'PY'
+import hashlib, json, sys
+from datetime import datetime, timedelta
+
+bundle, mode = sys.argv[1], sys.argv[2]
+events = [json.loads(line) for line in open(f"{bundle}/events.jsonl")]
+
+def shift(ts, seconds):
+    value = datetime.fromisoformat(ts.replace("Z", "+00:00")) + timedelta(seconds=seconds)
+    return value.strftime("%Y-%m-%dT%H:%M:%S.%fZ")
+
+# 从第 10 条事件起墙上时钟跳变
+at = 9
+if mode in ("back_with_jump", "back_without_jump"):
+    for event in events[at:]:
+        event["ts"] = shift(event["ts"], -5)
+    if mode == "back_with_jump":
+        jump = dict(events[at], type="clock_jump",
+                    payload={"jump_ms": -5000, "skew_ms": -5000, "previous_ts": events[at - 1]["ts"]})
+        events.insert(at, jump)
+elif mode == "ahead_without_jump":
+    for event in events[at:]:
+        event["ts"] = shift(event["ts"], 5)
+elif mode == "mono_back":
+    events[at]["mono_us"], events[at + 1]["mono_us"] = events[at + 1]["mono_us"], events[at]["mono_us"]
+
+def canonical(value):
+    return json.dumps(value, sort_keys=True, separators=(",", ":"), ensure_ascii=False)
+
+prev = ""
+lines = []
+for seq, event in enumerate(events, 1):
+    event["seq"] = seq
+    event["prev_hash"] = prev
+    fields = {key: value for key, value in event.items() if key != "hash"}
+    event["hash"] = hashlib.sha256(canonical(fields).encode()).hexdigest()
+    prev = event["hash"]
+    lines.append(json.dumps(event, separators=(",", ":"), ensure_ascii=False) + "\n")
+data = "".join(lines).encode()
+open(f"{bundle}/events.jsonl", "wb").write(data)
+
+manifest = json.load(open(f"{bundle}/manifest.json"))
+manifest["events_hash"] = hashlib.sha256(data).hexdigest()
+manifest["final_hash"] = prev
+for entry in manifest["files"]:
+    if entry["rel_path"] == "events.jsonl":
+        entry["hash"] = manifest["events_hash"]
+json.dump(manifest, open(f"{bundle}/manifest.json", "w"), indent=2)
+PY
+}
+
+echo "2. 墙上时钟回拨并记录 clock_jump..."
+rewrite back_with_jump back_with_jump
+"$BIN/aegis-verifier" verify "$TMP_DIR/back_with_jump" > "$TMP_DIR/verify.txt" 2>&1
+if ! grep -q "^PASS" "$TMP_DIR/verify.txt" \
+    || ! grep -q "WARN: line 10: clock_jump of -5000ms" "$TMP_DIR/verify.txt"; then
+    echo "❌ 有 clock_jump 的回拨应通过并给出警告"
+    cat "$TMP_DIR/verify.txt"
+    exit 1
+fi
+echo "✓ clock_jump 处允许 ts 倒退，并输出 WARN"
+
+echo "3. 没有 clock_jump 的时间异常..."
+for case in "back_without_jump:ts goes backwards at line 10 without clock_jump" \
+    "ahead_without_jump:ts moved 5000ms against mono_us at line 10 without clock_jump" \
+    "mono_back:mono_us goes backwards at line 11"; do
+    mode="${case%%:*}"
+    expected="${case#*:}"
+    rewrite "$mode" "$mode"
+    if "$BIN/aegis-verifier" verify "$TMP_DIR/$mode" > "$TMP_DIR/verify.txt" 2>&1 \
+        || ! grep -qF "$expected" "$TMP_DIR/verify.txt"; then
+        echo "❌ $mode 应校验失败: $expected"
+        cat "$TMP_DIR/verify.txt"
+        exit 1
+    fi
+done
+echo "✓ ts 倒退、ts 与 mono_us 偏离、mono_us 倒退均校验失败"
+
+echo ""
+echo "=== 事件时间精度与单调性测试通过 ==="
@@ -0,0 +1,109 @@
+#!/bin/bash
+set -e
+
+echo "=== AEGISTRACE 批量发送（send --stdin）测试 ==="
+echo ""
+
+ROOT_DIR="$(cd "$(dirname "$0")/.." && pwd)"
+cd "$ROOT_DIR"
+
+cargo build -q -p aegis-core-server -p aegis-collector-cli -p aegis-verifier
+BIN="$ROOT_DIR/target/debug"
+
+export AEGIS_CORE_ADDR="127.0.0.1:7901"
+TMP_DIR="$(mktemp -d)"
+trap 'kill $SERVER_PID 2>/dev/null || true; rm -rf "$TMP_DIR"' EXIT
+SPOOL="$TMP_DIR/spool"
+
+cli() {
+    "$BIN/aegis-collector-cli" --set "client.spool_dir=\"$SPOOL\"" \
+        --set client.retry_attempts=2 --set client.retry_backoff_ms=100 "$@"
+}
+
+"$BIN/aegis-core-server" linux test "$TMP_DIR/out" &
+SERVER_PID=$!
+sleep 1
+
+echo "1. 生成 500 行输入（JSON 与子命令混合，中间夹带错误行）..."
+INPUT="$TMP_DIR/input.txt"
+echo "# 注释与空行会被跳过" > "$INPUT"
+echo "" >> "$INPUT"
+for i in $(seq 1 250); do
+    echo "{\"type\":\"note\",\"payload\":{\"n\":$i}}" >> "$INPUT"
+    echo "focus org.example.App$i 'Example App' \"Window \\\"$i\\\"\"" >> "$INPUT"
+    if [ "$i" -eq 100 ]; then
+        echo "input 1000 not-a-number 0 0" >> "$INPUT"
+        echo "{\"type\":\"file_added\",\"payload\":{\"rel_path\":\"../x\",\"kind\":\"x\",\"source_path\":\"/etc/hostname\"}}" >> "$INPUT"
+        echo "{broken json" >> "$INPUT"
+    fi
+done
+
+set +e
+cli send --stdin < "$INPUT" > "$TMP_DIR/out.txt" 2> "$TMP_DIR/err.txt"
+STATUS=$?
+set -e
+
+echo "2. 检查逐行结果..."
+if [ "$STATUS" -eq 0 ]; then
+    echo "❌ 存在被拒绝的行时应返回非零退出码"
+    exit 1
+fi
+OK_COUNT=$(grep -c " OK " "$TMP_DIR/out.txt" || true)
+ERR_COUNT=$(grep -c " ERR " "$TMP_DIR/out.txt" || true)
+if [ "$OK_COUNT" -ne 500 ] || [ "$ERR_COUNT" -ne 3 ]; then
+    echo "❌ 期望 500 行 OK、3 行 ERR，实际 $OK_COUNT / $ERR_COUNT"
+    cat "$TMP_DIR/out.txt" "$TMP_DIR/err.txt"
+    exit 1
+fi
+# session_started 为 seq 1，接受的消息依次为 2..501
+if [ "$(grep " OK " "$TMP_DIR/out.txt" | awk '{print $3}' | tr '\n' ' ')" != "$(seq -s ' ' 2 501) " ]; then
+    echo "❌ 返回的 seq 不连续"
+    exit 1
+fi
+if ! grep -q "^3 OK 2$" "$TMP_DIR/out.txt"; then
+    echo "❌ 行号应对应输入文件行号"
+    head -3 "$TMP_DIR/out.txt"
+    exit 1
+fi
+for line in "^203 ERR invalid key_count" "^204 ERR .*must be relative" "^205 ERR invalid JSON"; do
+    if ! grep -q "$line" "$TMP_DIR/out.txt"; then
+        echo "❌ 缺少错误行: $line"
+        grep " ERR " "$TMP_DIR/out.txt"
+        exit 1
+    fi
+done
+echo "✓ 每行都有结果，错误行不影响后续消息"
+
+echo "3. 检查引号解析..."
+BUNDLE=$(ls -d "$TMP_DIR"/out/Evidence_* | head -1)
+if ! grep -qF '"app_name":"Example App","window_title":"Window \"7\""' "$BUNDLE/events.jsonl"; then
+    echo "❌ 子命令参数的引号解析不正确"
+    grep -m1 app_focus_changed "$BUNDLE/events.jsonl"
+    exit 1
+fi
+echo "✓ 引号参数解析正确"
+if grep '"type":"note"' "$BUNDLE/events.jsonl" | grep -qv '"observed_at":'; then
+    echo "❌ 批量发送的事件缺少 observed_at"
+    exit 1
+fi
+echo "✓ 批量发送的事件带有 observed_at"
+
+echo "4. 停止会话后剩余行与单条发送一样重试并进入 spool..."
+printf 'stop batch\nfocus late.app Late\n' | cli send --stdin > "$TMP_DIR/stop.txt" 2>/dev/null || true
+wait "$SERVER_PID"
+if ! grep -q "^1 OK$" "$TMP_DIR/stop.txt" || ! grep -q "^2 SPOOLED$" "$TMP_DIR/stop.txt"; then
+    echo "❌ stop 之后的行应进入 spool"
+    cat "$TMP_DIR/stop.txt"
+    exit 1
+fi
+if [ "$(ls "$SPOOL/cli"/*.json 2>/dev/null | wc -l)" -ne 1 ] || grep -q "late.app" "$BUNDLE/events.jsonl"; then
+    echo "❌ stop 之后的行应只在 spool 中，而不在证据包中"
+    exit 1
+fi
+echo "✓ stop 后的行进入 spool，未写入证据包"
+
+echo "5. 验证证据包..."
+"$BIN/aegis-verifier" verify "$BUNDLE"
+
+echo ""
+echo "=== 批量发送测试通过 ==="
@@ -0,0 +1,149 @@
+#!/bin/bash
+set -e
+
+echo "=== AEGISTRACE Linux DNS 域名采集测试 ==="
+echo ""
+
+ROOT_DIR="$(cd "$(dirname "$0")/.." && pwd)"
+cd "$ROOT_DIR"
+
+if [ "$(uname)" != "Linux" ]; then
+    echo "⚠ 非 Linux 平台，跳过测试"
+    exit 0
+fi
+if ! command -v python3 >/dev/null 2>&1; then
+    echo "⚠ 未找到 python3，跳过测试"
+    exit 0
+fi
+
+cargo build -q -p aegis-core-server -p aegis-dns-collector -p aegis-collector-cli -p aegis-verifier
+BIN="$ROOT_DIR/target/debug"
+
+export AEGIS_CORE_ADDR="127.0.0.1:7894"
+STUB_ADDR="127.0.0.1:5394"
+UPSTREAM_PORT=5395
+BUSY_STUB_ADDR="127.0.0.1:5396"
+SILENT_PORT=5397
+TMP_DIR="$(mktemp -d)"
+trap 'kill $SERVER_PID $COLLECTOR_PID $UPSTREAM_PID $BUSY_PID $SILENT_PID 2>/dev/null || true; rm -rf "$TMP_DIR"' EXIT
+
+# 假上游：对任何查询回答 A 192.0.2.1
+python3 - "$UPSTREAM_PORT" <<'PY' &
+import socket, sys
+sock = socket.socket(socket.AF_INET, socket.SOCK_DGRAM)
+sock.bind(("127.0.0.1", int(sys.argv[1])))
+while True:
+    query, addr = sock.recvfrom(4096)
+    end = 12
+    while query[end] != 0:
+        end += query[end] + 1
+    question = query[12:end + 5]
+    header = query[:2] + b"\x81\x80\x00\x01\x00\x01\x00\x00\x00\x00"
+    answer = b"\xc0\x0c\x00\x01\x00\x01\x00\x00\x00\x3c\x00\x04\xc0\x00\x02\x01"
+    sock.sendto(header + question + answer, addr)
+PY
+UPSTREAM_PID=$!
+
+"$BIN/aegis-core-server" linux test "$TMP_DIR" &
+SERVER_PID=$!
+sleep 1
+
+"$BIN/aegis-dns-collector" --listen "$STUB_ADDR" --upstream "127.0.0.1:$UPSTREAM_PORT" &
+COLLECTOR_PID=$!
+sleep 1
+
+echo "1. 发送合成查询..."
+python3 - "$STUB_ADDR" <<'PY'
+import socket, struct, sys
+host, port = sys.argv[1].rsplit(":", 1)
+
+def query(name, qtype, ident):
+    packet = struct.pack(">HHHHHH", ident, 0x0100, 1, 0, 0, 0)
+    for label in name.split("."):
+        packet += bytes([len(label)]) + label.encode()
+    packet += b"\x00" + struct.pack(">HH", qtype, 1)
+    sock = socket.socket(socket.AF_INET, socket.SOCK_DGRAM)
+    sock.settimeout(3)
+    sock.sendto(packet, (host, int(port)))
+    reply = sock.recv(4096)
+    sock.close()
+    assert reply[:2] == packet[:2] and reply[2] & 0x80, "bad reply"
+    assert reply.endswith(b"\xc0\x00\x02\x01"), "answer not relayed"
+
+# 同一域名重复查询（A、AAAA、再次 A）只应记录一次
+query("Example.COM", 1, 1)
+query("example.com", 28, 2)
+query("example.com", 1, 3)
+query("other.test", 1, 4)
+PY
+echo "✓ 查询均得到上游应答"
+
+echo "2. 工作线程占满时直接返回 SERVFAIL..."
+# 不应答的上游让唯一的工作线程一直等待
+python3 - "$SILENT_PORT" <<'PY' &
+import socket, sys
+sock = socket.socket(socket.AF_INET, socket.SOCK_DGRAM)
+sock.bind(("127.0.0.1", int(sys.argv[1])))
+while True:
+    sock.recvfrom(4096)
+PY
+SILENT_PID=$!
+"$BIN/aegis-dns-collector" --listen "$BUSY_STUB_ADDR" --upstream "127.0.0.1:$SILENT_PORT" \
+    --workers 1 --timeout-ms 5000 --no-attribute &
+BUSY_PID=$!
+sleep 1
+
+python3 - "$BUSY_STUB_ADDR" <<'PY'
+import socket, struct, sys, time
+host, port = sys.argv[1].rsplit(":", 1)
+
+def send(name, ident):
+    packet = struct.pack(">HHHHHH", ident, 0x0100, 1, 0, 0, 0)
+    for label in name.split("."):
+        packet += bytes([len(label)]) + label.encode()
+    packet += b"\x00" + struct.pack(">HH", 1, 1)
+    sock = socket.socket(socket.AF_INET, socket.SOCK_DGRAM)
+    sock.sendto(packet, (host, int(port)))
+    return sock
+
+# 第 1 个占用工作线程，第 2 个排队，第 3 个无处可放
+send("busy1.test", 11)
+time.sleep(0.2)
+send("busy2.test", 12)
+time.sleep(0.2)
+sock = send("busy3.test", 13)
+sock.settimeout(1)
+reply = sock.recv(4096)
+assert reply[:2] == struct.pack(">H", 13), "wrong id"
+assert reply[2] & 0x80 and reply[3] & 0x0f == 2, "expected SERVFAIL"
+PY
+kill "$BUSY_PID" "$SILENT_PID" 2>/dev/null || true
+echo "✓ 超出队列的查询立即得到 SERVFAIL"
+sleep 1
+
+"$BIN/aegis-collector-cli" stop "test"
+wait "$SERVER_PID"
+
+BUNDLE=$(ls -d "$TMP_DIR"/Evidence_* | head -1)
+EVENTS="$BUNDLE/events.jsonl"
+
+echo "3. 检查 net_domain 事件..."
+EXAMPLE_COUNT=$(grep '"net_domain"' "$EVENTS" | grep -c '"domain":"example.com"' || true)
+OTHER_COUNT=$(grep '"net_domain"' "$EVENTS" | grep -c '"domain":"other.test"' || true)
+if [ "$EXAMPLE_COUNT" -ne 1 ] || [ "$OTHER_COUNT" -ne 1 ]; then
+    echo "❌ 期望 example.com/other.test 各 1 条，实际 $EXAMPLE_COUNT/$OTHER_COUNT"
+    cat "$EVENTS"
+    exit 1
+fi
+if ! grep '"net_domain"' "$EVENTS" | grep -q '"app_id":"python3'; then
+    echo "❌ 未关联到发起查询的进程"
+    grep '"net_domain"' "$EVENTS"
+    exit 1
+fi
+echo "✓ 域名去重并关联到进程"
+
+echo "4. 验证证据包..."
+"$BIN/aegis-verifier" verify "$BUNDLE"
+
+echo ""
+echo "=== DNS 域名采集测试通过 ==="
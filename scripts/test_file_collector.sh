@@ -0,0 +1,98 @@
+#!/bin/bash
+set -e
+
+echo "=== AEGISTRACE Linux 文件活动采集测试 ==="
+echo ""
+
+ROOT_DIR="$(cd "$(dirname "$0")/.." && pwd)"
+cd "$ROOT_DIR"
+
+if [ "$(uname)" != "Linux" ]; then
+    echo "⚠ 非 Linux 平台，跳过测试"
+    exit 0
+fi
+
+cargo build -q -p aegis-core-server -p aegis-file-collector -p aegis-collector-cli -p aegis-verifier
+BIN="$ROOT_DIR/target/debug"
+
+export AEGIS_CORE_ADDR="127.0.0.1:7892"
+TMP_DIR="$(mktemp -d)"
+WATCH_DIR="$TMP_DIR/watched"
+SAVE_DIR="$TMP_DIR/bundles"
+mkdir -p "$WATCH_DIR" "$SAVE_DIR"
+trap 'kill $SERVER_PID $COLLECTOR_PID 2>/dev/null || true; rm -rf "$TMP_DIR"' EXIT
+
+"$BIN/aegis-core-server" linux test "$SAVE_DIR" &
+SERVER_PID=$!
+sleep 1
+
+"$BIN/aegis-file-collector" --backend inotify --watch "$WATCH_DIR" \
+    --exclude "*.tmp" --hash --capture --coalesce-ms 500 --max-rate 20 &
+COLLECTOR_PID=$!
+sleep 1
+
+echo "1. 生成文件活动..."
+echo "hello" > "$WATCH_DIR/notes.txt"
+echo "ignored" > "$WATCH_DIR/scratch.tmp"
+mkdir "$WATCH_DIR/sub"
+sleep 0.3
+echo "nested" > "$WATCH_DIR/sub/deep.txt"
+mv "$WATCH_DIR/notes.txt" "$WATCH_DIR/renamed.txt"
+rm "$WATCH_DIR/sub/deep.txt"
+# 目录改名后，子目录中的事件应使用新路径；移出监视范围的目录不再上报
+mkdir -p "$WATCH_DIR/olddir/inner" "$WATCH_DIR/leaving"
+sleep 0.3
+mv "$WATCH_DIR/olddir" "$WATCH_DIR/newdir"
+mv "$WATCH_DIR/leaving" "$TMP_DIR/outside"
+sleep 0.3
+echo "after" > "$WATCH_DIR/newdir/inner/after_rename.txt"
+echo "gone" > "$TMP_DIR/outside/left.txt"
+sleep 0.5
+# 短时间内大量写入应触发限流
+for i in $(seq 1 60); do
+    echo "$i" > "$WATCH_DIR/burst_$i.log"
+done
+sleep 2
+
+"$BIN/aegis-collector-cli" stop "test"
+wait "$SERVER_PID"
+
+BUNDLE=$(ls -d "$SAVE_DIR"/Evidence_* | head -1)
+EVENTS="$BUNDLE/events.jsonl"
+
+echo "2. 检查事件..."
+for expected in '"op":"created"' '"op":"moved_to"' '"op":"deleted"' 'sub/deep.txt' '"hash":"'; do
+    if ! grep -q "$expected" "$EVENTS"; then
+        echo "❌ 缺少事件: $expected"
+        cat "$EVENTS"
+        exit 1
+    fi
+done
+if ! grep -q 'newdir/inner/after_rename.txt' "$EVENTS" || grep -q 'olddir/inner/after_rename.txt' "$EVENTS"; then
+    echo "❌ 目录改名后路径未更新"
+    grep 'after_rename' "$EVENTS" || true
+    exit 1
+fi
+if grep -q 'left.txt' "$EVENTS"; then
+    echo "❌ 移出监视范围的目录仍在上报"
+    exit 1
+fi
+if grep -q 'scratch.tmp' "$EVENTS"; then
+    echo "❌ 排除规则未生效"
+    exit 1
+fi
+if ! grep -q '"file_activity_suppressed"' "$EVENTS"; then
+    echo "❌ 未记录限流事件"
+    exit 1
+fi
+if ! grep -q '"kind":"file_capture"' "$EVENTS" || [ -z "$(ls "$BUNDLE/files/fs" 2>/dev/null)" ]; then
+    echo "❌ 未捕获文件内容"
+    exit 1
+fi
+echo "✓ 过滤、递归监视、目录改名、哈希、限流与捕获均正常"
+
+echo "3. 验证证据包..."
+"$BIN/aegis-verifier" verify "$BUNDLE"
+
+echo ""
+echo "=== 文件活动采集测试通过 ==="
@@ -0,0 +1,79 @@
+#!/bin/bash
+set -e
+
+echo "=== AEGISTRACE Linux 焦点采集测试（Xvfb） ==="
+echo ""
+
+ROOT_DIR="$(cd "$(dirname "$0")/.." && pwd)"
+cd "$ROOT_DIR"
+
+for tool in Xvfb xprop xwininfo; do
+    if ! command -v "$tool" >/dev/null 2>&1; then
+        echo "⚠ 未找到 $tool，跳过测试"
+        exit 0
+    fi
+done
+
+cargo build -q -p aegis-core-server -p aegis-focus-collector -p aegis-verifier
+BIN="$ROOT_DIR/target/debug"
+
+export DISPLAY=":98"
+export AEGIS_CORE_ADDR="127.0.0.1:7891"
+TMP_DIR="$(mktemp -d)"
+Xvfb "$DISPLAY" -screen 0 1280x720x24 >/dev/null 2>&1 &
+XVFB_PID=$!
+trap 'kill $XVFB_PID $SERVER_PID $COLLECTOR_PID 2>/dev/null || true; rm -rf "$TMP_DIR"' EXIT
+sleep 1
+
+"$BIN/aegis-core-server" linux test "$TMP_DIR" &
+SERVER_PID=$!
+sleep 1
+
+"$BIN/aegis-focus-collector" --backend x11 --debounce-ms 200 &
+COLLECTOR_PID=$!
+sleep 1
+
+# 脚本化的“窗口管理器”：直接在根窗口上设置窗口属性并切换 _NET_ACTIVE_WINDOW
+ROOT_ID=$(xwininfo -root | awk '/Window id:/ {print $4}')
+focus_window() {
+    xprop -root -f WM_CLASS 8s -set WM_CLASS "$1"
+    xprop -root -f _NET_WM_NAME 8u -set _NET_WM_NAME "$2"
+    xprop -root -f _NET_WM_PID 32c -set _NET_WM_PID "$$"
+    xprop -root -f _NET_ACTIVE_WINDOW 32x -set _NET_ACTIVE_WINDOW 0x0
+    sleep 0.05
+    xprop -root -f _NET_ACTIVE_WINDOW 32x -set _NET_ACTIVE_WINDOW "$ROOT_ID"
+}
+
+echo "1. 切换焦点..."
+focus_window "Editor" "main.rs"
+sleep 1
+# 快速连续切换只应记录最后一个
+focus_window "Flicker" "flicker"
+focus_window "Terminal" "shell"
+sleep 1
+
+"$BIN/aegis-collector-cli" stop "test"
+wait "$SERVER_PID"
+
+BUNDLE=$(ls -d "$TMP_DIR"/Evidence_* | head -1)
+FOCUS_COUNT=$(grep -c '"app_focus_changed"' "$BUNDLE/events.jsonl" || true)
+if [ "$FOCUS_COUNT" -ne 2 ]; then
+    echo "❌ 期望 2 条焦点事件，实际 $FOCUS_COUNT"
+    cat "$BUNDLE/events.jsonl"
+    exit 1
+fi
+if grep -q '"Flicker"' "$BUNDLE/events.jsonl"; then
+    echo "❌ 去抖失败：记录了短暂焦点"
+    exit 1
+fi
+if ! grep -q '"exe"' "$BUNDLE/events.jsonl"; then
+    echo "❌ 未解析出进程可执行文件"
+    exit 1
+fi
+echo "✓ 焦点事件去抖并解析 WM_CLASS / PID"
+
+echo "2. 验证证据包..."
+"$BIN/aegis-verifier" verify "$BUNDLE"
+
+echo ""
+echo "=== 焦点采集测试通过 ==="
@@ -0,0 +1,63 @@
+#!/bin/bash
+set -e
+
+echo "=== AEGISTRACE 黄金样例证据包测试 ==="
+echo ""
+
+ROOT_DIR="$(cd "$(dirname "$0")/.." && pwd)"
+cd "$ROOT_DIR"
+
+cargo build -q -p aegis-verifier
+BIN="$ROOT_DIR/target/debug"
+
+TMP_DIR="$(mktemp -d)"
+trap 'rm -rf "$TMP_DIR"' EXIT
+
+echo "1. 各版本写入端生成的证据包..."
+for bundle in spec/golden/*/; do
+    name=$(basename "$bundle")
+    if ! "$BIN/aegis-verifier" verify "$bundle" > "$TMP_DIR/verify.txt" 2>&1; then
+        echo "❌ $name 校验失败（规范化 JSON 或哈希算法与写入时不一致）"
+        cat "$TMP_DIR/verify.txt"
+        exit 1
+    fi
+    echo "✓ $name"
+done
+
+echo "2. 篡改后应校验失败..."
+for bundle in spec/golden/*/; do
+    name=$(basename "$bundle")
+    cp -r "$bundle" "$TMP_DIR/$name"
+    # 第 3 行是 fixture_values 事件
+    sed -i '3s/"zeta":1/"zeta":2/' "$TMP_DIR/$name/events.jsonl"
+    if "$BIN/aegis-verifier" verify "$TMP_DIR/$name" > "$TMP_DIR/verify.txt" 2>&1 \
+        || ! grep -q "hash mismatch at line 3" "$TMP_DIR/verify.txt"; then
+        echo "❌ $name 篡改 payload 后应报告 hash mismatch"
+        cat "$TMP_DIR/verify.txt"
+        exit 1
+    fi
+done
+echo "✓ 所有样例的篡改均被发现"
+
+echo "3. schema_version 决定哈希算法..."
+# v1 样例按 JCS 校验：第 3 行的 -0.0 等数字格式不同，哈希对不上
+cp -r spec/golden/1-ts-seconds "$TMP_DIR/as-v2"
+sed -i 's/"schema_version": 1/"schema_version": 2/' "$TMP_DIR/as-v2/manifest.json"
+if "$BIN/aegis-verifier" verify "$TMP_DIR/as-v2" > "$TMP_DIR/verify.txt" 2>&1 \
+    || ! grep -q "hash mismatch at line 3" "$TMP_DIR/verify.txt"; then
+    echo "❌ v1 证据包不能按 schema_version 2 通过校验"
+    cat "$TMP_DIR/verify.txt"
+    exit 1
+fi
+cp -r spec/golden/5-jcs "$TMP_DIR/as-v1"
+sed -i 's/"schema_version": 2/"schema_version": 1/' "$TMP_DIR/as-v1/manifest.json"
+if "$BIN/aegis-verifier" verify "$TMP_DIR/as-v1" > "$TMP_DIR/verify.txt" 2>&1 \
+    || ! grep -q "schema_version mismatch: manifest says 1, session_started says 2" "$TMP_DIR/verify.txt"; then
+    echo "❌ manifest 与 session_started 的 schema_version 不一致时应校验失败"
+    cat "$TMP_DIR/verify.txt"
+    exit 1
+fi
+echo "✓ 修改 schema_version 后校验失败"
+
+echo ""
+echo "=== 黄金样例证据包测试通过 ==="
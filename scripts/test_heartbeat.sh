@@ -0,0 +1,81 @@
+#!/bin/bash
+set -e
+
+echo "=== AEGISTRACE 采集器心跳与覆盖率测试 ==="
+echo ""
+
+ROOT_DIR="$(cd "$(dirname "$0")/.." && pwd)"
+cd "$ROOT_DIR"
+
+if [ "$(uname)" != "Linux" ]; then
+    echo "⚠ 非 Linux 平台，跳过测试"
+    exit 0
+fi
+
+cargo build -q -p aegis-core-server -p aegis-process-collector -p aegis-collector-cli -p aegis-verifier
+BIN="$ROOT_DIR/target/debug"
+
+export AEGIS_CORE_ADDR="127.0.0.1:7900"
+TMP_DIR="$(mktemp -d)"
+trap 'kill -CONT $COLLECTOR_PID 2>/dev/null || true; kill $SERVER_PID $COLLECTOR_PID 2>/dev/null || true; rm -rf "$TMP_DIR"' EXIT
+
+# 心跳 200ms，错过 2 次即判定失联
+"$BIN/aegis-core-server" --set server.heartbeat_missed_limit=2 linux test "$TMP_DIR" &
+SERVER_PID=$!
+sleep 1
+
+"$BIN/aegis-process-collector" --set server.heartbeat_interval_ms=200 --backend proc --poll-ms 200 &
+COLLECTOR_PID=$!
+sleep 1
+
+echo "1. 暂停采集器，模拟无声卡死..."
+kill -STOP "$COLLECTOR_PID"
+sleep 1.5
+kill -CONT "$COLLECTOR_PID"
+sleep 1
+
+echo "2. 结束采集器，会话结束时仍失联..."
+kill "$COLLECTOR_PID"
+wait "$COLLECTOR_PID" 2>/dev/null || true
+COLLECTOR_PID=""
+sleep 1.5
+
+"$BIN/aegis-collector-cli" stop "test"
+wait "$SERVER_PID"
+
+BUNDLE=$(ls -d "$TMP_DIR"/Evidence_* | head -1)
+EVENTS="$BUNDLE/events.jsonl"
+
+echo "3. 检查心跳事件..."
+for expected in heartbeat:1 collector_lost:2 collector_recovered:1; do
+    event_type="${expected%%:*}"
+    COUNT=$(grep -c "\"type\":\"$event_type\"" "$EVENTS" || true)
+    if [ "$COUNT" -ne "${expected##*:}" ]; then
+        echo "❌ 期望 ${expected##*:} 条 $event_type，实际 $COUNT"
+        cat "$EVENTS"
+        exit 1
+    fi
+done
+if ! grep '"collector_recovered"' "$EVENTS" | grep -q '"collector":"process"'; then
+    echo "❌ collector_recovered 缺少采集器名称"
+    cat "$EVENTS"
+    exit 1
+fi
+echo "✓ 只记录首次心跳与失联/恢复，不记录每次心跳"
+
+echo "4. 覆盖率报告..."
+"$BIN/aegis-verifier" coverage "$BUNDLE"
+REPORT=$("$BIN/aegis-verifier" coverage "$BUNDLE" --json)
+for expected in '"collector":"process"' '"gaps":2' '"lost_at_end":true'; do
+    if ! echo "$REPORT" | grep -qF "$expected"; then
+        echo "❌ 覆盖率报告缺少 $expected: $REPORT"
+        exit 1
+    fi
+done
+echo "✓ 覆盖率报告包含失联区间"
+
+echo "5. 验证证据包..."
+"$BIN/aegis-verifier" verify "$BUNDLE"
+
+echo ""
+echo "=== 采集器心跳与覆盖率测试通过 ==="
@@ -0,0 +1,156 @@
+#!/bin/bash
+set -e
+
+echo "=== AEGISTRACE Linux 空闲/锁屏采集测试 ==="
+echo ""
+
+ROOT_DIR="$(cd "$(dirname "$0")/.." && pwd)"
+cd "$ROOT_DIR"
+
+if [ "$(uname)" != "Linux" ]; then
+    echo "⚠ 非 Linux 平台，跳过测试"
+    exit 0
+fi
+if ! command -v dbus-daemon >/dev/null 2>&1 || ! command -v dbus-monitor >/dev/null 2>&1; then
+    echo "⚠ 未找到 dbus-daemon/dbus-monitor，跳过测试"
+    exit 0
+fi
+# 伪 logind 需要 python3-dbus
+PYTHON=""
+for candidate in python3 /usr/bin/python3; do
+    if "$candidate" -c 'import dbus' >/dev/null 2>&1; then
+        PYTHON="$candidate"
+        break
+    fi
+done
+if [ -z "$PYTHON" ]; then
+    echo "⚠ 未找到带 dbus 模块的 python3，跳过测试"
+    exit 0
+fi
+
+cargo build -q -p aegis-core-server -p aegis-idle-collector -p aegis-collector-cli -p aegis-verifier
+BIN="$ROOT_DIR/target/debug"
+
+export AEGIS_CORE_ADDR="127.0.0.1:7897"
+TMP_DIR="$(mktemp -d)"
+trap 'kill $SERVER_PID $LOGIND_COLLECTOR_PID $X11_COLLECTOR_PID $BUS_PID 2>/dev/null || true; rm -rf "$TMP_DIR"' EXIT
+
+# 私有会话总线，不触碰系统 logind
+dbus-daemon --session --fork --print-address=3 --print-pid=4 3>"$TMP_DIR/bus_address" 4>"$TMP_DIR/bus_pid"
+BUS_ADDRESS=$(head -1 "$TMP_DIR/bus_address")
+BUS_PID=$(head -1 "$TMP_DIR/bus_pid")
+
+"$BIN/aegis-core-server" linux test "$TMP_DIR" &
+SERVER_PID=$!
+sleep 1
+
+echo "1. logind 信号（私有总线）..."
+"$BIN/aegis-idle-collector" --no-x11 --dbus-address "$BUS_ADDRESS" --session-id 2 &
+LOGIND_COLLECTOR_PID=$!
+sleep 1
+
+"$PYTHON" - "$BUS_ADDRESS" <<'PY'
+import sys, time
+import dbus, dbus.lowlevel
+
+bus = dbus.bus.BusConnection(sys.argv[1])
+bus.request_name("org.freedesktop.login1")
+SESSION = "/org/freedesktop/login1/session/_32"
+OTHER = "/org/freedesktop/login1/session/_33"
+
+def emit(path, interface, member, signature="", *args):
+    message = dbus.lowlevel.SignalMessage(path, interface, member)
+    if signature:
+        message.append(*args, signature=signature)
+    bus.send_message(message)
+    bus.flush()
+    time.sleep(0.1)
+
+def changed(path, name, value):
+    emit(path, "org.freedesktop.DBus.Properties", "PropertiesChanged", "sa{sv}as",
+         "org.freedesktop.login1.Session", {name: dbus.Boolean(value)}, [])
+
+emit(SESSION, "org.freedesktop.login1.Session", "Lock")
+changed(SESSION, "LockedHint", True)
+emit(OTHER, "org.freedesktop.login1.Session", "Unlock")
+changed(SESSION, "IdleHint", True)
+emit("/org/freedesktop/login1", "org.freedesktop.login1.Manager", "PrepareForSleep", "b", True)
+time.sleep(0.3)
+emit("/org/freedesktop/login1", "org.freedesktop.login1.Manager", "PrepareForSleep", "b", False)
+changed(SESSION, "IdleHint", False)
+emit(SESSION, "org.freedesktop.login1.Session", "Unlock")
+PY
+sleep 0.5
+kill "$LOGIND_COLLECTOR_PID"
+
+echo "2. X11 空闲计时（桩 xprintidle）..."
+mkdir -p "$TMP_DIR/bin"
+export FAKE_IDLE="$TMP_DIR/idle_ms"
+echo 0 > "$FAKE_IDLE"
+cat > "$TMP_DIR/bin/xprintidle" <<'STUB'
+#!/bin/sh
+cat "$FAKE_IDLE"
+STUB
+chmod +x "$TMP_DIR/bin/xprintidle"
+DISPLAY=":stub" PATH="$TMP_DIR/bin:$PATH" "$BIN/aegis-idle-collector" --no-logind --idle-secs 2 --poll-ms 100 &
+X11_COLLECTOR_PID=$!
+sleep 0.5
+echo 2500 > "$FAKE_IDLE"
+sleep 0.5
+echo 30 > "$FAKE_IDLE"
+sleep 0.5
+kill "$X11_COLLECTOR_PID"
+
+"$BIN/aegis-collector-cli" stop "test"
+wait "$SERVER_PID"
+
+BUNDLE=$(ls -d "$TMP_DIR"/Evidence_* | head -1)
+EVENTS="$BUNDLE/events.jsonl"
+
+echo "3. 检查 logind 事件..."
+for expected in screen_locked:1 screen_unlocked:1 system_suspend:1 system_resume:1; do
+    event_type="${expected%%:*}"
+    COUNT=$(grep -c "\"$event_type\"" "$EVENTS" || true)
+    if [ "$COUNT" -ne "${expected##*:}" ]; then
+        echo "❌ 期望 ${expected##*:} 条 $event_type，实际 $COUNT（重复状态或其他会话未被过滤）"
+        cat "$EVENTS"
+        exit 1
+    fi
+done
+if ! grep '"screen_locked"' "$EVENTS" | grep -q '"via":"Lock"'; then
+    echo "❌ screen_locked 应来自 Lock 信号"
+    cat "$EVENTS"
+    exit 1
+fi
+if ! grep '"system_resume"' "$EVENTS" | grep -q '"suspended_ms"'; then
+    echo "❌ system_resume 缺少 suspended_ms"
+    cat "$EVENTS"
+    exit 1
+fi
+for event_type in user_idle user_active; do
+    if ! grep "\"$event_type\"" "$EVENTS" | grep -q '"source":"logind"'; then
+        echo "❌ 缺少来自 logind 的 $event_type"
+        cat "$EVENTS"
+        exit 1
+    fi
+done
+echo "✓ 锁屏、挂起/恢复与 IdleHint 均已记录，其他会话被忽略"
+
+echo "4. 检查 X11 空闲事件..."
+if ! grep '"user_idle"' "$EVENTS" | grep '"source":"x11"' | grep -q '"idle_ms":2500'; then
+    echo "❌ 缺少 X11 user_idle"
+    cat "$EVENTS"
+    exit 1
+fi
+if ! grep '"user_active"' "$EVENTS" | grep '"source":"x11"' | grep -q '"idle_duration_ms"'; then
+    echo "❌ 缺少 X11 user_active"
+    cat "$EVENTS"
+    exit 1
+fi
+echo "✓ 空闲阈值跨越双向记录"
+
+echo "5. 验证证据包..."
+"$BIN/aegis-verifier" verify "$BUNDLE"
+
+echo ""
+echo "=== 空闲/锁屏采集测试通过 ==="
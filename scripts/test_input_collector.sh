@@ -0,0 +1,25 @@
+#!/bin/bash
+set -e
+
+echo "=== AEGISTRACE Linux 输入统计测试（事件回放） ==="
+echo ""
+
+ROOT_DIR="$(cd "$(dirname "$0")/.." && pwd)"
+cd "$ROOT_DIR"
+
+cargo build -q -p aegis-input-collector
+FIXTURES="collectors/linux/input/fixtures"
+
+for fixture in "$FIXTURES"/*.txt; do
+    name="$(basename "$fixture" .txt)"
+    echo "回放 $name..."
+    if ! ./target/debug/aegis-input-collector --replay "$fixture" --interval-ms 5000 --print \
+        | diff -u "$FIXTURES/$name.expected.jsonl" -; then
+        echo "❌ $name 输出与预期不符"
+        exit 1
+    fi
+    echo "✓ $name"
+done
+
+echo ""
+echo "=== 输入统计测试通过 ==="
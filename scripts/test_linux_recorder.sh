@@ -0,0 +1,81 @@
+#!/bin/bash
+set -e
+
+echo "=== AEGISTRACE Linux 录屏测试（Xvfb） ==="
+echo ""
+
+ROOT_DIR="$(cd "$(dirname "$0")/.." && pwd)"
+cd "$ROOT_DIR"
+
+for tool in Xvfb ffmpeg ffprobe; do
+    if ! command -v "$tool" >/dev/null 2>&1; then
+        echo "⚠ 未找到 $tool，跳过测试"
+        exit 0
+    fi
+done
+
+cargo build -q -p aegis-linux-recorder
+RECORDER="$ROOT_DIR/target/debug/aegis-linux-recorder"
+
+DISPLAY_NUM=":99"
+Xvfb "$DISPLAY_NUM" -screen 0 1920x1080x24 >/dev/null 2>&1 &
+XVFB_PID=$!
+TMP_DIR="$(mktemp -d)"
+trap 'kill $XVFB_PID 2>/dev/null || true; rm -rf "$TMP_DIR"' EXIT
+sleep 1
+
+# 测试 1: 按分段时长自动结束，分辨率按上限缩放
+echo "1. 分段时长..."
+"$RECORDER" "$TMP_DIR/segment.mp4" 2 --codec h264 --width 1280 --height 720 --fps 15 \
+    --bitrate 1000000 --source x11 --display "$DISPLAY_NUM" < <(sleep 10)
+SIZE=$(ffprobe -v error -select_streams v:0 -show_entries stream=width,height -of csv=p=0 "$TMP_DIR/segment.mp4")
+if [ "$SIZE" != "1280,720" ]; then
+    echo "❌ 分辨率不符: $SIZE"
+    exit 1
+fi
+echo "✓ 分段生成，分辨率 $SIZE"
+
+# 测试 2: stdin 换行提前停止，文件仍可读
+echo "2. stdin 停止..."
+START=$(date +%s)
+(sleep 2; echo) | "$RECORDER" "$TMP_DIR/stopped.mp4" 60 --codec hevc --fps 15 \
+    --source x11 --display "$DISPLAY_NUM"
+ELAPSED=$(( $(date +%s) - START ))
+if [ "$ELAPSED" -ge 30 ]; then
+    echo "❌ 未响应 stdin 停止（耗时 ${ELAPSED}s）"
+    exit 1
+fi
+CODEC=$(ffprobe -v error -select_streams v:0 -show_entries stream=codec_name -of csv=p=0 "$TMP_DIR/stopped.mp4")
+if [ "$CODEC" != "hevc" ]; then
+    echo "❌ 编码不符: $CODEC"
+    exit 1
+fi
+echo "✓ ${ELAPSED}s 内停止，编码 $CODEC"
+
+# 测试 3: 不支持的参数直接失败
+echo "3. 参数校验..."
+if "$RECORDER" "$TMP_DIR/bad.mp4" 2 --codec vp9 --display "$DISPLAY_NUM" </dev/null 2>/dev/null; then
+    echo "❌ 不支持的编码未报错"
+    exit 1
+fi
+echo "✓ 不支持的编码被拒绝"
+
+# 测试 4: ffmpeg 缺少 pipewiregrab 时，PipeWire 来源启动即失败并说明原因
+echo "4. PipeWire 滤镜检查..."
+if ffmpeg -hide_banner -filters 2>/dev/null | awk '{print $2}' | grep -qx pipewiregrab; then
+    echo "⚠ 本机 ffmpeg 含 pipewiregrab，跳过"
+else
+    if "$RECORDER" "$TMP_DIR/wayland.mp4" 2 --source pipewire </dev/null 2>"$TMP_DIR/wayland.err"; then
+        echo "❌ 缺少 pipewiregrab 时未报错"
+        exit 1
+    fi
+    if ! grep -q "pipewiregrab" "$TMP_DIR/wayland.err"; then
+        echo "❌ 错误信息未说明缺少 pipewiregrab"
+        cat "$TMP_DIR/wayland.err"
+        exit 1
+    fi
+    echo "✓ 缺少 pipewiregrab 时直接报错"
+fi
+
+echo ""
+echo "=== Linux 录屏测试通过 ==="
@@ -0,0 +1,103 @@
+#!/bin/bash
+set -e
+
+echo "=== AEGISTRACE 采集时间 observed_at 测试 ==="
+echo ""
+
+ROOT_DIR="$(cd "$(dirname "$0")/.." && pwd)"
+cd "$ROOT_DIR"
+
+cargo build -q -p aegis-core-server -p aegis-collector-cli -p aegis-verifier
+BIN="$ROOT_DIR/target/debug"
+
+export AEGIS_CORE_ADDR="127.0.0.1:7903"
+TMP_DIR="$(mktemp -d)"
+trap 'kill $SERVER_PID 2>/dev/null || true; rm -rf "$TMP_DIR"' EXIT
+
+at() {
+    date -u -d "$1" +%Y-%m-%dT%H:%M:%S.%3NZ
+}
+
+"$BIN/aegis-core-server" --set server.observed_max_behind_ms=30000 linux test "$TMP_DIR/out" &
+SERVER_PID=$!
+sleep 1
+
+echo "1. 发送带 observed_at 的消息..."
+INPUT="$TMP_DIR/input.txt"
+{
+    echo "{\"type\":\"note\",\"payload\":{\"n\":1},\"observed_at\":\"$(at '-5 seconds')\"}"
+    echo "{\"type\":\"note\",\"payload\":{\"n\":2},\"observed_at\":\"$(at '-10 seconds')\"}"
+    echo "{\"type\":\"clock\",\"payload\":{\"n\":3},\"observed_at\":\"$(at '+3 seconds')\"}"
+    echo "{\"type\":\"plain\",\"payload\":{\"n\":4}}"
+    echo "{\"type\":\"note\",\"payload\":{\"n\":5},\"observed_at\":\"$(at '+60 seconds')\"}"
+    echo "{\"type\":\"note\",\"payload\":{\"n\":6},\"observed_at\":\"$(at '-60 seconds')\"}"
+    echo "{\"type\":\"note\",\"payload\":{\"n\":7},\"observed_at\":\"yesterday\"}"
+} > "$INPUT"
+set +e
+"$BIN/aegis-collector-cli" send --stdin < "$INPUT" > "$TMP_DIR/out.txt" 2> /dev/null
+set -e
+if [ "$(grep -c " OK " "$TMP_DIR/out.txt")" -ne 4 ] \
+    || ! grep -q "^5 ERR .*ahead of the server clock" "$TMP_DIR/out.txt" \
+    || ! grep -q "^6 ERR .*behind the server clock" "$TMP_DIR/out.txt" \
+    || ! grep -q "^7 ERR .*invalid observed_at" "$TMP_DIR/out.txt"; then
+    echo "❌ 超出偏差上限或格式错误的 observed_at 应被拒绝"
+    cat "$TMP_DIR/out.txt"
+    exit 1
+fi
+echo "✓ 偏差上限内接受，超前 60 秒、落后 60 秒与无效格式被拒绝"
+
+echo "2. 通过 aegis-client 发送..."
+# 绕过 aegis-client 直接发送不带 observed_at 的消息
+exec 3<>"/dev/tcp/${AEGIS_CORE_ADDR%:*}/${AEGIS_CORE_ADDR##*:}"
+echo '{"type":"plain","payload":{"n":8}}' >&3
+read -r REPLY <&3
+exec 3>&-
+"$BIN/aegis-collector-cli" focus org.example.App "Example"
+"$BIN/aegis-collector-cli" stop "done"
+wait "$SERVER_PID"
+
+BUNDLE=$(ls -d "$TMP_DIR"/out/Evidence_* | head -1)
+EVENTS="$BUNDLE/events.jsonl"
+python3 - "$EVENTS" <<'PY'
+import json, sys
+events = [json.loads(line) for line in open(sys.argv[1])]
+by_type = {}
+for event in events:
+    by_type.setdefault(event["type"], []).append(event)
+if len(by_type["note"]) != 2 or any("observed_at" not in event for event in by_type["note"]):
+    sys.exit("note events should keep observed_at")
+# send --stdin goes through aegis-client, which stamps messages without one
+if "observed_at" not in by_type["plain"][0]:
+    sys.exit(f"send --stdin should stamp observed_at: {by_type['plain'][0]}")
+if "observed_at" in by_type["plain"][1] or "observed_at" in by_type["session_started"][0]:
+    sys.exit("events without observed_at should not get one")
+focus = by_type["app_focus_changed"][0]
+if not focus.get("observed_at", "").endswith("Z") or "." not in focus["observed_at"]:
+    sys.exit(f"aegis-client should stamp observed_at in UTC with milliseconds: {focus}")
+PY
+echo "✓ observed_at 与 ts 分开保存，aegis-client 自动填写"
+
+echo "3. 校验器警告..."
+"$BIN/aegis-verifier" verify "$BUNDLE" > "$TMP_DIR/verify.txt" 2>&1
+if ! grep -q "^PASS" "$TMP_DIR/verify.txt" \
+    || ! grep -q "WARN: line 4: clock observed_at runs .*ahead of ts" "$TMP_DIR/verify.txt" \
+    || ! grep -q "WARN: line 3: note observed_at goes back" "$TMP_DIR/verify.txt" \
+    || [ "$(grep -c WARN "$TMP_DIR/verify.txt")" -ne 2 ]; then
+    echo "❌ 应对超前与倒退的 observed_at 给出警告且仍然通过"
+    cat "$TMP_DIR/verify.txt"
+    exit 1
+fi
+echo "✓ 超前与倒退各输出一条 WARN，校验通过"
+
+echo "4. 篡改 observed_at..."
+sed -i '2s/"observed_at":"[^"]*"/"observed_at":"2000-01-01T00:00:00.000Z"/' "$EVENTS"
+if "$BIN/aegis-verifier" verify "$BUNDLE" > "$TMP_DIR/verify.txt" 2>&1 \
+    || ! grep -q "hash mismatch at line 2" "$TMP_DIR/verify.txt"; then
+    echo "❌ observed_at 应受哈希链保护"
+    cat "$TMP_DIR/verify.txt"
+    exit 1
+fi
+echo "✓ 修改 observed_at 会破坏哈希链"
+
+echo ""
+echo "=== observed_at 测试通过 ==="
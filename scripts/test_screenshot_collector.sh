@@ -0,0 +1,132 @@
+#!/bin/bash
+set -e
+
+echo "=== AEGISTRACE Linux 截图采集测试 ==="
+echo ""
+
+ROOT_DIR="$(cd "$(dirname "$0")/.." && pwd)"
+cd "$ROOT_DIR"
+
+if [ "$(uname)" != "Linux" ]; then
+    echo "⚠ 非 Linux 平台，跳过测试"
+    exit 0
+fi
+
+cargo build -q -p aegis-core-server -p aegis-screenshot-collector -p aegis-collector-cli -p aegis-verifier
+BIN="$ROOT_DIR/target/debug"
+
+export AEGIS_CORE_ADDR="127.0.0.1:7897"
+TMP_DIR="$(mktemp -d)"
+trap 'kill $XVFB_PID $SERVER_PID $COLLECTOR_PID 2>/dev/null || true; rm -rf "$TMP_DIR"' EXIT
+
+if command -v Xvfb >/dev/null 2>&1 && command -v ffmpeg >/dev/null 2>&1 && command -v xprop >/dev/null 2>&1; then
+    echo "使用 Xvfb + ffmpeg"
+    REAL_X=1
+    export DISPLAY=":96"
+    Xvfb "$DISPLAY" -screen 0 1280x720x24 >/dev/null 2>&1 &
+    XVFB_PID=$!
+    sleep 1
+    ROOT_ID=$(xwininfo -root | awk '/Window id:/ {print $4}')
+    switch_focus() {
+        xprop -root -f _NET_ACTIVE_WINDOW 32x -set _NET_ACTIVE_WINDOW "$1"
+    }
+else
+    # 没有 X 环境时用桩程序模拟 ffmpeg 与 xprop -spy
+    echo "未找到 Xvfb/ffmpeg，使用桩程序"
+    REAL_X=0
+    export DISPLAY=":stub"
+    export FAKE_FOCUS="$TMP_DIR/focus"
+    mkdir -p "$TMP_DIR/bin"
+    touch "$FAKE_FOCUS"
+    cat > "$TMP_DIR/bin/ffmpeg" <<'STUB'
+#!/bin/sh
+for out; do :; done
+echo "$*" >> "$(dirname "$0")/ffmpeg.log"
+printf '\377\330FAKESHOT' > "$out"
+STUB
+    cat > "$TMP_DIR/bin/xprop" <<'STUB'
+#!/bin/sh
+# 采集器退出后 tail 随之退出
+exec tail -f --pid="$PPID" "$FAKE_FOCUS"
+STUB
+    chmod +x "$TMP_DIR/bin/ffmpeg" "$TMP_DIR/bin/xprop"
+    export PATH="$TMP_DIR/bin:$PATH"
+    switch_focus() {
+        echo "_NET_ACTIVE_WINDOW(WINDOW): window id # $1" >> "$FAKE_FOCUS"
+    }
+fi
+
+"$BIN/aegis-core-server" linux test "$TMP_DIR" &
+SERVER_PID=$!
+sleep 1
+
+echo "1. 定时截图 + 焦点切换截图..."
+"$BIN/aegis-screenshot-collector" --interval-secs 2 --on-focus --focus-delay-ms 200 \
+    --min-gap-ms 500 --quality 100 &
+COLLECTOR_PID=$!
+sleep 0.5
+switch_focus 0x1200007
+sleep 0.05
+switch_focus 0x1400009
+sleep 2.5
+kill "$COLLECTOR_PID"
+wait "$COLLECTOR_PID" 2>/dev/null || true
+
+echo "2. 再次启动，编号应继续而不覆盖..."
+"$BIN/aegis-screenshot-collector" --interval-secs 1 &
+COLLECTOR_PID=$!
+sleep 1.5
+kill "$COLLECTOR_PID"
+wait "$COLLECTOR_PID" 2>/dev/null || true
+
+echo "3. 同一 rel_path 重发相同内容应接受，不同内容应拒绝..."
+printf 'SAME' > "$TMP_DIR/same.jpg"
+printf 'OTHER' > "$TMP_DIR/other.jpg"
+"$BIN/aegis-collector-cli" shot "$TMP_DIR/same.jpg" files/manual/000001.jpg >/dev/null
+"$BIN/aegis-collector-cli" shot "$TMP_DIR/same.jpg" files/manual/000001.jpg >/dev/null
+if "$BIN/aegis-collector-cli" shot "$TMP_DIR/other.jpg" files/manual/000001.jpg >/dev/null 2>&1; then
+    echo "❌ 不同内容不应覆盖已有截图"
+    exit 1
+fi
+echo "✓ 相同内容重发被接受，不同内容被拒绝"
+
+"$BIN/aegis-collector-cli" stop "test"
+wait "$SERVER_PID"
+
+BUNDLE=$(ls -d "$TMP_DIR"/Evidence_* | head -1)
+EVENTS="$BUNDLE/events.jsonl"
+
+echo "4. 检查截图..."
+FOCUS_COUNT=$(grep '"shot_saved"' "$EVENTS" | grep -c '"trigger":"focus"' || true)
+INTERVAL_COUNT=$(grep '"shot_saved"' "$EVENTS" | grep -c '"trigger":"interval"' || true)
+if [ "$FOCUS_COUNT" -ne 1 ]; then
+    echo "❌ 快速连续的焦点切换应只截 1 张，实际 $FOCUS_COUNT"
+    cat "$EVENTS"
+    exit 1
+fi
+if [ "$INTERVAL_COUNT" -lt 2 ]; then
+    echo "❌ 定时截图不足，实际 $INTERVAL_COUNT"
+    exit 1
+fi
+TOTAL=$((FOCUS_COUNT + INTERVAL_COUNT))
+EXPECTED=$(seq -f "%06g.jpg" 1 "$TOTAL" | tr '\n' ' ')
+ACTUAL=$(ls "$BUNDLE/files/shots" | tr '\n' ' ')
+if [ "$ACTUAL" != "$EXPECTED" ]; then
+    echo "❌ 编号不连续：$ACTUAL"
+    exit 1
+fi
+if [ "$REAL_X" -eq 0 ] && ! grep -q -- "-q:v 2 " "$TMP_DIR/bin/ffmpeg.log"; then
+    echo "❌ quality 100 应映射为 -q:v 2"
+    exit 1
+fi
+if [ "$(cat "$BUNDLE/files/manual/000001.jpg")" != "SAME" ]; then
+    echo "❌ 已有截图被覆盖"
+    exit 1
+fi
+echo "✓ $TOTAL 张截图顺序编号（焦点 $FOCUS_COUNT，定时 $INTERVAL_COUNT）"
+
+echo "5. 验证证据包..."
+"$BIN/aegis-verifier" verify "$BUNDLE"
+
+echo ""
+echo "=== 截图采集测试通过 ==="
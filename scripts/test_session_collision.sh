@@ -0,0 +1,93 @@
+#!/bin/bash
+set -e
+
+echo "=== AEGISTRACE 同一秒启动多个会话测试 ==="
+echo ""
+
+ROOT_DIR="$(cd "$(dirname "$0")/.." && pwd)"
+cd "$ROOT_DIR"
+
+cargo build -q -p aegis-core-server -p aegis-collector-cli -p aegis-verifier
+BIN="$ROOT_DIR/target/debug"
+
+TMP_DIR="$(mktemp -d)"
+trap 'kill $SERVER_A $SERVER_B $SERVER_C 2>/dev/null || true; rm -rf "$TMP_DIR"' EXIT
+OUT="$TMP_DIR/out"
+
+echo "1. 同一秒内在同一保存目录启动两个 core server..."
+# 对齐到秒的开头，保证两个会话落在同一秒
+python3 -c 'import time; time.sleep(1 - time.time() % 1 + 0.05)'
+"$BIN/aegis-core-server" linux test "$OUT" 127.0.0.1:7905 2> "$TMP_DIR/a.log" &
+SERVER_A=$!
+"$BIN/aegis-core-server" linux test "$OUT" 127.0.0.1:7906 2> "$TMP_DIR/b.log" &
+SERVER_B=$!
+sleep 1
+
+for i in $(seq 1 5); do
+    AEGIS_CORE_ADDR=127.0.0.1:7905 "$BIN/aegis-collector-cli" focus "a.app$i" "A"
+    AEGIS_CORE_ADDR=127.0.0.1:7906 "$BIN/aegis-collector-cli" focus "b.app$i" "B"
+done
+
+echo "2. 停止后立即重新开始（GUI 快速停止/开始）..."
+AEGIS_CORE_ADDR=127.0.0.1:7905 "$BIN/aegis-collector-cli" stop "a"
+wait "$SERVER_A"
+"$BIN/aegis-core-server" linux test "$OUT" 127.0.0.1:7905 2> "$TMP_DIR/c.log" &
+SERVER_C=$!
+sleep 0.3
+AEGIS_CORE_ADDR=127.0.0.1:7905 "$BIN/aegis-collector-cli" focus "c.app" "C"
+AEGIS_CORE_ADDR=127.0.0.1:7905 "$BIN/aegis-collector-cli" stop "c"
+AEGIS_CORE_ADDR=127.0.0.1:7906 "$BIN/aegis-collector-cli" stop "b"
+wait "$SERVER_B" "$SERVER_C"
+
+echo "3. 检查证据包..."
+BUNDLES=$(ls -d "$OUT"/Evidence_*)
+if [ "$(echo "$BUNDLES" | wc -l)" -ne 3 ]; then
+    echo "❌ 三个会话应各有一个证据包目录"
+    echo "$BUNDLES"
+    exit 1
+fi
+FIRST=$(echo "$BUNDLES" | head -1)
+if [ ! -d "${FIRST}_2" ]; then
+    echo "❌ 同一秒内的第二个会话应使用 _2 后缀"
+    echo "$BUNDLES"
+    exit 1
+fi
+python3 - $BUNDLES <<'PY'
+import json, sys
+session_ids = set()
+apps = {}
+for bundle in sys.argv[1:]:
+    session = json.load(open(f"{bundle}/session.json"))
+    events = [json.loads(line) for line in open(f"{bundle}/events.jsonl")]
+    ids = {event.get("session_id") for event in events}
+    if ids != {session["session_id"]}:
+        sys.exit(f"{bundle}: events should carry the session's session_id, got {ids}")
+    if bundle.rsplit("/", 1)[-1] != session["bundle_dir"]:
+        sys.exit(f"{bundle}: bundle_dir should match the directory name")
+    session_ids.add(session["session_id"])
+    starts = [event for event in events if event["type"] == "session_started"]
+    if len(starts) != 1:
+        sys.exit(f"{bundle}: expected one session_started, got {len(starts)}")
+    apps[bundle] = {event["payload"]["app_id"][0] for event in events if event["type"] == "app_focus_changed"}
+if len(session_ids) != 3:
+    sys.exit("every session should get its own session_id")
+if sorted(map(sorted, apps.values())) != [["a"], ["b"], ["c"]]:
+    sys.exit(f"events of different sessions were mixed: {apps}")
+PY
+for bundle in $BUNDLES; do
+    "$BIN/aegis-verifier" verify "$bundle" > /dev/null
+done
+echo "✓ 三个会话各自独立的目录、session_id 与事件，均校验通过"
+
+echo "4. 混入其他会话的事件..."
+sed -i "s/\"session_id\": \"[^\"]*\"/\"session_id\": \"$(python3 -c 'import uuid; print(uuid.uuid4())')\"/" "$FIRST/session.json"
+if "$BIN/aegis-verifier" verify "$FIRST" > "$TMP_DIR/verify.txt" 2>&1 \
+    || ! grep -q "session_id mismatch at line 1" "$TMP_DIR/verify.txt"; then
+    echo "❌ 事件的 session_id 与 session.json 不一致时应校验失败"
+    cat "$TMP_DIR/verify.txt"
+    exit 1
+fi
+echo "✓ session_id 不一致时校验失败"
+
+echo ""
+echo "=== 同一秒启动多个会话测试通过 ==="
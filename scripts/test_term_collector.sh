@@ -0,0 +1,80 @@
+#!/bin/bash
+set -e
+
+echo "=== AEGISTRACE 终端会话采集测试 ==="
+echo ""
+
+ROOT_DIR="$(cd "$(dirname "$0")/.." && pwd)"
+cd "$ROOT_DIR"
+
+if [ "$(uname)" != "Linux" ]; then
+    echo "⚠ 非 Linux 平台，跳过测试"
+    exit 0
+fi
+
+cargo build -q -p aegis-core-server -p aegis-term -p aegis-collector-cli -p aegis-verifier
+BIN="$ROOT_DIR/target/debug"
+
+export AEGIS_CORE_ADDR="127.0.0.1:7895"
+TMP_DIR="$(mktemp -d)"
+trap 'kill $SERVER_PID 2>/dev/null || true; rm -rf "$TMP_DIR"' EXIT
+
+"$BIN/aegis-core-server" linux test "$TMP_DIR" &
+SERVER_PID=$!
+sleep 1
+
+echo "1. 在 aegis-term 中运行 shell..."
+set +e
+printf 'echo aegis-term-marker\nsleep 1.5\nexit 7\n' | "$BIN/aegis-term" --chunk-secs 1 -- /bin/sh >/dev/null
+EXIT_CODE=$?
+set -e
+if [ "$EXIT_CODE" -ne 7 ]; then
+    echo "❌ 退出码未透传：$EXIT_CODE"
+    exit 1
+fi
+echo "✓ shell 退出码透传"
+
+"$BIN/aegis-collector-cli" stop "test"
+wait "$SERVER_PID"
+
+BUNDLE=$(ls -d "$TMP_DIR"/Evidence_* | head -1)
+EVENTS="$BUNDLE/events.jsonl"
+CAST=$(cd "$BUNDLE" && ls files/term/*.cast | head -1)
+
+echo "2. 检查事件与 cast 文件..."
+for expected in '"terminal_session_started"' '"terminal_chunk"' '"terminal_session_ended"' '"kind":"terminal_cast"'; do
+    if ! grep -q "$expected" "$EVENTS"; then
+        echo "❌ 缺少事件: $expected"
+        cat "$EVENTS"
+        exit 1
+    fi
+done
+CHUNKS=$(grep -c '"terminal_chunk"' "$EVENTS")
+if [ "$CHUNKS" -lt 2 ]; then
+    echo "❌ 期望多个 terminal_chunk，实际 $CHUNKS"
+    exit 1
+fi
+if ! head -1 "$BUNDLE/$CAST" | grep -q '"version":2'; then
+    echo "❌ cast 头部不是 asciicast v2"
+    exit 1
+fi
+echo "✓ $CHUNKS 个分块，asciicast v2 格式"
+
+echo "3. 验证与回放..."
+"$BIN/aegis-verifier" verify "$BUNDLE"
+if ! "$BIN/aegis-verifier" replay "$BUNDLE" "$CAST" --speed 0 | grep -q "aegis-term-marker"; then
+    echo "❌ 回放输出不含会话内容"
+    exit 1
+fi
+echo "✓ 回放输出正确"
+
+echo "4. 篡改检测..."
+sed -i 's/aegis-term-marker/aegis-term-MARKER/' "$BUNDLE/$CAST"
+if "$BIN/aegis-verifier" verify "$BUNDLE" 2>/dev/null; then
+    echo "❌ 篡改 cast 后仍通过验证"
+    exit 1
+fi
+echo "✓ 篡改被检测到"
+
+echo ""
+echo "=== 终端会话采集测试通过 ==="